@@ -0,0 +1,158 @@
+use crate::Diagnostic;
+use std::{fmt::Display, str::FromStr};
+
+/**
+ * The words of a line, the keyword first. Tokens are byte ranges of the line,
+ * columns are only counted for diagnostics.
+ */
+pub struct Tokens<'a> {
+    pub line: usize,
+    content: &'a str,
+    spans: Vec<(usize, usize)>,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(line: usize, content: &'a str) -> Self {
+        Self::reuse(line, content, Vec::new())
+    }
+    /**
+     * Split a line into the buffer of a previous line, see `into_buffer`.
+     */
+    pub fn reuse(line: usize, content: &'a str, mut spans: Vec<(usize, usize)>) -> Self {
+        spans.clear();
+        spans.extend(content.split_whitespace().map(|token| {
+            let start = token.as_ptr() as usize - content.as_ptr() as usize;
+            (start, start + token.len())
+        }));
+        Self {
+            line,
+            content,
+            spans,
+        }
+    }
+    pub fn into_buffer(self) -> Vec<(usize, usize)> {
+        self.spans
+    }
+    /** number of tokens, with the keyword */
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+    pub fn get(&self, i: usize) -> Option<&'a str> {
+        self.spans
+            .get(i)
+            .map(|&(start, end)| &self.content[start..end])
+    }
+    /** the first token, empty on an empty line */
+    pub fn keyword(&self) -> &'a str {
+        self.get(0).unwrap_or_default()
+    }
+    /** the tokens from `i` on */
+    pub fn tokens_from(&self, i: usize) -> impl Iterator<Item = &'a str> + '_ {
+        (i..self.len()).filter_map(|i| self.get(i))
+    }
+    /** the tokens from `i` on joined by spaces, for names which may contain spaces */
+    pub fn rest(&self, i: usize) -> String {
+        self.tokens_from(i).collect::<Vec<_>>().join(" ")
+    }
+    /** column of a token, missing tokens are after the end of the line */
+    pub fn column(&self, i: usize) -> usize {
+        let offset = self
+            .spans
+            .get(i)
+            .map_or(self.content.trim_end().len(), |(start, _)| *start);
+        self.content[..offset].chars().count() + 1
+    }
+    pub fn error(&self, i: usize, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.line, self.column(i), message)
+    }
+    /** get a required token */
+    pub fn text(&self, i: usize) -> Result<&'a str, Diagnostic> {
+        self.get(i)
+            .ok_or_else(|| self.error(i, format!("missing argument {i} of `{}`", self.keyword())))
+    }
+    /** parse a required token */
+    pub fn parse<T: FromStr>(&self, i: usize) -> Result<T, Diagnostic>
+    where
+        T::Err: Display,
+    {
+        let text = self.text(i)?;
+        text.parse()
+            .map_err(|err| self.error(i, format!("invalid value `{text}`: {err}")))
+    }
+    /** parse an optional token */
+    pub fn parse_or<T: FromStr>(&self, i: usize, default: T) -> Result<T, Diagnostic>
+    where
+        T::Err: Display,
+    {
+        match self.get(i) {
+            Some(_) => self.parse(i),
+            None => Ok(default),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_count_characters_from_one() {
+        let tokens = Tokens::new(3, "  usemtl\tmétal  rouge ");
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens.keyword(), "usemtl");
+        assert_eq!(tokens.column(0), 3);
+        assert_eq!(tokens.column(1), 10);
+        /* the accent is one character but two bytes */
+        assert_eq!(tokens.column(2), 17);
+        /* missing tokens are right after the last one */
+        assert_eq!(tokens.column(3), 22);
+        let error = tokens.error(2, "no such material");
+        assert_eq!(error, Diagnostic::new(3, 17, "no such material"));
+        assert_eq!(error.to_string(), "3:17: no such material");
+    }
+
+    #[test]
+    fn empty_lines() {
+        let tokens = Tokens::new(1, "   ");
+        assert!(tokens.is_empty());
+        assert_eq!(tokens.keyword(), "");
+        assert_eq!(tokens.column(0), 1);
+        assert_eq!(tokens.rest(0), "");
+    }
+
+    #[test]
+    fn rest_joins_the_tokens_by_single_spaces() {
+        let tokens = Tokens::new(1, "newmtl  my   red\tpaint");
+        assert_eq!(tokens.rest(1), "my red paint");
+        assert_eq!(tokens.rest(3), "paint");
+        assert_eq!(tokens.rest(4), "");
+        assert_eq!(tokens.tokens_from(2).collect::<Vec<_>>(), ["red", "paint"]);
+    }
+
+    #[test]
+    fn parse_required_and_optional_tokens() {
+        let tokens = Tokens::new(7, "size 640 x");
+        assert_eq!(tokens.parse::<usize>(1), Ok(640));
+        assert_eq!(tokens.parse_or(3, 480usize), Ok(480));
+
+        let error = tokens.parse_or(2, 480usize).unwrap_err();
+        assert_eq!((error.line, error.column), (7, 10));
+        assert!(error.message.starts_with("invalid value `x`"), "{error}");
+
+        let error = tokens.parse::<usize>(3).unwrap_err();
+        assert_eq!(error.column, 11);
+        assert_eq!(error.message, "missing argument 3 of `size`");
+    }
+
+    #[test]
+    fn buffers_are_reused() {
+        let tokens = Tokens::new(1, "a b c");
+        let buffer = tokens.into_buffer();
+        let tokens = Tokens::reuse(2, "d", buffer);
+        assert_eq!((tokens.len(), tokens.line), (1, 2));
+        assert_eq!(tokens.get(1), None);
+    }
+}
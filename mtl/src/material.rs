@@ -1,7 +1,124 @@
-#[derive(Clone, Copy, Debug)]
-pub enum Material {
-    Ns(f64),
-    Ni(f64),
-    Kd(f64, f64, f64),
-    D(f64),
+pub type Color = (f64, f64, f64);
+
+/**
+ * A texture map and its options.
+ */
+#[derive(Clone, Debug)]
+pub struct TextureMap {
+    pub path: String,
+    /** horizontal texture blending, `-blendu` */
+    pub blend_u: bool,
+    /** vertical texture blending, `-blendv` */
+    pub blend_v: bool,
+    /** bump multiplier, `-bm` */
+    pub bump_multiplier: f64,
+    /** sharpness of mip-map boundaries, `-boost` */
+    pub boost: f64,
+    /** color correction, `-cc` */
+    pub color_correction: bool,
+    /** clamp texture coordinates instead of repeating the texture, `-clamp` */
+    pub clamp: bool,
+    /** channel of scalar maps (r, g, b, m, l or z), `-imfchan` */
+    pub channel: Option<char>,
+    /** base and gain of the values, `-mm` */
+    pub base_gain: (f64, f64),
+    /** offset of the texture coordinates, `-o` */
+    pub offset: Color,
+    /** scale of the texture coordinates, `-s` */
+    pub scale: Color,
+    /** turbulence of the texture coordinates, `-t` */
+    pub turbulence: Color,
+    /** resolution of the texture, `-texres` */
+    pub resolution: Option<usize>,
+    /** mapping of reflection maps (sphere, cube_top...), `-type` */
+    pub reflection_type: Option<String>,
+}
+
+impl Default for TextureMap {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            blend_u: true,
+            blend_v: true,
+            bump_multiplier: 1.,
+            boost: 0.,
+            color_correction: false,
+            clamp: false,
+            channel: None,
+            base_gain: (0., 1.),
+            offset: (0., 0., 0.),
+            scale: (1., 1., 1.),
+            turbulence: (0., 0., 0.),
+            resolution: None,
+            reflection_type: None,
+        }
+    }
+}
+
+/**
+ * A material of a `.mtl` file, parameters missing from the file are `None`.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct Material {
+    pub name: String,
+    /** ambient color */
+    pub ka: Option<Color>,
+    /** diffuse color */
+    pub kd: Option<Color>,
+    /** specular color */
+    pub ks: Option<Color>,
+    /** emissive color */
+    pub ke: Option<Color>,
+    /** transmission filter */
+    pub tf: Option<Color>,
+    /** specular exponent from 0 to 1000 */
+    pub ns: Option<f64>,
+    /** index of refraction */
+    pub ni: Option<f64>,
+    /** dissolve, 1 is opaque */
+    pub d: Option<f64>,
+    /** `-halo` option of `d`, dissolve depends on the viewing angle */
+    pub halo: bool,
+    /** transparency, the opposite of `d` */
+    pub tr: Option<f64>,
+    /** illumination model from 0 to 10 */
+    pub illum: Option<u32>,
+    /** sharpness of reflections from 0 to 1000 */
+    pub sharpness: Option<f64>,
+
+    pub map_ka: Option<TextureMap>,
+    pub map_kd: Option<TextureMap>,
+    pub map_ks: Option<TextureMap>,
+    pub map_ke: Option<TextureMap>,
+    pub map_ns: Option<TextureMap>,
+    pub map_d: Option<TextureMap>,
+    /** height map from `map_Bump` or `bump` */
+    pub map_bump: Option<TextureMap>,
+    /** displacement map */
+    pub disp: Option<TextureMap>,
+    /** decal map */
+    pub decal: Option<TextureMap>,
+    /** reflection maps, a cube map has one map for every face */
+    pub refl: Vec<TextureMap>,
+
+    /* physically based rendering extension */
+    /** roughness */
+    pub pr: Option<f64>,
+    /** metallic */
+    pub pm: Option<f64>,
+    /** sheen */
+    pub ps: Option<f64>,
+    /** clearcoat thickness */
+    pub pc: Option<f64>,
+    /** clearcoat roughness */
+    pub pcr: Option<f64>,
+    /** anisotropy */
+    pub aniso: Option<f64>,
+    /** anisotropy rotation */
+    pub anisor: Option<f64>,
+    pub map_pr: Option<TextureMap>,
+    pub map_pm: Option<TextureMap>,
+    pub map_ps: Option<TextureMap>,
+    /** tangent space normal map */
+    pub norm: Option<TextureMap>,
 }
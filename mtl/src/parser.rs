@@ -1,62 +1,323 @@
-use crate::material::Material;
-use std::collections::HashMap;
-
-fn find_next_token(tokens: &[&str]) -> usize {
-    let mut t = 1;
-    while t < tokens.len() && tokens[t].is_empty() {
-        t += 1;
-    }
-    t
-}
-
-pub fn parse_mtl(mtl_content: &str) -> HashMap<String, Vec<Material>> {
-    let mtl_content = mtl_content.replace('\n', " ");
-    let tokens = mtl_content.split(' ').collect::<Vec<&str>>();
-    let mut materials: HashMap<String, Vec<Material>> = HashMap::new();
-    let mut current_mtl = String::new();
-
-    let mut t = 0;
-    while t < tokens.len() {
-        match tokens[t] {
-            "Ns" => {
-                t += find_next_token(&tokens[t..]);
-                let value = tokens[t].parse().unwrap();
-                let ns = Material::Ns(value);
-                materials.get_mut(&current_mtl).unwrap().push(ns);
-            }
-            "Ni" => {
-                t += find_next_token(&tokens[t..]);
-                let value = tokens[t].parse().unwrap();
-                let ns = Material::Ni(value);
-                materials.get_mut(&current_mtl).unwrap().push(ns);
-            }
-            "Kd" => {
-                t += find_next_token(&tokens[t..]);
-                let r = tokens[t].parse().unwrap();
-
-                t += find_next_token(&tokens[t..]);
-                let g = tokens[t].parse().unwrap();
-
-                t += find_next_token(&tokens[t..]);
-                let b = tokens[t].parse().unwrap();
-                let ns = Material::Kd(r, g, b);
-                materials.get_mut(&current_mtl).unwrap().push(ns);
-            }
-            "d" => {
-                t += find_next_token(&tokens[t..]);
-                let d = tokens[t].parse().unwrap();
-
-                let ns = Material::D(d);
-                materials.get_mut(&current_mtl).unwrap().push(ns);
-            }
-            "newmtl" => {
-                t += find_next_token(&tokens[t..]);
-                current_mtl = tokens[t].parse().unwrap();
-                materials.insert(current_mtl.clone(), Vec::new());
-            }
-            _ => {}
+use crate::material::{Color, Material, TextureMap};
+use diagnostic::{Diagnostic, Tokens};
+use std::collections::{HashMap, HashSet};
+
+type ParseResult<T> = Result<T, Diagnostic>;
+
+fn parse_f64(s: &Tokens, i: usize) -> ParseResult<f64> {
+    match s.get(i) {
+        Some(v) => v
+            .parse()
+            .map_err(|_| s.error(i, format!("expected a number, found `{v}`"))),
+        None => Err(s.error(i, format!("missing number after `{}`", s.keyword()))),
+    }
+}
+
+/**
+ * Parse `r [g b]`, `g` and `b` default to `r` if they are missing.
+ *
+ * `xyz x [y z]` is read as it is, `spectral` curves are not supported.
+ */
+fn parse_color(s: &Tokens, warnings: &mut Vec<Diagnostic>) -> ParseResult<Option<Color>> {
+    let start = match s.get(1) {
+        Some("xyz") => 2,
+        Some("spectral") => {
+            warnings.push(s.error(1, "spectral colors are not supported"));
+            return Ok(None);
+        }
+        _ => 1,
+    };
+    let r = parse_f64(s, start)?;
+    let g = match s.get(start + 1) {
+        Some(_) => parse_f64(s, start + 1)?,
+        None => r,
+    };
+    let b = match s.get(start + 2) {
+        Some(_) => parse_f64(s, start + 2)?,
+        None => r,
+    };
+    Ok(Some((r, g, b)))
+}
+
+fn parse_switch(s: &Tokens, i: usize) -> ParseResult<bool> {
+    match s.get(i) {
+        Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        _ => Err(s.error(
+            i,
+            format!(
+                "expected `on` or `off` after `{}`",
+                s.get(i - 1).unwrap_or_default()
+            ),
+        )),
+    }
+}
+
+/**
+ * Parse numbers starting at argument `*i` into `values`, missing numbers keep their values.
+ * The first number is required.
+ */
+fn parse_numbers(s: &Tokens, i: &mut usize, values: &mut [f64]) -> ParseResult<()> {
+    for (n, value) in values.iter_mut().enumerate() {
+        match s.get(*i).and_then(|v| v.parse().ok()) {
+            Some(v) => {
+                *value = v;
+                *i += 1;
+            }
+            None if n == 0 => return Err(s.error(*i, "expected a number")),
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+fn parse_vector(s: &Tokens, i: &mut usize, default: Color) -> ParseResult<Color> {
+    let mut values = [default.0, default.1, default.2];
+    parse_numbers(s, i, &mut values)?;
+    Ok((values[0], values[1], values[2]))
+}
+
+/**
+ * Parse the options and the file name of a texture map, the file name may contain spaces.
+ */
+fn parse_map(s: &Tokens, warnings: &mut Vec<Diagnostic>) -> ParseResult<TextureMap> {
+    let mut map = TextureMap::default();
+    let mut i = 1;
+    while let Some(option) = s.get(i)
+        && option.starts_with('-')
+    {
+        i += 1;
+        match option {
+            "-blendu" => map.blend_u = parse_switch(s, i)?,
+            "-blendv" => map.blend_v = parse_switch(s, i)?,
+            "-cc" => map.color_correction = parse_switch(s, i)?,
+            "-clamp" => map.clamp = parse_switch(s, i)?,
+            "-bm" => map.bump_multiplier = parse_f64(s, i)?,
+            "-boost" => map.boost = parse_f64(s, i)?,
+            "-texres" => {
+                map.resolution = Some(
+                    s.get(i)
+                        .and_then(|v| v.parse().ok())
+                        .ok_or_else(|| s.error(i, "expected a resolution after `-texres`"))?,
+                )
+            }
+            "-imfchan" => match s.get(i) {
+                Some(channel @ ("r" | "g" | "b" | "m" | "l" | "z")) => {
+                    map.channel = channel.chars().next()
+                }
+                _ => return Err(s.error(i, "expected r, g, b, m, l or z after `-imfchan`")),
+            },
+            "-type" => {
+                map.reflection_type = Some(
+                    s.get(i)
+                        .ok_or_else(|| s.error(i, "missing type after `-type`"))?
+                        .to_string(),
+                )
+            }
+            "-mm" => {
+                let mut values = [map.base_gain.0, map.base_gain.1];
+                parse_numbers(s, &mut i, &mut values)?;
+                map.base_gain = (values[0], values[1]);
+            }
+            "-o" => map.offset = parse_vector(s, &mut i, map.offset)?,
+            "-s" => map.scale = parse_vector(s, &mut i, map.scale)?,
+            "-t" => map.turbulence = parse_vector(s, &mut i, map.turbulence)?,
+            /* unknown options are skipped with their numeric arguments, the file name is kept */
+            _ => {
+                warnings.push(s.error(i - 1, format!("unknown texture option `{option}`")));
+                while s.get(i + 1).is_some() && s.get(i).is_some_and(|v| v.parse::<f64>().is_ok()) {
+                    i += 1;
+                }
+                continue;
+            }
+        }
+        /* options with a single argument */
+        if !matches!(option, "-mm" | "-o" | "-s" | "-t") {
+            i += 1;
         }
-        t += find_next_token(&tokens[t..]);
     }
-    materials
+    if s.get(i).is_none() {
+        return Err(s.error(i, format!("missing file name after `{}`", s.keyword())));
+    }
+    map.path = s.rest(i);
+    Ok(map)
+}
+
+/**
+ * Parse a `.mtl` file, materials are indexed by name.
+ *
+ * Malformed values are errors. Unknown statements and unsupported options are skipped,
+ * they are returned as warnings, every unknown statement is only reported once.
+ */
+pub fn parse_mtl(mtl_content: &str) -> ParseResult<(HashMap<String, Material>, Vec<Diagnostic>)> {
+    let mut materials = HashMap::new();
+    let mut warnings = Vec::new();
+    let mut unknown = HashSet::new();
+    let mut current: Option<Material> = None;
+
+    for (number, line) in mtl_content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let s = Tokens::new(number + 1, line);
+        if s.is_empty() {
+            continue;
+        }
+
+        if s.keyword() == "newmtl" {
+            if s.len() < 2 {
+                return Err(s.error(1, "missing material name after `newmtl`"));
+            }
+            if let Some(material) = current.take() {
+                materials.insert(material.name.clone(), material);
+            }
+            current = Some(Material {
+                name: s.rest(1),
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(material) = current.as_mut() else {
+            return Err(Diagnostic::new(
+                s.line,
+                1,
+                format!("`{}` before the first `newmtl`", s.keyword()),
+            ));
+        };
+
+        let w = &mut warnings;
+        match s.keyword() {
+            "Ka" => material.ka = parse_color(&s, w)?,
+            "Kd" => material.kd = parse_color(&s, w)?,
+            "Ks" => material.ks = parse_color(&s, w)?,
+            "Ke" => material.ke = parse_color(&s, w)?,
+            "Tf" => material.tf = parse_color(&s, w)?,
+            "Ns" => material.ns = Some(parse_f64(&s, 1)?),
+            "Ni" => material.ni = Some(parse_f64(&s, 1)?),
+            "d" => match s.get(1) {
+                Some("-halo") => {
+                    material.halo = true;
+                    material.d = Some(parse_f64(&s, 2)?);
+                }
+                _ => material.d = Some(parse_f64(&s, 1)?),
+            },
+            "Tr" => material.tr = Some(parse_f64(&s, 1)?),
+            "illum" => {
+                material.illum = Some(
+                    s.get(1)
+                        .and_then(|v| v.parse().ok())
+                        .ok_or_else(|| s.error(1, "expected an illumination model from 0 to 10"))?,
+                )
+            }
+            "sharpness" => material.sharpness = Some(parse_f64(&s, 1)?),
+            "map_Ka" => material.map_ka = Some(parse_map(&s, w)?),
+            "map_Kd" => material.map_kd = Some(parse_map(&s, w)?),
+            "map_Ks" => material.map_ks = Some(parse_map(&s, w)?),
+            "map_Ke" => material.map_ke = Some(parse_map(&s, w)?),
+            "map_Ns" => material.map_ns = Some(parse_map(&s, w)?),
+            "map_d" => material.map_d = Some(parse_map(&s, w)?),
+            "map_Bump" | "map_bump" | "bump" => material.map_bump = Some(parse_map(&s, w)?),
+            "disp" => material.disp = Some(parse_map(&s, w)?),
+            "decal" => material.decal = Some(parse_map(&s, w)?),
+            "refl" => material.refl.push(parse_map(&s, w)?),
+            "Pr" => material.pr = Some(parse_f64(&s, 1)?),
+            "Pm" => material.pm = Some(parse_f64(&s, 1)?),
+            "Ps" => material.ps = Some(parse_f64(&s, 1)?),
+            "Pc" => material.pc = Some(parse_f64(&s, 1)?),
+            "Pcr" => material.pcr = Some(parse_f64(&s, 1)?),
+            "aniso" => material.aniso = Some(parse_f64(&s, 1)?),
+            "anisor" => material.anisor = Some(parse_f64(&s, 1)?),
+            "map_Pr" => material.map_pr = Some(parse_map(&s, w)?),
+            "map_Pm" => material.map_pm = Some(parse_map(&s, w)?),
+            "map_Ps" => material.map_ps = Some(parse_map(&s, w)?),
+            "norm" => material.norm = Some(parse_map(&s, w)?),
+            keyword => {
+                if unknown.insert(keyword.to_string()) {
+                    w.push(Diagnostic::new(
+                        s.line,
+                        1,
+                        format!("unknown statement `{keyword}`"),
+                    ));
+                }
+            }
+        }
+    }
+    if let Some(material) = current {
+        materials.insert(material.name.clone(), material);
+    }
+    Ok((materials, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> (HashMap<String, Material>, Vec<Diagnostic>) {
+        parse_mtl(content).unwrap()
+    }
+
+    #[test]
+    fn map_options_and_file_names_with_spaces() {
+        let (materials, warnings) = parse(
+            "newmtl a\n\
+             map_Kd -s 2 3 -clamp on -mm 0.1 0.9 -imfchan r -bm 0.5 my tex.ppm\n",
+        );
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let map = materials["a"].map_kd.as_ref().unwrap();
+        assert_eq!(map.scale, (2., 3., 1.));
+        assert!(map.clamp);
+        assert_eq!(map.base_gain, (0.1, 0.9));
+        assert_eq!(map.channel, Some('r'));
+        assert_eq!(map.bump_multiplier, 0.5);
+        assert_eq!(map.path, "my tex.ppm");
+    }
+
+    #[test]
+    fn unknown_map_options_skip_their_arguments() {
+        let (materials, warnings) = parse("newmtl a\nmap_Kd -foo 2 tex.ppm\nmap_Ks -bar 7\n");
+        assert_eq!(materials["a"].map_kd.as_ref().unwrap().path, "tex.ppm");
+        /* a number after the last option is the file name */
+        assert_eq!(materials["a"].map_ks.as_ref().unwrap().path, "7");
+        assert_eq!(warnings.len(), 2);
+        assert_eq!((warnings[0].line, warnings[0].column), (2, 8));
+    }
+
+    #[test]
+    fn map_without_file_name() {
+        let err = parse_mtl("newmtl a\nmap_Kd -clamp on\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(err.message.contains("missing file name"), "{}", err.message);
+    }
+
+    #[test]
+    fn dissolve_with_halo() {
+        let (materials, _) = parse("newmtl a\nd -halo 0.5\nnewmtl b\nd 0.7\n");
+        assert!(materials["a"].halo);
+        assert_eq!(materials["a"].d, Some(0.5));
+        assert!(!materials["b"].halo);
+        assert_eq!(materials["b"].d, Some(0.7));
+    }
+
+    #[test]
+    fn statements_belong_to_the_last_newmtl() {
+        let (materials, _) = parse("newmtl red\nKd 1 0 0\nnewmtl grey\nKd 0.5\n");
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials["red"].kd, Some((1., 0., 0.)));
+        assert_eq!(materials["grey"].kd, Some((0.5, 0.5, 0.5)));
+
+        let err = parse_mtl("# comment\nKd 1 0 0\nnewmtl a\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+    }
+
+    #[test]
+    fn unknown_statements_are_warned_once() {
+        let (_, warnings) = parse("newmtl a\nfoo 1\nfoo 2\nbar\nnewmtl b\nfoo 3\n");
+        let messages = warnings
+            .iter()
+            .map(|w| w.message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            ["unknown statement `foo`", "unknown statement `bar`"]
+        );
+        assert_eq!(warnings[0].line, 2);
+    }
 }
@@ -1,8 +1,55 @@
-use crate::vertex::Vertex;
-use mtl::material::Material;
+use std::ops::Range;
 
-#[derive(Default, Debug)]
+/**
+ * A corner of an element, the indexes start at 0 and point into the lists of the model.
+ */
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Corner {
+    pub vertex: u32,
+    pub texcoord: Option<u32>,
+    pub normal: Option<u32>,
+}
+
+/**
+ * The object and the groups elements belong to, set by `o` and `g`.
+ */
+#[derive(Clone, Default, Debug)]
+pub struct Group {
+    pub object: Option<String>,
+    /** an element may belong to several groups, `default` if no group is set */
+    pub names: Vec<String>,
+}
+
+/*
+ * Elements keep their corners in the corner buffer of the model, so they need no allocation.
+ */
+
+#[derive(Clone, Default, Debug)]
 pub struct Face {
-    pub vertexes: Vec<Vertex>,
-    pub materials: Vec<Material>,
+    /** range of the corners in the model */
+    pub corners: Range<u32>,
+    /** smoothing group set by `s`, 0 if smoothing is off */
+    pub smoothing: u32,
+    /** index of the group in the model */
+    pub group: u32,
+    /** index of the material name set by `usemtl` */
+    pub material: Option<u32>,
+    /** line of the `f` statement, to report problems of the face */
+    pub line: u32,
+}
+
+/** a polyline, `l` */
+#[derive(Clone, Default, Debug)]
+pub struct Line {
+    pub corners: Range<u32>,
+    pub group: u32,
+    pub material: Option<u32>,
+}
+
+/** points, `p`, their corners only have vertexes */
+#[derive(Clone, Default, Debug)]
+pub struct Point {
+    pub corners: Range<u32>,
+    pub group: u32,
+    pub material: Option<u32>,
 }
@@ -0,0 +1 @@
+pub use diagnostic::Diagnostic;
@@ -1,3 +1,5 @@
 pub mod element;
+pub mod error;
+pub mod model;
 pub mod parser;
 pub mod vertex;
@@ -0,0 +1,58 @@
+use crate::{
+    element::{Corner, Face, Group, Line, Point},
+    vertex::{Normal, TexCoord, Vertex},
+};
+use std::ops::Range;
+
+/**
+ * The content of a `.obj` file, elements refer to the buffers of vertexes and corners by index.
+ */
+#[derive(Clone, Default, Debug)]
+pub struct Obj {
+    pub vertexes: Vec<Vertex>,
+    /** colors of the vertexes given by `v x y z r g b`, empty if no vertex has a color */
+    pub colors: Vec<(f64, f64, f64)>,
+    pub texcoords: Vec<TexCoord>,
+    pub normals: Vec<Normal>,
+    /** corners of every element, one after another */
+    pub corners: Vec<Corner>,
+    pub faces: Vec<Face>,
+    pub lines: Vec<Line>,
+    pub points: Vec<Point>,
+    pub groups: Vec<Group>,
+    /** material names used by `usemtl` */
+    pub materials: Vec<String>,
+    /** material libraries in the order of `mtllib`, as written in the file */
+    pub mtllibs: Vec<String>,
+}
+
+impl Obj {
+    pub fn corners(&self, range: &Range<u32>) -> &[Corner] {
+        &self.corners[range.start as usize..range.end as usize]
+    }
+    pub fn vertex(&self, corner: &Corner) -> &Vertex {
+        &self.vertexes[corner.vertex as usize]
+    }
+    pub fn texcoord(&self, corner: &Corner) -> Option<&TexCoord> {
+        corner.texcoord.map(|i| &self.texcoords[i as usize])
+    }
+    pub fn normal(&self, corner: &Corner) -> Option<&Normal> {
+        corner.normal.map(|i| &self.normals[i as usize])
+    }
+    /** texture coordinates are only used if every corner has them */
+    pub fn has_texcoords(&self, face: &Face) -> bool {
+        self.corners(&face.corners)
+            .iter()
+            .all(|c| c.texcoord.is_some())
+    }
+    /** normals are only used if every corner has them */
+    pub fn has_normals(&self, face: &Face) -> bool {
+        self.corners(&face.corners)
+            .iter()
+            .all(|c| c.normal.is_some())
+    }
+    /** name of the material of an element */
+    pub fn material(&self, material: Option<u32>) -> Option<&str> {
+        material.map(|i| self.materials[i as usize].as_str())
+    }
+}
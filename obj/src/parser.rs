@@ -1,95 +1,713 @@
-use crate::{element::Face, vertex::Vertex};
-use mtl::material::Material;
-use std::{any::Any, collections::HashMap};
+use crate::{
+    element::{Corner, Face, Group, Line, Point},
+    model::Obj,
+    vertex::{Normal, TexCoord, Vertex},
+};
+use diagnostic::{Diagnostic, Tokens};
+use std::{collections::HashSet, io::BufRead, ops::Range, panic};
 
-fn find_next_token(tokens: &[&str]) -> usize {
-    let mut t = 1;
-    while t < tokens.len() && tokens[t].is_empty() {
-        t += 1;
+type ParseResult<T> = Result<T, Diagnostic>;
+
+/** color of the vertexes without color, in a file where other vertexes have one */
+const WHITE: (f64, f64, f64) = (1., 1., 1.);
+
+/**
+ * Parse up to `N` numbers after the keyword, at least `min` numbers are required.
+ * Returns the numbers and how many were given.
+ */
+fn numbers<const N: usize>(s: &Tokens, min: usize) -> ParseResult<([f64; N], usize)> {
+    let given = s.len() - 1;
+    if given < min {
+        return Err(s.error(
+            s.len(),
+            format!("`{}` needs at least {min} numbers", s.keyword()),
+        ));
     }
-    t
+    let mut values = [0.; N];
+    let count = given.min(N);
+    for (i, value) in values.iter_mut().enumerate().take(count) {
+        let v = s.get(i + 1).unwrap_or_default();
+        *value = v
+            .parse()
+            .map_err(|_| s.error(i + 1, format!("expected a number, found `{v}`")))?;
+    }
+    Ok((values, count))
 }
 
-fn is_keyword(param: &str) -> bool {
-    let types = ["v", "f", "o", "usemtl"];
-    for i in types {
-        if param == i {
-            return true;
-        }
-    }
-    false
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    vertexes: usize,
+    texcoords: usize,
+    normals: usize,
+}
+
+/**
+ * Resolve a 1-based index, negative indexes count back from the last element read so far.
+ */
+fn resolve_index(param: &str, count: usize, kind: &str) -> Result<u32, String> {
+    let index = param
+        .parse::<isize>()
+        .map_err(|_| format!("expected an index, found `{param}`"))?;
+    let resolved = if index > 0 && index as usize <= count {
+        index as usize - 1
+    } else if index < 0 && index.unsigned_abs() <= count {
+        count - index.unsigned_abs()
+    } else {
+        return Err(format!(
+            "{kind} index {index} is out of range, {count} are defined"
+        ));
+    };
+    u32::try_from(resolved).map_err(|_| {
+        format!(
+            "{kind} index {index} is above the largest index {}",
+            u32::MAX
+        )
+    })
 }
 
-fn get_v_index(param: &str) -> usize {
-    param
-        .split('/')
-        .collect::<Vec<&str>>()
-        .first()
-        .unwrap()
-        .parse::<usize>()
-        .unwrap()
+/** parse `v`, `v/vt`, `v//vn` or `v/vt/vn` */
+fn parse_corner(param: &str, counts: &Counts) -> Result<Corner, String> {
+    let mut indexes = param.split('/');
+    let vertex = resolve_index(
+        indexes.next().unwrap_or_default(),
+        counts.vertexes,
+        "vertex",
+    )?;
+    let texcoord = match indexes.next() {
+        None | Some("") => None,
+        Some(vt) => Some(resolve_index(vt, counts.texcoords, "texture coordinate")?),
+    };
+    let normal = match indexes.next() {
+        None | Some("") => None,
+        Some(vn) => Some(resolve_index(vn, counts.normals, "normal")?),
+    };
+    Ok(Corner {
+        vertex,
+        texcoord,
+        normal,
+    })
 }
 
-pub fn parse_obj(obj_content: &str) -> Vec<Box<dyn Any>> {
-    let obj_content = obj_content.replace('\n', " ");
-    let tokens = obj_content.split(' ').collect::<Vec<&str>>();
-    let mut objects: Vec<Box<dyn Any>> = Vec::new();
-    let mut vertexes = Vec::new();
-    let mut usemtl = String::new();
-    let mut mtllib: HashMap<String, Vec<Material>> = std::collections::HashMap::new();
+/**
+ * Which statements a parser handles, a file parsed in parallel is read twice:
+ * once sequentially for the state and once in chunks for the elements.
+ */
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Full,
+    /** statements changing the state, vertexes are only counted and elements are skipped */
+    State,
+    /** vertexes and elements, groups and materials come from a parser in `State` mode */
+    Elements,
+}
 
-    let mut t = 0;
-    while t < tokens.len() {
-        match tokens[t] {
+/** state between statements, saved at the start of every chunk */
+#[derive(Clone, Copy, Default)]
+struct State {
+    /** elements read so far in the whole file, indexes of a chunk are global */
+    counts: Counts,
+    group: u32,
+    /** groups created by `o` and `g` so far, the next group gets the next index */
+    groups_created: u32,
+    smoothing: u32,
+    material: Option<u32>,
+}
+
+struct Parser<'t> {
+    mode: Mode,
+    obj: Obj,
+    state: State,
+    /** groups and materials of the whole file, in `Elements` mode */
+    tables: Option<&'t Obj>,
+    warnings: Vec<Diagnostic>,
+    unknown: HashSet<String>,
+}
+
+impl<'t> Parser<'t> {
+    fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            obj: Obj {
+                groups: vec![Group {
+                    object: None,
+                    names: vec!["default".into()],
+                }],
+                ..Default::default()
+            },
+            state: State::default(),
+            tables: None,
+            warnings: Vec::new(),
+            unknown: HashSet::new(),
+        }
+    }
+    fn for_chunk(state: State, tables: &'t Obj) -> Self {
+        Self {
+            mode: Mode::Elements,
+            obj: Obj::default(),
+            state,
+            tables: Some(tables),
+            warnings: Vec::new(),
+            unknown: HashSet::new(),
+        }
+    }
+    /** parse the corners of an element into the corner buffer, at least `min` are required */
+    fn corners(&mut self, s: &Tokens, min: usize) -> ParseResult<Range<u32>> {
+        if s.len() - 1 < min {
+            return Err(s.error(
+                s.len(),
+                format!("`{}` needs at least {min} vertexes", s.keyword()),
+            ));
+        }
+        let start = self.obj.corners.len() as u32;
+        for (i, param) in s.tokens_from(1).enumerate() {
+            let corner = parse_corner(param, &self.state.counts).map_err(|m| s.error(i + 1, m))?;
+            self.obj.corners.push(corner);
+        }
+        let end = u32::try_from(self.obj.corners.len())
+            .map_err(|_| s.error(1, format!("more than {} corners in the file", u32::MAX)))?;
+        Ok(start..end)
+    }
+    fn statement(&mut self, s: &Tokens) -> ParseResult<()> {
+        let elements = self.mode != Mode::State;
+        let state = &mut self.state;
+
+        match s.keyword() {
             "v" => {
-                let mut last_vertex = 0;
-                let mut v = Vertex::default();
-                loop {
-                    t += find_next_token(&tokens[t..]);
-                    if !tokens[t].is_empty() {
-                        match last_vertex {
-                            0 => v.x = tokens[t].parse::<f64>().unwrap(),
-                            1 => v.y = tokens[t].parse::<f64>().unwrap(),
-                            2 => v.z = tokens[t].parse::<f64>().unwrap(),
-                            _ => {}
-                        }
-                        last_vertex += 1;
-                        if last_vertex == 3 {
-                            break;
-                        }
+                state.counts.vertexes += 1;
+                if elements {
+                    let (values, count) = numbers::<6>(s, 3)?;
+                    /* a 4th value alone is the weight of rational curves, 6 values include a color */
+                    if count == 6 {
+                        self.obj.colors.resize(self.obj.vertexes.len(), WHITE);
+                        self.obj.colors.push((values[3], values[4], values[5]));
                     }
+                    self.obj.vertexes.push(Vertex {
+                        x: values[0],
+                        y: values[1],
+                        z: values[2],
+                    });
                 }
-                vertexes.push(v);
             }
-            "f" => {
-                let mut f = Face::default();
-                if !usemtl.is_empty() {
-                    f.materials = mtllib.get(&usemtl).unwrap().clone();
+            "vt" => {
+                state.counts.texcoords += 1;
+                if elements {
+                    let (values, count) = numbers::<2>(s, 1)?;
+                    self.obj.texcoords.push(TexCoord {
+                        u: values[0],
+                        v: if count > 1 { values[1] } else { 0. },
+                    });
                 }
-                loop {
-                    t += find_next_token(&tokens[t..]);
-                    if t == tokens.len() || is_keyword(tokens[t]) {
-                        t -= 1;
-                        break;
-                    }
-                    if !tokens[t].is_empty() {
-                        f.vertexes
-                            .push(vertexes[get_v_index(tokens[t]) - 1].clone());
+            }
+            "vn" => {
+                state.counts.normals += 1;
+                if elements {
+                    let (values, _) = numbers::<3>(s, 3)?;
+                    self.obj.normals.push(Normal {
+                        x: values[0],
+                        y: values[1],
+                        z: values[2],
+                    });
+                }
+            }
+            "f" if elements => {
+                let face = Face {
+                    corners: self.corners(s, 3)?,
+                    smoothing: self.state.smoothing,
+                    group: self.state.group,
+                    material: self.state.material,
+                    line: s.line as u32,
+                };
+                self.obj.faces.push(face);
+            }
+            "l" if elements => {
+                let line = Line {
+                    corners: self.corners(s, 2)?,
+                    group: self.state.group,
+                    material: self.state.material,
+                };
+                self.obj.lines.push(line);
+            }
+            "p" if elements => {
+                let point = Point {
+                    corners: self.corners(s, 1)?,
+                    group: self.state.group,
+                    material: self.state.material,
+                };
+                self.obj.points.push(point);
+            }
+            "f" | "l" | "p" => {}
+            keyword @ ("o" | "g") => {
+                if self.mode != Mode::Elements {
+                    let mut next = self.obj.groups[state.group as usize].clone();
+                    let names = s.tokens_from(1).collect::<Vec<_>>();
+                    if keyword == "o" {
+                        next.object = (!names.is_empty()).then(|| names.join(" "));
+                    } else if names.is_empty() {
+                        next.names = vec!["default".into()];
+                    } else {
+                        next.names = names.iter().map(|name| name.to_string()).collect();
                     }
+                    self.obj.groups.push(next);
                 }
-                objects.push(Box::new(f));
+                state.groups_created += 1;
+                state.group = state.groups_created;
             }
-            "mtllib" => {
-                t += find_next_token(&tokens[t..]);
-                mtllib = mtl::parser::parse_mtl(&std::fs::read_to_string(tokens[t]).unwrap());
+            /* `s off` and `s 0` turn smoothing off */
+            "s" => {
+                state.smoothing = match s.tokens_from(1).next() {
+                    Some("off") => 0,
+                    Some(v) => v.parse().map_err(|_| {
+                        s.error(
+                            1,
+                            format!("expected a smoothing group or `off`, found `{v}`"),
+                        )
+                    })?,
+                    None => return Err(s.error(1, "missing smoothing group after `s`")),
+                }
             }
             "usemtl" => {
-                t += find_next_token(&tokens[t..]);
-                usemtl = tokens[t].to_owned();
+                if s.len() == 1 {
+                    return Err(s.error(1, "missing material name after `usemtl`"));
+                }
+                let name = s.tokens_from(1).collect::<Vec<_>>().join(" ");
+                let materials = match self.tables {
+                    Some(tables) => &tables.materials,
+                    None => &self.obj.materials,
+                };
+                state.material = Some(match materials.iter().position(|m| *m == name) {
+                    Some(i) => i as u32,
+                    None => {
+                        self.obj.materials.push(name);
+                        self.obj.materials.len() as u32 - 1
+                    }
+                });
+            }
+            _ if self.mode == Mode::Elements => {}
+            "mtllib" => {
+                if s.len() == 1 {
+                    return Err(s.error(1, "missing file name after `mtllib`"));
+                }
+                self.obj
+                    .mtllibs
+                    .extend(s.tokens_from(1).map(|lib| lib.to_string()))
+            }
+            keyword => {
+                if self.unknown.insert(keyword.to_string()) {
+                    self.warnings.push(Diagnostic::new(
+                        s.line,
+                        1,
+                        format!("unsupported statement `{keyword}`"),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+    fn finish(mut self) -> (Obj, Vec<Diagnostic>) {
+        if !self.obj.colors.is_empty() {
+            self.obj.colors.resize(self.obj.vertexes.len(), WHITE);
+        }
+        (self.obj, self.warnings)
+    }
+}
+
+/**
+ * Feed the logical lines of a file to a parser, comments are stripped and
+ * lines ending with `\` continue on the next line. Columns of continued lines count from the
+ * first line. Returns the number of the line after the last one.
+ */
+fn parse_lines(
+    mut reader: impl BufRead,
+    parser: &mut Parser,
+    first_line: usize,
+) -> ParseResult<usize> {
+    /* buffers are reused between lines */
+    let mut content = String::new();
+    let mut spans = Vec::new();
+    let mut number = first_line;
+    loop {
+        content.clear();
+        let line = number;
+        loop {
+            let start = content.len();
+            let read = reader
+                .read_line(&mut content)
+                .map_err(|err| Diagnostic::new(number, 1, err.to_string()))?;
+            if read == 0 {
+                break;
+            }
+            number += 1;
+            if let Some(comment) = content[start..].find('#') {
+                content.truncate(start + comment);
+            }
+            content.truncate(content.trim_end().len());
+            if content.ends_with('\\') {
+                content.pop();
+                content.push(' ');
+            } else {
+                break;
+            }
+        }
+        if number == line {
+            return Ok(number);
+        }
+
+        /* the state is not changed by vertexes and elements, their keywords are enough */
+        let Some(keyword) = content.split_whitespace().next() else {
+            continue;
+        };
+        let end = if parser.mode == Mode::State
+            && matches!(keyword, "v" | "vt" | "vn" | "f" | "l" | "p")
+        {
+            keyword.as_ptr() as usize - content.as_ptr() as usize + keyword.len()
+        } else {
+            content.len()
+        };
+        let tokens = Tokens::reuse(line, &content[..end], spans);
+        parser.statement(&tokens)?;
+        spans = tokens.into_buffer();
+    }
+}
+
+/**
+ * Parse a `.obj` file in memory, see `read_obj`.
+ */
+pub fn parse_obj(obj_content: &str) -> ParseResult<(Obj, Vec<Diagnostic>)> {
+    read_obj(obj_content.as_bytes())
+}
+
+/**
+ * Parse a `.obj` file line by line, the file is never loaded whole.
+ * Material libraries are only listed, they are not read.
+ *
+ * Malformed values and invalid indexes are errors. Unsupported statements are skipped,
+ * they are returned as warnings, every statement is only reported once.
+ */
+pub fn read_obj(reader: impl BufRead) -> ParseResult<(Obj, Vec<Diagnostic>)> {
+    let mut parser = Parser::new(Mode::Full);
+    parse_lines(reader, &mut parser, 1)?;
+    Ok(parser.finish())
+}
+
+/** whether a line continues on the next line */
+fn is_continued(line: &str) -> bool {
+    line.split('#')
+        .next()
+        .unwrap_or_default()
+        .trim_end()
+        .ends_with('\\')
+}
+
+/**
+ * Split a file into about `count` chunks at the starts of lines, continued lines stay in one chunk.
+ */
+fn split_chunks(content: &str, count: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    for i in 1..count {
+        let mut end = (content.len() * i / count).max(start);
+        loop {
+            match content.as_bytes()[end..].iter().position(|b| *b == b'\n') {
+                Some(newline) => end += newline + 1,
+                None => {
+                    end = content.len();
+                    break;
+                }
             }
-            _ => {}
+            let line = content[..end - 1].rsplit('\n').next().unwrap_or_default();
+            if !is_continued(line) {
+                break;
+            }
+        }
+        if end > start {
+            chunks.push(&content[start..end]);
+            start = end;
+        }
+    }
+    if start < content.len() {
+        chunks.push(&content[start..]);
+    }
+    chunks
+}
+
+/**
+ * Append a chunk parsed in `Elements` mode, its corners move behind the corners of `obj`.
+ * Returns false if the corners don't fit 32 bit indexes.
+ */
+fn join_chunk(obj: &mut Obj, chunk: Obj) -> bool {
+    if u32::try_from(obj.corners.len() + chunk.corners.len()).is_err() {
+        return false;
+    }
+    let offset = obj.corners.len() as u32;
+    let shift = |range: Range<u32>| range.start + offset..range.end + offset;
+    if !chunk.colors.is_empty() {
+        obj.colors.resize(obj.vertexes.len(), WHITE);
+        obj.colors.extend(chunk.colors);
+    }
+    obj.vertexes.extend(chunk.vertexes);
+    obj.texcoords.extend(chunk.texcoords);
+    obj.normals.extend(chunk.normals);
+    obj.corners.extend(chunk.corners);
+    obj.faces.extend(chunk.faces.into_iter().map(|face| Face {
+        corners: shift(face.corners),
+        ..face
+    }));
+    obj.lines.extend(chunk.lines.into_iter().map(|line| Line {
+        corners: shift(line.corners),
+        ..line
+    }));
+    obj.points
+        .extend(chunk.points.into_iter().map(|point| Point {
+            corners: shift(point.corners),
+            ..point
+        }));
+    true
+}
+
+/**
+ * Parse a `.obj` file in memory with `threads` threads, the result is the same as `parse_obj`.
+ *
+ * The state set by `o`, `g`, `s` and `usemtl` is read sequentially first,
+ * then chunks of the file are parsed in parallel and joined in order.
+ */
+pub fn parse_obj_parallel(
+    obj_content: &str,
+    threads: usize,
+) -> ParseResult<(Obj, Vec<Diagnostic>)> {
+    let chunks = split_chunks(obj_content, threads);
+    if chunks.len() <= 1 {
+        return parse_obj(obj_content);
+    }
+
+    /* state at the start of every chunk */
+    let mut prepass = Parser::new(Mode::State);
+    let mut starts = Vec::new();
+    let mut line = 1;
+    for chunk in &chunks {
+        starts.push((line, prepass.state));
+        match parse_lines(chunk.as_bytes(), &mut prepass, line) {
+            Ok(next) => line = next,
+            /* the sequential parser reports the first error of the file */
+            Err(_) => return parse_obj(obj_content),
         }
-        t += find_next_token(&tokens[t..]);
     }
-    objects
+    let (tables, warnings) = prepass.finish();
+
+    let parts = std::thread::scope(|scope| {
+        let handles = chunks
+            .iter()
+            .zip(&starts)
+            .map(|(chunk, (line, state))| {
+                let tables = &tables;
+                scope.spawn(move || {
+                    let mut parser = Parser::for_chunk(*state, tables);
+                    parse_lines(chunk.as_bytes(), &mut parser, *line).map(|_| parser.obj)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|panic| panic::resume_unwind(panic))
+            })
+            .collect::<ParseResult<Vec<Obj>>>()
+    });
+    let Ok(parts) = parts else {
+        return parse_obj(obj_content);
+    };
+
+    let mut obj = tables;
+    for part in parts {
+        if !join_chunk(&mut obj, part) {
+            /* the sequential parser reports where the indexes overflow */
+            return parse_obj(obj_content);
+        }
+    }
+    if !obj.colors.is_empty() {
+        obj.colors.resize(obj.vertexes.len(), WHITE);
+    }
+    Ok((obj, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Obj {
+        parse_obj(content).unwrap().0
+    }
+
+    fn vertexes(obj: &Obj, corners: &Range<u32>) -> Vec<u32> {
+        obj.corners(corners).iter().map(|c| c.vertex).collect()
+    }
+
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+    #[test]
+    fn negative_indexes_count_back_from_the_last_vertex() {
+        let obj = parse(&format!("{TRIANGLE}f -3 -2 -1\nv 1 1 0\nf 1 -3 -1\n"));
+        assert_eq!(vertexes(&obj, &obj.faces[0].corners), [0, 1, 2]);
+        assert_eq!(vertexes(&obj, &obj.faces[1].corners), [0, 1, 3]);
+    }
+
+    #[test]
+    fn corners_with_texture_coordinates_and_normals() {
+        let obj = parse(&format!(
+            "{TRIANGLE}vt 0 0\nvt 1\nvn 0 0 1\nf 1/1/1 2//1 3/2\n"
+        ));
+        let corners = obj.corners(&obj.faces[0].corners);
+        let indexes = corners
+            .iter()
+            .map(|c| (c.vertex, c.texcoord, c.normal))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            indexes,
+            [
+                (0, Some(0), Some(0)),
+                (1, None, Some(0)),
+                (2, Some(1), None)
+            ]
+        );
+        assert_eq!(obj.texcoords[1].v, 0.);
+        assert!(!obj.has_texcoords(&obj.faces[0]));
+        assert!(!obj.has_normals(&obj.faces[0]));
+    }
+
+    #[test]
+    fn lines_and_points() {
+        let obj = parse(&format!("{TRIANGLE}l 1 2 3\np 3 1\n"));
+        assert_eq!(vertexes(&obj, &obj.lines[0].corners), [0, 1, 2]);
+        assert_eq!(vertexes(&obj, &obj.points[0].corners), [2, 0]);
+        assert!(obj.faces.is_empty());
+
+        let err = parse_obj(&format!("{TRIANGLE}l 1\n")).unwrap_err();
+        assert_eq!(err.line, 4);
+    }
+
+    #[test]
+    fn groups_objects_and_smoothing_groups() {
+        let obj = parse(&format!(
+            "{TRIANGLE}f 1 2 3\no box\ng a b\ns 2\nusemtl red\nf 1 2 3\ng\ns off\nf 1 2 3\n"
+        ));
+        let group = |face: &Face| &obj.groups[face.group as usize];
+        let [first, second, third] = &obj.faces[..] else {
+            panic!("{:?}", obj.faces);
+        };
+        assert_eq!(group(first).names, ["default"]);
+        assert_eq!(group(first).object, None);
+        assert_eq!(group(second).names, ["a", "b"]);
+        assert_eq!(group(second).object.as_deref(), Some("box"));
+        assert_eq!(group(third).names, ["default"]);
+        assert_eq!(group(third).object.as_deref(), Some("box"));
+        assert_eq!(
+            [first.smoothing, second.smoothing, third.smoothing],
+            [0, 2, 0]
+        );
+        assert_eq!(obj.material(first.material), None);
+        assert_eq!(obj.material(third.material), Some("red"));
+        assert_eq!(third.line, 12);
+    }
+
+    #[test]
+    fn vertex_colors() {
+        let obj = parse("v 0 0 0\nv 1 0 0 1 0 0\nv 0 1 0\n");
+        assert_eq!(obj.colors, [WHITE, (1., 0., 0.), WHITE]);
+        assert!(parse(TRIANGLE).colors.is_empty());
+    }
+
+    #[test]
+    fn out_of_range_indexes() {
+        let err = parse_obj("v 0 0 0\nf 1 2 -2\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 5));
+        assert_eq!(err.message, "vertex index 2 is out of range, 1 are defined");
+
+        let err = parse_obj(&format!("{TRIANGLE}f 0 1 2\n")).unwrap_err();
+        assert_eq!((err.line, err.column), (4, 3));
+
+        let err = parse_obj(&format!("{TRIANGLE}vt 0 0\nf 1/2 2/1 3/1\n")).unwrap_err();
+        assert!(
+            err.message.starts_with("texture coordinate index 2"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn unsupported_statements_are_warned_once() {
+        let (_, warnings) = parse_obj(&format!("{TRIANGLE}curv 0 1\ncurv 1 2\n")).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 4);
+    }
+
+    /** parse with threads, checking that the content is split */
+    fn parse_parallel(content: &str, threads: usize) -> ParseResult<(Obj, Vec<Diagnostic>)> {
+        assert!(split_chunks(content, threads).len() > 1);
+        parse_obj_parallel(content, threads)
+    }
+
+    fn assert_parallel_matches(content: &str, threads: usize) {
+        let (obj, warnings) = parse_obj(content).unwrap();
+        let (parallel, parallel_warnings) = parse_parallel(content, threads).unwrap();
+        assert_eq!(format!("{parallel:?}"), format!("{obj:?}"));
+        let lines =
+            |warnings: &[Diagnostic]| warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(lines(&parallel_warnings), lines(&warnings));
+    }
+
+    #[test]
+    fn parallel_continuation_across_chunks() {
+        let face = format!("f{}1 2 3\n", " 1 2 3 \\\n".repeat(30));
+        let content = format!("{TRIANGLE}{face}{face}");
+        let count = 4;
+        /* without the continuations, some chunk would end inside a face */
+        let split_inside = (1..count).any(|i| {
+            let end = content.len() * i / count;
+            let line_end = end + content[end..].find('\n').unwrap();
+            is_continued(content[..line_end].rsplit('\n').next().unwrap())
+        });
+        assert!(split_inside);
+        for chunk in split_chunks(&content, count) {
+            assert!(!is_continued(
+                chunk.trim_end_matches('\n').rsplit('\n').next().unwrap()
+            ));
+        }
+        assert_parallel_matches(&content, count);
+    }
+
+    #[test]
+    fn parallel_negative_indexes_across_chunks() {
+        let mut content = String::from("v 0 0 0\nvn 0 0 1\n");
+        for i in 0..40 {
+            content += &format!("v {i} 0 0\nv {i} 1 0 0.5 0.5 0.5\nvt {i} 0\ng g{i}\n");
+            content += "usemtl m\ns 1\nf -3/-1/-1 -2/-1/-1 -1/-1/-1\n";
+        }
+        content += "v 9 9 9\nf -1 1 2\n";
+        assert_parallel_matches(&content, 3);
+        let (obj, _) = parse_parallel(&content, 3).unwrap();
+        let last = obj.faces.last().unwrap();
+        assert_eq!(vertexes(&obj, &last.corners), [81, 0, 1]);
+        assert_eq!(vertexes(&obj, &obj.faces[39].corners), [78, 79, 80]);
+    }
+
+    #[test]
+    fn parallel_line_numbers_of_errors_and_warnings() {
+        let mut content = format!("{TRIANGLE}curv 0 1\n");
+        content += &"f 1 2 3\n".repeat(30);
+        content += "curv 1 2\nf 1 \\\n2 \\\n3\nf 1 2 \\\n4\n";
+        let err = parse_parallel(&content, 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            parse_obj(&content).unwrap_err().to_string()
+        );
+        assert_eq!((err.line, err.column), (39, 8));
+
+        let content = content.replace("f 1 2 \\\n4\n", "f 1 2 \\\n3\n");
+        assert_parallel_matches(&content, 4);
+        let (obj, warnings) = parse_parallel(&content, 4).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 4);
+        assert_eq!(obj.faces.last().unwrap().line, 39);
+        assert_eq!(obj.faces[obj.faces.len() - 2].line, 36);
+    }
 }
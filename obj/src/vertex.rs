@@ -4,3 +4,16 @@ pub struct Vertex {
     pub y: f64,
     pub z: f64,
 }
+
+#[derive(Clone, Default, Debug)]
+pub struct TexCoord {
+    pub u: f64,
+    pub v: f64,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Normal {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
@@ -0,0 +1,236 @@
+use crate::pixel_count;
+use std::io::{Error, ErrorKind, Result as IOResult};
+
+/**
+ * High dynamic range RGB image, rows are stored from top to bottom.
+ */
+#[derive(Default, Debug, Clone)]
+pub struct FloatImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 3]>,
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_owned())
+}
+
+impl FloatImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![[0.; 3]; width * height],
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn get_pixel(&self, x: usize, y: usize) -> [f32; 3] {
+        self.pixels[y * self.width + x]
+    }
+    pub fn set_pixel(&mut self, x: usize, y: usize, pixel: [f32; 3]) {
+        self.pixels[y * self.width + x] = pixel;
+    }
+    /**
+     * Load a `.pfm` (Portable Float Map) or `.hdr` (Radiance RGBE) image.
+     */
+    pub fn load(path: &str) -> IOResult<Self> {
+        let data = std::fs::read(path)?;
+        if data.starts_with(b"PF") || data.starts_with(b"Pf") {
+            Self::from_pfm(&data)
+        } else if data.starts_with(b"#?") {
+            Self::from_hdr(&data)
+        } else {
+            Err(invalid_data("unknown HDR image format"))
+        }
+    }
+    /**
+     * Split the first `count` whitespace separated tokens from a header.
+     */
+    fn header_tokens(data: &[u8], count: usize) -> IOResult<(Vec<String>, usize)> {
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        while tokens.len() < count {
+            while pos < data.len() && data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(invalid_data("truncated header"));
+            }
+            tokens.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+        }
+        /* a single whitespace character ends the header */
+        Ok((tokens, pos + 1))
+    }
+    fn from_pfm(data: &[u8]) -> IOResult<Self> {
+        let (tokens, offset) = Self::header_tokens(data, 4)?;
+        let channels = if tokens[0] == "PF" { 3 } else { 1 };
+        let size = |t: &str| {
+            t.parse::<usize>()
+                .map_err(|_| invalid_data("invalid header"))
+        };
+        let (width, height) = (size(&tokens[1])?, size(&tokens[2])?);
+        /* negative scale means little endian */
+        let little_endian = tokens[3]
+            .parse::<f64>()
+            .map_err(|_| invalid_data("invalid header"))?
+            < 0.;
+
+        let body = &data[offset.min(data.len())..];
+        let length = pixel_count(width, height)?
+            .checked_mul(channels * 4)
+            .ok_or_else(|| invalid_data("image too large"))?;
+        if body.len() < length {
+            return Err(invalid_data("truncated PFM data"));
+        }
+
+        let mut image = Self::new(width, height);
+        let mut values = body.chunks_exact(4).map(|b| {
+            let b = [b[0], b[1], b[2], b[3]];
+            if little_endian {
+                f32::from_le_bytes(b)
+            } else {
+                f32::from_be_bytes(b)
+            }
+        });
+        /* rows of PFM are stored from bottom to top */
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let pixel = if channels == 3 {
+                    [
+                        values.next().unwrap(),
+                        values.next().unwrap(),
+                        values.next().unwrap(),
+                    ]
+                } else {
+                    [values.next().unwrap(); 3]
+                };
+                image.set_pixel(x, y, pixel);
+            }
+        }
+        Ok(image)
+    }
+    fn from_hdr(data: &[u8]) -> IOResult<Self> {
+        /* header lines end with an empty line */
+        let mut pos = 0;
+        loop {
+            let end = data[pos..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .ok_or_else(|| invalid_data("truncated header"))?;
+            let line = &data[pos..pos + end];
+            pos += end + 1;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid_data("unsupported HDR format"));
+            }
+        }
+
+        let end = data[pos..]
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| invalid_data("missing resolution"))?;
+        let resolution = String::from_utf8_lossy(&data[pos..pos + end]).into_owned();
+        pos += end + 1;
+        let resolution = resolution.split_whitespace().collect::<Vec<&str>>();
+        if resolution.len() != 4 || resolution[0] != "-Y" || resolution[2] != "+X" {
+            return Err(invalid_data("unsupported HDR orientation"));
+        }
+        let height = resolution[1]
+            .parse()
+            .map_err(|_| invalid_data("invalid resolution"))?;
+        let width = resolution[3]
+            .parse()
+            .map_err(|_| invalid_data("invalid resolution"))?;
+        /* the shortest scanline is either flat or run-length encoded with the longest runs */
+        pixel_count(width, height)?;
+        let shortest = width.saturating_mul(4).min(4 + 8 * width.div_ceil(128));
+        if height
+            .checked_mul(shortest)
+            .is_none_or(|length| length > data.len() - pos)
+        {
+            return Err(invalid_data("truncated HDR data"));
+        }
+
+        let mut image = Self::new(width, height);
+        let mut scanline = vec![[0u8; 4]; width];
+        for y in 0..height {
+            pos = Self::read_hdr_scanline(data, pos, &mut scanline)?;
+            for (x, rgbe) in scanline.iter().enumerate() {
+                let pixel = if rgbe[3] == 0 {
+                    [0.; 3]
+                } else {
+                    let scale = 2f32.powi(rgbe[3] as i32 - 136);
+                    [
+                        rgbe[0] as f32 * scale,
+                        rgbe[1] as f32 * scale,
+                        rgbe[2] as f32 * scale,
+                    ]
+                };
+                image.set_pixel(x, y, pixel);
+            }
+        }
+        Ok(image)
+    }
+    /**
+     * Read a flat or run-length encoded RGBE scanline, returns the position after it.
+     */
+    fn read_hdr_scanline(data: &[u8], mut pos: usize, scanline: &mut [[u8; 4]]) -> IOResult<usize> {
+        let truncated = || invalid_data("truncated HDR data");
+        let width = scanline.len();
+        let header = data.get(pos..pos + 4).ok_or_else(truncated)?;
+
+        /* flat scanline */
+        if !(8..0x8000).contains(&width)
+            || header[0] != 2
+            || header[1] != 2
+            || header[2] & 0x80 != 0
+        {
+            for pixel in scanline.iter_mut() {
+                let rgbe = data.get(pos..pos + 4).ok_or_else(truncated)?;
+                pixel.copy_from_slice(rgbe);
+                pos += 4;
+            }
+            return Ok(pos);
+        }
+
+        pos += 4;
+        /* each channel is encoded separately */
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = *data.get(pos).ok_or_else(truncated)? as usize;
+                pos += 1;
+                if count > 128 {
+                    let count = count - 128;
+                    let value = *data.get(pos).ok_or_else(truncated)?;
+                    pos += 1;
+                    for pixel in scanline.iter_mut().skip(x).take(count) {
+                        pixel[channel] = value;
+                    }
+                    x += count;
+                } else {
+                    for pixel in scanline.iter_mut().skip(x).take(count) {
+                        pixel[channel] = *data.get(pos).ok_or_else(truncated)?;
+                        pos += 1;
+                    }
+                    x += count;
+                }
+                if count == 0 {
+                    return Err(invalid_data("invalid run length"));
+                }
+            }
+        }
+        Ok(pos)
+    }
+}
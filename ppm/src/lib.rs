@@ -1,4 +1,20 @@
-use std::io::{Result as IOResult, Write};
+mod float;
+
+pub use float::FloatImage;
+use std::io::{Error, ErrorKind, Result as IOResult, Write};
+
+/**
+ * Number of pixels of an image read from a header, images without pixels are rejected.
+ */
+fn pixel_count(width: usize, height: usize) -> IOResult<usize> {
+    let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_owned());
+    if width == 0 || height == 0 {
+        return Err(invalid("image without pixels"));
+    }
+    width
+        .checked_mul(height)
+        .ok_or_else(|| invalid("image too large"))
+}
 
 pub enum PPMType {
     P3,
@@ -32,6 +48,104 @@ impl Image {
             pixels: vec![Pixel::default(); width * height],
         }
     }
+    /**
+     * Open a P3 or P6 image, the size is read from the header.
+     */
+    pub fn open(path: &str) -> IOResult<Self> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, msg.to_owned());
+        let data = std::fs::read(path)?;
+
+        /* read the magic number, width, height and max value, skipping comments */
+        let mut header = Vec::new();
+        let mut pos = 0;
+        while header.len() < 4 {
+            while pos < data.len() && data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if pos < data.len() && data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            if start == pos {
+                return Err(invalid("truncated PPM header"));
+            }
+            header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+        }
+        let parse = |s: &str| {
+            s.parse::<usize>()
+                .map_err(|_| invalid("invalid PPM header"))
+        };
+        let (width, height, max) = (parse(&header[1])?, parse(&header[2])?, parse(&header[3])?);
+        if !(1..=65535).contains(&max) {
+            return Err(invalid("invalid PPM max value"));
+        }
+        let scale = |v: usize| {
+            if v > max {
+                return Err(invalid("PPM sample above the max value"));
+            }
+            Ok((v * 255 / max) as u8)
+        };
+        let count = pixel_count(width, height)?;
+
+        let pixels = match header[0].as_str() {
+            "P3" => {
+                /* every sample takes at least a digit and a whitespace */
+                let body = data.get(pos..).unwrap_or_default();
+                if count
+                    .checked_mul(6)
+                    .is_none_or(|length| length > body.len())
+                {
+                    return Err(invalid("truncated PPM data"));
+                }
+                let body = String::from_utf8_lossy(body);
+                let mut values = body.split_ascii_whitespace().map(parse);
+                let mut next = || {
+                    let value = values.next().unwrap_or(Err(invalid("truncated PPM data")));
+                    scale(value?)
+                };
+                (0..count)
+                    .map(|_| Ok(Pixel::new(next()?, next()?, next()?)))
+                    .collect::<IOResult<Vec<Pixel>>>()?
+            }
+            "P6" => {
+                /* a single whitespace character ends the header */
+                let body = data.get(pos + 1..).unwrap_or_default();
+                let bytes = if max < 256 { 1 } else { 2 };
+                if count
+                    .checked_mul(3 * bytes)
+                    .is_none_or(|length| length > body.len())
+                {
+                    return Err(invalid("truncated PPM data"));
+                }
+                body.chunks_exact(3 * bytes)
+                    .take(count)
+                    .map(|rgb| {
+                        let channel = |c: usize| {
+                            scale(if bytes == 1 {
+                                rgb[c] as usize
+                            } else {
+                                (rgb[2 * c] as usize) << 8 | rgb[2 * c + 1] as usize
+                            })
+                        };
+                        Ok(Pixel::new(channel(0)?, channel(1)?, channel(2)?))
+                    })
+                    .collect::<IOResult<Vec<Pixel>>>()?
+            }
+            _ => return Err(invalid("unsupported PPM type")),
+        };
+        let image = Self {
+            width,
+            height,
+            pixels,
+        };
+        Ok(image)
+    }
     pub fn save(&self, path: &str, ppm_type: PPMType) -> IOResult<()> {
         let mut f = std::fs::File::create(path)?;
         match ppm_type {
@@ -96,4 +210,23 @@ impl Image {
     pub fn set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
         self.pixels[y * self.width + x] = pixel;
     }
+    pub fn get_pixel(&self, x: usize, y: usize) -> &Pixel {
+        &self.pixels[y * self.width + x]
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /**
+     * Copy another image into this image, with its top left corner at (x, y).
+     */
+    pub fn paste(&mut self, image: &Image, x: usize, y: usize) {
+        for j in 0..image.height.min(self.height.saturating_sub(y)) {
+            for i in 0..image.width.min(self.width.saturating_sub(x)) {
+                self.set_pixel(x + i, y + j, image.get_pixel(i, j).clone());
+            }
+        }
+    }
 }
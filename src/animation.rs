@@ -0,0 +1,179 @@
+use crate::{
+    point::Point,
+    script::{Instruction, Script},
+    transform::Transform,
+    vector::Vector3D,
+};
+use std::{collections::HashMap, str::FromStr};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /** cubic Bezier curve with automatic handles, the curve passes through every key */
+    Bezier,
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "bezier" => Ok(Self::Bezier),
+            _ => Err(format!("unknown interpolation `{s}`")),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum KeyProperty {
+    Camera,
+    CameraTarget,
+    CameraFov,
+    /** transformation of an object group */
+    Transform(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Keyframe {
+    pub frame: f64,
+    pub values: Vec<f64>,
+    /** interpolation of the segment starting at this key */
+    pub interpolation: Interpolation,
+}
+
+/**
+ * Animated values of one property, keys are sorted by frame.
+ */
+#[derive(Default, Debug)]
+pub struct Track {
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn insert(&mut self, key: Keyframe) {
+        let index = self.keys.partition_point(|k| k.frame < key.frame);
+        self.keys.insert(index, key);
+    }
+    /**
+     * Get the slope of the track at key `i`, from its neighbours.
+     */
+    fn slope(&self, i: usize, component: usize) -> f64 {
+        let prev = &self.keys[i.saturating_sub(1)];
+        let next = &self.keys[(i + 1).min(self.keys.len() - 1)];
+        if next.frame > prev.frame {
+            (next.values[component] - prev.values[component]) / (next.frame - prev.frame)
+        } else {
+            0.
+        }
+    }
+    /**
+     * Evaluate the track at a frame, values are held constant outside the keyed range.
+     */
+    pub fn sample(&self, frame: f64) -> Option<Vec<f64>> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+        if frame <= first.frame {
+            return Some(first.values.clone());
+        }
+        if frame >= last.frame {
+            return Some(last.values.clone());
+        }
+
+        let i = self.keys.partition_point(|k| k.frame <= frame) - 1;
+        let (k0, k1) = (&self.keys[i], &self.keys[i + 1]);
+        let length = k1.frame - k0.frame;
+        let t = (frame - k0.frame) / length;
+
+        Some(
+            (0..k0.values.len().min(k1.values.len()))
+                .map(|c| {
+                    let (v0, v1) = (k0.values[c], k1.values[c]);
+                    match k0.interpolation {
+                        Interpolation::Linear => v0 + t * (v1 - v0),
+                        Interpolation::Bezier => {
+                            /* control points placed along the slopes at one third of the segment */
+                            let p1 = v0 + self.slope(i, c) * length / 3.;
+                            let p2 = v1 - self.slope(i + 1, c) * length / 3.;
+                            let s = 1. - t;
+                            s.powi(3) * v0
+                                + 3. * s.powi(2) * t * p1
+                                + 3. * s * t.powi(2) * p2
+                                + t.powi(3) * v1
+                        }
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+/**
+ * All animated properties of a script.
+ */
+#[derive(Default, Debug)]
+pub struct Animation {
+    pub camera: Track,
+    pub camera_target: Track,
+    pub camera_fov: Track,
+    /** transformations of object groups, by group name */
+    pub transforms: HashMap<String, Track>,
+}
+
+impl Animation {
+    pub fn from_script(script: &Script) -> Self {
+        let mut animation = Self::default();
+        for i in &script.instructions {
+            if let Instruction::Key {
+                frame,
+                interpolation,
+                property,
+                values,
+            } = i
+            {
+                let key = Keyframe {
+                    frame: *frame,
+                    values: values.clone(),
+                    interpolation: *interpolation,
+                };
+                match property {
+                    KeyProperty::Camera => animation.camera.insert(key),
+                    KeyProperty::CameraTarget => animation.camera_target.insert(key),
+                    KeyProperty::CameraFov => animation.camera_fov.insert(key),
+                    KeyProperty::Transform(group) => animation
+                        .transforms
+                        .entry(group.to_owned())
+                        .or_default()
+                        .insert(key),
+                }
+            }
+        }
+        animation
+    }
+    pub fn camera(&self, frame: f64) -> Option<Point> {
+        self.camera
+            .sample(frame)
+            .map(|v| Point::new(v[0], v[1], v[2]))
+    }
+    pub fn camera_target(&self, frame: f64) -> Option<Point> {
+        self.camera_target
+            .sample(frame)
+            .map(|v| Point::new(v[0], v[1], v[2]))
+    }
+    /** vertical field of view in radians */
+    pub fn camera_fov(&self, frame: f64) -> Option<f64> {
+        self.camera_fov.sample(frame).map(|v| v[0].to_radians())
+    }
+    /**
+     * Get the transformation of a group, groups without keys are not transformed.
+     */
+    pub fn transform(&self, group: &str, frame: f64) -> Transform {
+        match self.transforms.get(group).and_then(|t| t.sample(frame)) {
+            Some(v) => Transform {
+                translate: Vector3D::new(v[0], v[1], v[2]),
+                rotate: Vector3D::new(v[3], v[4], v[5]),
+                scale: Vector3D::new(v[6], v[7], v[8]),
+            },
+            None => Transform::default(),
+        }
+    }
+}
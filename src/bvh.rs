@@ -1,47 +1,91 @@
-use crate::{objects::Object, point::Point, ray::Ray};
-use std::{
-    cmp::Ordering::{Greater, Less},
-    rc::Rc,
+use crate::{
+    objects::{HitRecord, Object, Primitive},
+    point::Point,
+    ray::Ray,
 };
 
-fn get_aabb(objects: &[Rc<dyn Object>]) -> (Point, Point) {
-    let mut aa = Point::new(objects[0].x_min(), objects[0].y_min(), objects[0].z_min());
-    let mut bb = Point::new(objects[0].x_max(), objects[0].y_max(), objects[0].z_max());
+fn get_aabb(objects: &[Primitive]) -> (Point, Point) {
+    let mut aabb = bounds(objects[0].object());
     for o in &objects[1..] {
-        if o.x_min() < aa.x() {
-            aa.set_x(o.x_min());
-        }
-        if o.y_min() < aa.y() {
-            aa.set_y(o.y_min());
-        }
-        if o.z_min() < aa.z() {
-            aa.set_z(o.z_min());
-        }
-        if o.x_max() > bb.x() {
-            bb.set_x(o.x_max());
-        }
-        if o.y_max() > bb.y() {
-            bb.set_y(o.y_max());
-        }
-        if o.z_max() > bb.z() {
-            bb.set_z(o.z_max());
+        aabb = union(&aabb, &bounds(o.object()));
+    }
+    aabb
+}
+
+/** the bounding box of an object as its minimum and maximum corner */
+pub fn bounds(o: &dyn Object) -> (Point, Point) {
+    (
+        Point::new(o.x_min(), o.y_min(), o.z_min()),
+        Point::new(o.x_max(), o.y_max(), o.z_max()),
+    )
+}
+
+pub fn union(a: &(Point, Point), b: &(Point, Point)) -> (Point, Point) {
+    (
+        Point::new(
+            a.0.x().min(b.0.x()),
+            a.0.y().min(b.0.y()),
+            a.0.z().min(b.0.z()),
+        ),
+        Point::new(
+            a.1.x().max(b.1.x()),
+            a.1.y().max(b.1.y()),
+            a.1.z().max(b.1.z()),
+        ),
+    )
+}
+
+/** maximum number of cut out surfaces a ray passes through within one object */
+const MAX_CUTOUTS: usize = 16;
+/** distance to move past a cut out hit */
+const CUTOUT_EPSILON: f64 = 1e-6;
+
+/**
+ * Hit an object, the hits cut out by the alpha mask of its material are skipped.
+ */
+fn hit_unmasked<'a>(ray: &Ray, object: &'a dyn Object, t_max: f64) -> Option<HitRecord<'a>> {
+    let mut t_min = 0.;
+    for _ in 0..MAX_CUTOUTS {
+        let hit = object.hit(ray, t_min, t_max)?;
+        if !hit.material.is_cut_out(&hit.texture_coord()) {
+            return Some(hit);
         }
+        /* continue behind the hit */
+        t_min = hit.t + CUTOUT_EPSILON;
     }
-    (aa, bb)
+    None
 }
 
-fn find_closest_hit(ray: &Ray, objects: &[Rc<dyn Object>]) -> Option<(f64, Rc<dyn Object>)> {
-    let mut closest = 0.;
-    let mut closest_object = None;
+/**
+ * Find the closest hit closer than `t_max` among objects, `hit` intersects an object closer than
+ * the distance it is given.
+ */
+fn closest<'a>(
+    objects: &'a [Primitive],
+    t_max: f64,
+    hit: &impl Fn(&'a dyn Object, f64) -> Option<HitRecord<'a>>,
+) -> Option<HitRecord<'a>> {
+    let mut closest: Option<HitRecord> = None;
     for object in objects {
-        if let Some(t) = object.hit(ray)
-            && (closest == 0. || t < closest)
-        {
-            closest = t;
-            closest_object = Some((t, Rc::clone(object)));
+        let t_max = closest.as_ref().map_or(t_max, |hit| hit.t);
+        if let Some(hit) = hit(object.object(), t_max) {
+            closest = Some(hit);
         }
     }
-    closest_object
+    closest
+}
+
+/**
+ * Find the closest hit closer than `t_max` among objects.
+ */
+pub fn find_closest_hit<'a>(
+    ray: &Ray,
+    objects: &'a [Primitive],
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    closest(objects, t_max, &|object, t_max| {
+        hit_unmasked(ray, object, t_max)
+    })
 }
 
 pub trait BoarderDedection {
@@ -53,104 +97,116 @@ pub trait BoarderDedection {
     fn z_min(&self) -> f64;
 }
 
+/**
+ * Implement the bounds of a shape by its cached bounding box `aabb`.
+ */
+macro_rules! bounds_from_aabb {
+    ($shape:ty) => {
+        impl $crate::bvh::BoarderDedection for $shape {
+            fn x_max(&self) -> f64 {
+                self.aabb.1.x()
+            }
+            fn x_min(&self) -> f64 {
+                self.aabb.0.x()
+            }
+            fn y_max(&self) -> f64 {
+                self.aabb.1.y()
+            }
+            fn y_min(&self) -> f64 {
+                self.aabb.0.y()
+            }
+            fn z_max(&self) -> f64 {
+                self.aabb.1.z()
+            }
+            fn z_min(&self) -> f64 {
+                self.aabb.0.z()
+            }
+        }
+    };
+}
+pub(crate) use bounds_from_aabb;
+
+/** number of objects a leaf of a BVH holds */
+pub const LEAF_CAPACITY: usize = 20;
+
 #[derive(Default)]
 pub struct BVHNode {
     pub nodes: Vec<BVHNode>,
     pub max: Point,
     pub min: Point,
-    pub objects: Vec<Rc<dyn Object>>,
+    pub objects: Vec<Primitive>,
 }
 
 impl BVHNode {
-    pub fn build(objects: &[Rc<dyn Object>], capability: usize) -> Self {
+    pub fn build(objects: &[Primitive], capability: usize) -> Self {
         /* calculate SAH cost */
-        fn calculate_cost(aa: &Point, bb: &Point, objects: usize) -> f64 {
+        fn calculate_cost((aa, bb): &(Point, Point), objects: usize) -> f64 {
             (((aa.x() - bb.x()) * (aa.y() - bb.y())).abs()
                 + ((aa.x() - bb.x()) * (aa.z() - bb.z())).abs()
                 + ((aa.y() - bb.y()) * (aa.z() - bb.z())).abs())
                 * objects as f64
         }
-        let mut objects = objects.to_vec();
         let mut node = Self::default();
-        (node.min, node.max) = get_aabb(&objects);
+        if objects.is_empty() {
+            return node;
+        }
+        (node.min, node.max) = get_aabb(objects);
         if objects.len() <= capability {
-            node.objects = objects;
-        } else {
-            let mut cost = 0.;
-            let mut left_objects = None;
-            let mut right_objects = None;
-
-            objects.sort_by(|a, b| {
-                if (a.x_min() + a.x_max()) / 2. > (b.x_min() + b.x_max()) / 2. {
-                    Greater
-                } else {
-                    Less
-                }
-            });
-            for i in 1..objects.len() {
-                let (aa, bb) = get_aabb(&objects[..i]);
-                let mut this_cost = calculate_cost(&aa, &bb, i);
-                let (aa, bb) = get_aabb(&objects[i..]);
-                this_cost += calculate_cost(&aa, &bb, objects.len() - i);
-                if this_cost < cost || cost == 0. {
-                    cost = this_cost;
-                    left_objects = Some(&objects[..i]);
-                    right_objects = Some(&objects[i..]);
-                }
-            }
+            node.objects = objects.to_vec();
+            return node;
+        }
 
-            let mut objects = objects.clone();
-            objects.sort_by(|a, b| {
-                if (a.y_min() + a.y_max()) / 2. > (b.y_min() + b.y_max()) / 2. {
-                    Greater
-                } else {
-                    Less
-                }
-            });
-            for i in 1..objects.len() {
-                let (aa, bb) = get_aabb(&objects[..i]);
-                let mut this_cost = calculate_cost(&aa, &bb, i);
-                let (aa, bb) = get_aabb(&objects[i..]);
-                this_cost += calculate_cost(&aa, &bb, objects.len() - i);
-                if this_cost < cost {
-                    cost = this_cost;
-                    left_objects = Some(&objects[..i]);
-                    right_objects = Some(&objects[i..]);
+        /* bounds are computed once, the splits sort indexes by the centers of the bounds */
+        let boxes = objects
+            .iter()
+            .map(|o| bounds(o.object()))
+            .collect::<Vec<_>>();
+        let sorted_by = |axis: usize| {
+            let center = |i: &usize| {
+                let (aa, bb) = (&boxes[*i].0.point_vec, &boxes[*i].1.point_vec);
+                match axis {
+                    0 => aa.x + bb.x,
+                    1 => aa.y + bb.y,
+                    _ => aa.z + bb.z,
                 }
-            }
+            };
+            let mut sorted = (0..objects.len()).collect::<Vec<_>>();
+            sorted.sort_by(|a, b| center(a).total_cmp(&center(b)));
+            sorted
+        };
 
-            let mut objects = objects.clone();
-            objects.sort_by(|a, b| {
-                if (a.z_min() + a.z_max()) / 2. > (b.z_min() + b.z_max()) / 2. {
-                    Greater
-                } else {
-                    Less
-                }
-            });
-            for i in 1..objects.len() {
-                let (aa, bb) = get_aabb(&objects[..i]);
-                let mut this_cost = calculate_cost(&aa, &bb, i);
-                let (aa, bb) = get_aabb(&objects[i..]);
-                this_cost += calculate_cost(&aa, &bb, objects.len() - i);
-                if this_cost < cost {
-                    cost = this_cost;
-                    left_objects = Some(&objects[..i]);
-                    right_objects = Some(&objects[i..]);
+        let mut best: Option<(f64, usize, usize)> = None;
+        for axis in 0..3 {
+            let sorted = sorted_by(axis);
+            /* bounds of all objects from each split to the end, so every split costs the same */
+            let mut right = vec![boxes[sorted[sorted.len() - 1]]; sorted.len()];
+            for i in (1..sorted.len() - 1).rev() {
+                right[i] = union(&right[i + 1], &boxes[sorted[i]]);
+            }
+            let mut left = boxes[sorted[0]];
+            for i in 1..sorted.len() {
+                let cost = calculate_cost(&left, i) + calculate_cost(&right[i], sorted.len() - i);
+                if best.is_none_or(|(best, _, _)| cost < best) {
+                    best = Some((cost, axis, i));
                 }
+                left = union(&left, &boxes[sorted[i]]);
             }
-
-            let subnode = Self::build(left_objects.unwrap(), capability);
-            node.nodes.push(subnode);
-            let subnode = Self::build(right_objects.unwrap(), capability);
-            node.nodes.push(subnode);
         }
+
+        let (_, axis, split) = best.unwrap();
+        let sorted = sorted_by(axis)
+            .into_iter()
+            .map(|i| objects[i].clone())
+            .collect::<Vec<_>>();
+        node.nodes.push(Self::build(&sorted[..split], capability));
+        node.nodes.push(Self::build(&sorted[split..], capability));
         node
     }
 
     /**
-     * Check if a ray hits this AABB Box.
+     * Check if a ray hits this AABB Box closer than `t_max`.
      */
-    pub fn hit(&self, ray: &Ray) -> bool {
+    pub fn hit_aabb(&self, ray: &Ray, t_max: f64) -> bool {
         if ray.origin.x() > self.min.x()
             && ray.origin.x() < self.max.x()
             && ray.origin.y() > self.min.y()
@@ -174,35 +230,52 @@ impl BVHNode {
         let z_near = ((self.max.z() - ray.origin.z()) / ray.direction.z)
             .min((self.min.z() - ray.origin.z()) / ray.direction.z);
 
-        let t_max = x_near.max(y_near).max(z_near);
-        let t_min = x_far.min(y_far).min(z_far);
-        t_max >= 0. && t_min > 0. && t_max <= t_min
+        let near = x_near.max(y_near).max(z_near);
+        let far = x_far.min(y_far).min(z_far);
+        near >= 0. && far > 0. && near <= far && near < t_max
     }
 
     /**
-     * Search the whole tree to find the closest object to hit.
+     * Search the whole tree to find the closest hit closer than `t_max`, `hit` intersects an
+     * object of a leaf closer than the distance it is given.
      */
-    pub fn find_closest_hit(&self, ray: &Ray) -> Option<(f64, Rc<dyn Object>)> {
-        if !self.hit(ray) {
+    fn traverse<'a>(
+        &'a self,
+        ray: &Ray,
+        t_max: f64,
+        hit: &impl Fn(&'a dyn Object, f64) -> Option<HitRecord<'a>>,
+    ) -> Option<HitRecord<'a>> {
+        if !self.hit_aabb(ray, t_max) {
             return None;
         }
         if self.nodes.is_empty() {
-            return find_closest_hit(ray, &self.objects);
+            return closest(&self.objects, t_max, hit);
         }
 
-        let mut closest_hit = None;
+        let mut closest: Option<HitRecord> = None;
         for bvh in &self.nodes {
-            if bvh.hit(ray) {
-                let hit = bvh.find_closest_hit(ray);
-                if closest_hit.is_none() {
-                    closest_hit = hit;
-                } else if let Some((t, _)) = &hit
-                    && *t < closest_hit.clone().unwrap().0
-                {
-                    closest_hit = hit;
-                }
+            let t_max = closest.as_ref().map_or(t_max, |hit| hit.t);
+            if let Some(hit) = bvh.traverse(ray, t_max, hit) {
+                closest = Some(hit);
             }
         }
-        closest_hit
+        closest
+    }
+
+    /**
+     * Find the closest hit closer than `t_max`, the hits cut out by alpha masks are skipped.
+     */
+    pub fn find_closest_hit(&self, ray: &Ray, t_max: f64) -> Option<HitRecord<'_>> {
+        self.traverse(ray, t_max, &|object, t_max| {
+            hit_unmasked(ray, object, t_max)
+        })
+    }
+
+    /**
+     * Find the closest hit within (t_min, t_max) including cut out hits,
+     * for trees inside of objects whose hits are masked by the tree of the scene.
+     */
+    pub fn closest_hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.traverse(ray, t_max, &|object, t_max| object.hit(ray, t_min, t_max))
     }
 }
@@ -0,0 +1,245 @@
+use crate::vector::Vector3D;
+use std::{
+    f64::consts::{FRAC_PI_2, PI},
+    str::FromStr,
+};
+
+/**
+ * A camera projection maps a position on the film to a ray in camera space.
+ *
+ * In camera space x points right, y points up and z points forward.
+ */
+pub trait Camera {
+    /**
+     * (u, v) is the position on the film, both range from -1 to 1, v points up.
+     *
+     * Returns the origin and direction of the ray, or `None` if the position is not covered by the projection.
+     */
+    fn project(&self, u: f64, v: f64) -> Option<(Vector3D, Vector3D)>;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Eye {
+    #[default]
+    Mono,
+    Left,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StereoMode {
+    /** both eyes are rotated to look at the convergence point */
+    ToeIn,
+    /** both eyes look forward, the image planes are shifted to converge */
+    OffAxis,
+    /** omni-directional stereo, the eyes are offset perpendicular to every ray */
+    Ods,
+}
+
+impl FromStr for StereoMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "toe-in" => Ok(Self::ToeIn),
+            "off-axis" => Ok(Self::OffAxis),
+            "ods" => Ok(Self::Ods),
+            _ => Err(format!("unknown stereo mode `{s}`")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Stereo {
+    /** distance between the two eyes */
+    pub interocular: f64,
+    /** distance of the zero parallax plane, ignored by ODS */
+    pub convergence: f64,
+    pub mode: StereoMode,
+}
+
+impl Stereo {
+    /**
+     * Offset a ray in camera space for the given eye.
+     */
+    pub fn apply(&self, eye: Eye, origin: Vector3D, direction: Vector3D) -> (Vector3D, Vector3D) {
+        let half = match eye {
+            Eye::Mono => return (origin, direction),
+            Eye::Left => -self.interocular / 2.,
+            Eye::Right => self.interocular / 2.,
+        };
+
+        match self.mode {
+            StereoMode::ToeIn => {
+                let (sin, cos) = (half / self.convergence).atan().sin_cos();
+                (
+                    origin + Vector3D::new(half, 0., 0.),
+                    Vector3D::new(
+                        direction.x * cos - direction.z * sin,
+                        direction.y,
+                        direction.x * sin + direction.z * cos,
+                    ),
+                )
+            }
+            StereoMode::OffAxis => (
+                origin + Vector3D::new(half, 0., 0.),
+                direction - Vector3D::new(half * direction.z / self.convergence, 0., 0.),
+            ),
+            StereoMode::Ods => {
+                /* offset to the right of the horizontal component of the ray */
+                let horizontal = Vector3D::new(direction.x, 0., direction.z);
+                if horizontal.module() == 0. {
+                    return (origin, direction);
+                }
+                let horizontal = horizontal.unit();
+                (
+                    origin + half * Vector3D::new(horizontal.z, 0., -horizontal.x),
+                    direction,
+                )
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Projection {
+    #[default]
+    Perspective,
+    Orthographic,
+    FisheyeEquidistant,
+    FisheyeEquisolid,
+    Equirectangular,
+    Cubemap,
+}
+
+impl FromStr for Projection {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perspective" => Ok(Self::Perspective),
+            "orthographic" => Ok(Self::Orthographic),
+            "fisheye-equidistant" => Ok(Self::FisheyeEquidistant),
+            "fisheye-equisolid" => Ok(Self::FisheyeEquisolid),
+            "equirectangular" => Ok(Self::Equirectangular),
+            "cubemap" => Ok(Self::Cubemap),
+            _ => Err(format!("unknown camera type `{s}`")),
+        }
+    }
+}
+
+pub struct Perspective {
+    /** half width of the image plane at distance 1 */
+    pub half_width: f64,
+    /** half height of the image plane at distance 1 */
+    pub half_height: f64,
+}
+
+impl Camera for Perspective {
+    fn project(&self, u: f64, v: f64) -> Option<(Vector3D, Vector3D)> {
+        Some((
+            Vector3D::new(0., 0., 0.),
+            Vector3D::new(u * self.half_width, v * self.half_height, 1.),
+        ))
+    }
+}
+
+pub struct Orthographic {
+    /** half width of the view volume in world units */
+    pub half_width: f64,
+    /** half height of the view volume in world units */
+    pub half_height: f64,
+}
+
+impl Camera for Orthographic {
+    fn project(&self, u: f64, v: f64) -> Option<(Vector3D, Vector3D)> {
+        Some((
+            Vector3D::new(u * self.half_width, v * self.half_height, 0.),
+            Vector3D::new(0., 0., 1.),
+        ))
+    }
+}
+
+/**
+ * Circular fisheye, the image circle touches the top and bottom of the image.
+ */
+pub struct Fisheye {
+    /** field of view of the image circle in radians */
+    pub fov: f64,
+    /** width / height */
+    pub aspect: f64,
+    /** equisolid (equal-area) mapping if true, equidistant mapping otherwise */
+    pub equisolid: bool,
+}
+
+impl Camera for Fisheye {
+    fn project(&self, u: f64, v: f64) -> Option<(Vector3D, Vector3D)> {
+        let u = u * self.aspect;
+        let r = (u * u + v * v).sqrt();
+        if r > 1. {
+            return None;
+        }
+
+        let theta = if self.equisolid {
+            /* r = sin(theta / 2) / sin(fov / 4) */
+            2. * (r * (self.fov / 4.).sin()).asin()
+        } else {
+            /* r = theta / (fov / 2) */
+            r * self.fov / 2.
+        };
+        let phi = v.atan2(u);
+
+        Some((
+            Vector3D::new(0., 0., 0.),
+            Vector3D::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ),
+        ))
+    }
+}
+
+/**
+ * Latitude-longitude panorama covering the full sphere.
+ */
+pub struct Equirectangular;
+
+impl Camera for Equirectangular {
+    fn project(&self, u: f64, v: f64) -> Option<(Vector3D, Vector3D)> {
+        let longitude = u * PI;
+        let latitude = v * FRAC_PI_2;
+
+        Some((
+            Vector3D::new(0., 0., 0.),
+            Vector3D::new(
+                latitude.cos() * longitude.sin(),
+                latitude.sin(),
+                latitude.cos() * longitude.cos(),
+            ),
+        ))
+    }
+}
+
+/**
+ * Six 90° faces laid out as a horizontal strip in the order +X, -X, +Y, -Y, +Z, -Z of camera space.
+ */
+pub struct Cubemap;
+
+impl Camera for Cubemap {
+    fn project(&self, u: f64, v: f64) -> Option<(Vector3D, Vector3D)> {
+        let strip = (u + 1.) / 2. * 6.;
+        let face = (strip as usize).min(5);
+        /* position on the face from -1 to 1 */
+        let a = 2. * (strip - face as f64) - 1.;
+        let b = v;
+
+        let direction = match face {
+            0 => Vector3D::new(1., b, -a),
+            1 => Vector3D::new(-1., b, a),
+            2 => Vector3D::new(a, 1., -b),
+            3 => Vector3D::new(a, -1., b),
+            4 => Vector3D::new(a, b, 1.),
+            _ => Vector3D::new(-a, b, -1.),
+        };
+        Some((Vector3D::new(0., 0., 0.), direction))
+    }
+}
@@ -0,0 +1,281 @@
+use crate::{
+    bvh::{self, bounds_from_aabb},
+    objects::{HitRecord, Object},
+    point::Point,
+    ray::Ray,
+};
+use std::{rc::Rc, str::FromStr};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    /** the first solid minus the second */
+    Difference,
+}
+
+impl FromStr for Operation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "union" => Ok(Self::Union),
+            "intersection" => Ok(Self::Intersection),
+            "difference" => Ok(Self::Difference),
+            _ => Err(format!("unknown csg operation `{s}`")),
+        }
+    }
+}
+
+impl Operation {
+    /** whether a point is inside of the result, by whether it is inside of each solid */
+    fn contains(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            Self::Union => in_a || in_b,
+            Self::Intersection => in_a && in_b,
+            Self::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/**
+ * Boolean combination of two closed objects, which is closed itself.
+ */
+pub struct Csg {
+    operation: Operation,
+    a: Rc<dyn Object>,
+    b: Rc<dyn Object>,
+    aabb: (Point, Point),
+}
+
+impl Csg {
+    pub fn new(operation: Operation, a: Rc<dyn Object>, b: Rc<dyn Object>) -> Self {
+        let (a_box, b_box) = (bvh::bounds(a.as_ref()), bvh::bounds(b.as_ref()));
+        let aabb = match operation {
+            Operation::Union => bvh::union(&a_box, &b_box),
+            Operation::Intersection => (
+                Point::new(
+                    a_box.0.x().max(b_box.0.x()),
+                    a_box.0.y().max(b_box.0.y()),
+                    a_box.0.z().max(b_box.0.z()),
+                ),
+                Point::new(
+                    a_box.1.x().min(b_box.1.x()),
+                    a_box.1.y().min(b_box.1.y()),
+                    a_box.1.z().min(b_box.1.z()),
+                ),
+            ),
+            Operation::Difference => a_box,
+        };
+        Self {
+            operation,
+            a,
+            b,
+            aabb,
+        }
+    }
+    /**
+     * Walk the hits of both solids along the ray, the hits where the ray enters or leaves
+     * the result are its boundary. Their normals are turned to point out of the result.
+     *
+     * The solids are hit up to `reach`, the boundary is pushed up to the first hit at or beyond
+     * t_max. Returns false if that hit may be behind the hits of a solid cut at `reach`.
+     */
+    fn boundaries<'a>(
+        &'a self,
+        r: &Ray,
+        t_min: f64,
+        t_max: f64,
+        reach: f64,
+        hits: &mut Vec<HitRecord<'a>>,
+    ) -> bool {
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        self.a.hit_all(r, t_min, reach, &mut a);
+        self.b.hit_all(r, t_min, reach, &mut b);
+        /* the first hit of a solid tells whether the ray starts inside of it */
+        let starts_inside = |hits: &[HitRecord]| hits.first().is_some_and(|hit| !hit.front_face);
+        let (mut in_a, mut in_b) = (starts_inside(&a), starts_inside(&b));
+        let mut inside = self.operation.contains(in_a, in_b);
+        /* behind the last hit of a cut solid, whether the ray is inside of it is unknown */
+        let cut = |hits: &[HitRecord]| {
+            hits.last()
+                .filter(|hit| hit.t >= reach)
+                .map_or(f64::INFINITY, |hit| hit.t)
+        };
+        let known = cut(&a).min(cut(&b));
+
+        /* the hits of both solids are in ascending order, they are merged */
+        let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+        loop {
+            let from_a = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) => x.t <= y.t,
+                (x, _) => x.is_some(),
+            };
+            let Some(mut hit) = (if from_a { a.next() } else { b.next() }) else {
+                break;
+            };
+            if hit.t > known {
+                break;
+            }
+            if from_a {
+                in_a = hit.front_face;
+            } else {
+                in_b = hit.front_face;
+            }
+            let now_inside = self.operation.contains(in_a, in_b);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+            /* subtracted solids are entered where the result is left */
+            if hit.front_face != inside {
+                hit.geometric_normal = -hit.geometric_normal;
+                hit.normal = -hit.normal;
+                hit.front_face = inside;
+            }
+            let beyond = hit.t >= t_max;
+            hits.push(hit);
+            if beyond {
+                return true;
+            }
+        }
+        known == f64::INFINITY
+    }
+}
+
+bounds_from_aabb!(Csg);
+
+impl Object for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        /* the walk stops at the first boundary, which is known if it is closer than t_max */
+        let mut hits = Vec::new();
+        self.boundaries(r, t_min, t_min, t_max, &mut hits);
+        hits.into_iter().next().filter(|hit| hit.t < t_max)
+    }
+    fn is_closed(&self) -> bool {
+        true
+    }
+    fn hit_all<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, hits: &mut Vec<HitRecord<'a>>) {
+        let start = hits.len();
+        if !self.boundaries(r, t_min, t_max, t_max, hits) {
+            /* the boundary beyond t_max is behind a cut, the solids are hit along the whole ray */
+            hits.truncate(start);
+            self.boundaries(r, t_min, t_max, f64::INFINITY, hits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{material::Material, objects::Sphere, vector::Vector3D};
+
+    fn sphere(x: f64, y: f64, radius: f64) -> Rc<dyn Object> {
+        Rc::new(Sphere::new(
+            Point::new(x, y, 0.),
+            radius,
+            Material::default(),
+        ))
+    }
+
+    /** a ray along +x */
+    fn ray(x: f64, y: f64) -> Ray {
+        Ray::new(Point::new(x, y, 0.), Vector3D::new(1., 0., 0.), 0.)
+    }
+
+    /** the boundary up to t_max as x of the point, x of the normal and whether they enter */
+    fn boundary(csg: &Csg, r: &Ray, t_max: f64) -> Vec<(f64, f64, bool)> {
+        let mut hits = Vec::new();
+        csg.hit_all(r, 0., t_max, &mut hits);
+        hits.iter()
+            .map(|hit| (hit.point.x(), hit.normal.x, hit.front_face))
+            .collect()
+    }
+
+    fn assert_hits(actual: &[(f64, f64, bool)], expected: &[(f64, f64, bool)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a.0 - e.0).abs() < 1e-9 && (a.1 - e.1).abs() < 1e-9 && a.2 == e.2,
+                "{actual:?} != {expected:?}"
+            );
+        }
+    }
+
+    /** two unit spheres on the x axis, overlapping from x = 0 to 1 */
+    fn csg(operation: Operation) -> Csg {
+        Csg::new(operation, sphere(0., 0., 1.), sphere(1., 0., 1.))
+    }
+
+    #[test]
+    fn union_keeps_the_outer_boundary() {
+        let hits = boundary(&csg(Operation::Union), &ray(-10., 0.), f64::INFINITY);
+        assert_hits(&hits, &[(-1., -1., true), (2., 1., false)]);
+    }
+
+    #[test]
+    fn intersection_keeps_the_inner_boundary() {
+        let hits = boundary(&csg(Operation::Intersection), &ray(-10., 0.), f64::INFINITY);
+        assert_hits(&hits, &[(0., -1., true), (1., 1., false)]);
+    }
+
+    #[test]
+    fn difference_flips_the_normals_of_the_subtracted_solid() {
+        let hits = boundary(&csg(Operation::Difference), &ray(-10., 0.), f64::INFINITY);
+        assert_hits(&hits, &[(-1., -1., true), (0., 1., false)]);
+
+        /* the second sphere minus the first is entered where the first is left */
+        let reversed = Csg::new(
+            Operation::Difference,
+            sphere(1., 0., 1.),
+            sphere(0., 0., 1.),
+        );
+        let hits = boundary(&reversed, &ray(-10., 0.), f64::INFINITY);
+        assert_hits(&hits, &[(1., -1., true), (2., 1., false)]);
+    }
+
+    #[test]
+    fn ray_starting_inside_of_a_difference() {
+        let difference = csg(Operation::Difference);
+        let r = ray(-0.5, 0.);
+        assert_hits(
+            &boundary(&difference, &r, f64::INFINITY),
+            &[(0., 1., false)],
+        );
+        let hit = difference.hit(&r, 0., f64::INFINITY).unwrap();
+        assert!((hit.t - 0.5).abs() < 1e-9 && !hit.front_face);
+
+        /* inside of the subtracted sphere only, the ray never enters the result */
+        assert!(boundary(&difference, &ray(1.5, 0.), f64::INFINITY).is_empty());
+    }
+
+    #[test]
+    fn tangent_hits_keep_the_boundary() {
+        /* the ray touches the subtracted sphere at its lowest point, inside of the first sphere */
+        let difference = Csg::new(
+            Operation::Difference,
+            sphere(0., 0., 2.),
+            sphere(0., 2., 1.),
+        );
+        let hits = boundary(&difference, &ray(-10., 1.), f64::INFINITY);
+        let x = 3f64.sqrt();
+        assert_eq!(hits.len() % 2, 0, "{hits:?}");
+        assert_hits(&hits[..1], &[(-x, -x / 2., true)]);
+        assert!((hits[hits.len() - 1].0 - x).abs() < 1e-9 && !hits[hits.len() - 1].2);
+        assert!(hits.iter().step_by(2).all(|hit| hit.2));
+    }
+
+    #[test]
+    fn hits_end_at_the_first_boundary_beyond_t_max() {
+        let hits = boundary(&csg(Operation::Union), &ray(-10., 0.), 5.);
+        assert_hits(&hits, &[(-1., -1., true)]);
+
+        /* the first hit of the large sphere is no boundary, the small one is hit further */
+        let intersection = Csg::new(
+            Operation::Intersection,
+            sphere(0., 0., 3.),
+            sphere(2.5, 0., 1.),
+        );
+        let hits = boundary(&intersection, &ray(-10., 0.), 1.);
+        assert_hits(&hits, &[(1.5, -1., true)]);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::vector::Vector3D;
+use ppm::FloatImage;
+use std::f64::consts::PI;
+
+/**
+ * Radiance arriving from infinitely far away, seen by rays that miss every object.
+ */
+pub trait Environment {
+    /** radiance coming from `direction` (a unit vector pointing away from the scene) */
+    fn radiance(&self, direction: &Vector3D) -> Vector3D;
+    /**
+     * Importance sample a direction for next-event estimation.
+     *
+     * Returns the direction, its radiance and the probability density per solid angle.
+     */
+    fn sample(&self) -> Option<(Vector3D, Vector3D, f64)> {
+        None
+    }
+    /** probability density per solid angle of `sample` generating `direction` */
+    fn pdf(&self, _direction: &Vector3D) -> f64 {
+        0.
+    }
+}
+
+pub struct Constant {
+    pub color: Vector3D,
+}
+
+impl Environment for Constant {
+    fn radiance(&self, _direction: &Vector3D) -> Vector3D {
+        self.color
+    }
+}
+
+/**
+ * Vertical gradient from the nadir to the zenith.
+ */
+pub struct Gradient {
+    pub bottom: Vector3D,
+    pub top: Vector3D,
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Vector3D) -> Vector3D {
+        let t = (direction.y + 1.) / 2.;
+        self.bottom + t * (self.top - self.bottom)
+    }
+}
+
+/**
+ * Piecewise constant 1D distribution.
+ */
+struct Distribution1D {
+    /** cumulative distribution, `cdf[0] = 0` and `cdf[n] = 1` */
+    cdf: Vec<f64>,
+    /** integral of the function before normalization */
+    integral: f64,
+}
+
+impl Distribution1D {
+    fn new(weights: &[f64]) -> Self {
+        let mut cdf = vec![0.; weights.len() + 1];
+        for (i, w) in weights.iter().enumerate() {
+            cdf[i + 1] = cdf[i] + w / weights.len() as f64;
+        }
+        let integral = cdf[weights.len()];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0. {
+                *c / integral
+            } else {
+                i as f64 / weights.len().max(1) as f64
+            };
+        }
+        Self { cdf, integral }
+    }
+    fn len(&self) -> usize {
+        self.cdf.len() - 1
+    }
+    /** density of bucket `i` relative to a uniform distribution */
+    fn pdf(&self, i: usize) -> f64 {
+        (self.cdf[i + 1] - self.cdf[i]) * self.len() as f64
+    }
+    /**
+     * Map a uniform random number to a continuous position from 0 to 1, returns the position and the bucket.
+     * Distributions without buckets have no samples.
+     */
+    fn sample(&self, r: f64) -> Option<(f64, usize)> {
+        let last = self.len().checked_sub(1)?;
+        let i = (self.cdf.partition_point(|c| *c <= r) - 1).min(last);
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let offset = if width > 0. {
+            (r - self.cdf[i]) / width
+        } else {
+            0.
+        };
+        Some(((i as f64 + offset) / self.len() as f64, i))
+    }
+}
+
+/**
+ * Latitude-longitude environment map, importance sampled with a 2D CDF.
+ *
+ * The center of the image faces -Z and the top row is the zenith.
+ */
+pub struct EnvironmentMap {
+    image: FloatImage,
+    intensity: f64,
+    /** rotation around the Y axis in radians */
+    rotation: f64,
+    /** distribution of the rows */
+    marginal: Distribution1D,
+    /** distribution of the pixels within every row */
+    conditional: Vec<Distribution1D>,
+}
+
+impl EnvironmentMap {
+    /**
+     * rotation: rotation around the Y axis in degrees
+     */
+    pub fn new(image: FloatImage, intensity: f64, rotation: f64) -> Self {
+        let (width, height) = (image.width(), image.height());
+        let conditional = (0..height)
+            .map(|y| {
+                /* rows near the poles cover less solid angle */
+                let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+                let weights = (0..width)
+                    .map(|x| {
+                        let [r, g, b] = image.get_pixel(x, y);
+                        (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) * sin_theta
+                    })
+                    .collect::<Vec<f64>>();
+                Distribution1D::new(&weights)
+            })
+            .collect::<Vec<Distribution1D>>();
+        let marginal =
+            Distribution1D::new(&conditional.iter().map(|d| d.integral).collect::<Vec<f64>>());
+
+        Self {
+            image,
+            intensity,
+            rotation: rotation.to_radians(),
+            marginal,
+            conditional,
+        }
+    }
+    /** map a direction to image coordinates from 0 to 1 */
+    fn direction_to_uv(&self, direction: &Vector3D) -> (f64, f64) {
+        let phi = direction.x.atan2(-direction.z) - self.rotation;
+        let u = (phi / (2. * PI) + 0.5).rem_euclid(1.);
+        let v = direction.y.clamp(-1., 1.).acos() / PI;
+        (u, v)
+    }
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vector3D {
+        let phi = (u - 0.5) * 2. * PI + self.rotation;
+        let theta = v * PI;
+        Vector3D::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        )
+    }
+    /** the pixel at image coordinates from 0 to 1, empty images have none */
+    fn pixel(&self, u: f64, v: f64) -> Option<(usize, usize)> {
+        let (width, height) = (self.image.width(), self.image.height());
+        Some((
+            ((u * width as f64) as usize).min(width.checked_sub(1)?),
+            ((v * height as f64) as usize).min(height.checked_sub(1)?),
+        ))
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: &Vector3D) -> Vector3D {
+        let (u, v) = self.direction_to_uv(direction);
+        let Some((x, y)) = self.pixel(u, v) else {
+            return Vector3D::new(0., 0., 0.);
+        };
+        let [r, g, b] = self.image.get_pixel(x, y);
+        self.intensity * Vector3D::new(r as f64, g as f64, b as f64)
+    }
+    fn sample(&self) -> Option<(Vector3D, Vector3D, f64)> {
+        if self.marginal.integral <= 0. {
+            return None;
+        }
+        let (v, y) = self.marginal.sample(rand::random())?;
+        let (u, _) = self.conditional[y].sample(rand::random())?;
+        let direction = self.uv_to_direction(u, v);
+        let pdf = self.pdf(&direction);
+        if pdf <= 0. {
+            return None;
+        }
+        Some((direction, self.radiance(&direction), pdf))
+    }
+    fn pdf(&self, direction: &Vector3D) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let Some((x, y)) = self.pixel(u, v) else {
+            return 0.;
+        };
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        /* convert the density over the image to the density over solid angle */
+        self.marginal.pdf(y) * self.conditional[y].pdf(x) / (2. * PI * PI * sin_theta)
+    }
+}
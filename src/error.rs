@@ -0,0 +1,59 @@
+pub use diagnostic::Diagnostic;
+use std::fmt;
+
+/**
+ * An error of a file of the scene, with its position if it comes from a parser.
+ */
+#[derive(Debug)]
+pub struct Error {
+    pub path: String,
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_owned(),
+            position: None,
+            message: message.into(),
+        }
+    }
+    pub fn io(path: &str, err: std::io::Error) -> Self {
+        Self::new(path, err.to_string())
+    }
+    pub fn parse(path: &str, diagnostic: impl Into<Diagnostic>) -> Self {
+        let diagnostic = diagnostic.into();
+        Self {
+            path: path.to_owned(),
+            position: Some((diagnostic.line, diagnostic.column)),
+            message: diagnostic.message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{line}:{column}: {}", self.path, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/** print the warnings of a parser */
+pub fn warn<D: Into<Diagnostic>>(path: &str, warnings: impl IntoIterator<Item = D>) {
+    for warning in warnings {
+        let d = warning.into();
+        eprintln!("warning: {path}:{}:{}: {}", d.line, d.column, d.message);
+    }
+}
+
+/** read a file of the scene */
+pub fn read_file(path: &str) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|err| Error::io(path, err))
+}
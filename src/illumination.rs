@@ -0,0 +1,166 @@
+use crate::{material, texture::to_scalar, vector::Vector3D};
+use mtl::material::{Color, Material};
+
+/** parameters of the material with the defaults filled in */
+struct Params {
+    kd: Color,
+    ks: Color,
+    ke: Color,
+    tf: Color,
+    ni: f64,
+    /** opacity from `d` or `1 - Tr` */
+    d: f64,
+    illum: u32,
+}
+
+fn is_black(color: Color) -> bool {
+    color.0 <= 0. && color.1 <= 0. && color.2 <= 0.
+}
+
+fn scale(color: Color, s: f64) -> Color {
+    (color.0 * s, color.1 * s, color.2 * s)
+}
+
+/**
+ * Map the specular exponent `Ns` (from 0 to 1000) to the roughness (from 1 to 0).
+ */
+fn roughness(ns: f64) -> f64 {
+    (2. / (ns.max(0.) + 2.)).sqrt()
+}
+
+/**
+ * Convert the parameters of a `.mtl` material to a material of the renderer,
+ * missing parameters use the defaults of the format and the illumination models are:
+ *
+ * 0: constant color `Kd`, 1: diffuse, 2: diffuse and glossy highlight,
+ * 3 and 8: diffuse and reflection, 5: like 3 with Fresnel,
+ * 4 and 9: transparent glass, 6: refraction, 7: like 6 with Fresnel,
+ * 10: shadow matte.
+ *
+ * `Ka` is ignored, ambient light comes from the environment. Dissolve (`d` or `Tr`) lets light
+ * through unrefracted, filtered by `Tf`. Returns the material and warnings about parameters
+ * which are not supported.
+ */
+pub fn build_material(mtl: &Material) -> (material::Material, Vec<String>) {
+    let params = Params {
+        kd: mtl.kd.unwrap_or((1., 1., 1.)),
+        ks: mtl.ks.unwrap_or((0., 0., 0.)),
+        ke: mtl.ke.unwrap_or((0., 0., 0.)),
+        tf: mtl.tf.unwrap_or((1., 1., 1.)),
+        ni: mtl.ni.unwrap_or(1.),
+        d: mtl.d.or(mtl.tr.map(|tr| 1. - tr)).unwrap_or(1.),
+        illum: mtl.illum.unwrap_or(2),
+    };
+    let mut warnings = Vec::new();
+    let mut metal = material::Material {
+        /* the roughness of the PBR extension overrides the specular exponent */
+        fuzz: mtl.pr.unwrap_or_else(|| roughness(mtl.ns.unwrap_or(0.))),
+        refract_index: 1.,
+        emission: params.ke,
+        ..Default::default()
+    };
+
+    let (mut diffuse, mut specular) = (params.kd, params.ks);
+    /* metals reflect their diffuse color and scatter no diffuse light */
+    if let Some(metallic) = mtl.pm {
+        let metallic = metallic.clamp(0., 1.);
+        specular = (
+            specular.0 + metallic * (diffuse.0 - specular.0),
+            specular.1 + metallic * (diffuse.1 - specular.1),
+            specular.2 + metallic * (diffuse.2 - specular.2),
+        );
+        diffuse = scale(diffuse, 1. - metallic);
+    }
+    if mtl.halo {
+        warnings.push("`d -halo` is not supported, dissolve is constant".into());
+    }
+    match params.illum {
+        0 => {
+            /* flat color without lighting */
+            metal.emission = (
+                params.kd.0 + params.ke.0,
+                params.kd.1 + params.ke.1,
+                params.kd.2 + params.ke.2,
+            );
+            diffuse = (0., 0., 0.);
+            specular = (0., 0., 0.);
+        }
+        1 => specular = (0., 0., 0.),
+        2 | 3 | 4 | 6 => {}
+        5 | 7 => warnings.push(format!(
+            "illum {}: Fresnel reflection is approximated by a constant `Ks`",
+            params.illum
+        )),
+        8 | 9 => warnings.push(format!(
+            "illum {}: reflections are always ray traced",
+            params.illum
+        )),
+        10 => {
+            warnings.push("illum 10: shadow matte is not supported, rendered as illum 1".into());
+            specular = (0., 0., 0.);
+        }
+        illum => warnings.push(format!(
+            "unknown illumination model {illum}, rendered as illum 2"
+        )),
+    }
+    if params.illum <= 1 && !is_black(params.ks) {
+        warnings.push(format!("illum {}: `Ks` is ignored", params.illum));
+    }
+
+    /* refraction only for the models with refraction, glass and dissolve pass light straight */
+    let refractive = matches!(params.illum, 6 | 7);
+    let transparent = matches!(params.illum, 4 | 6 | 7 | 9);
+    let opacity = params.d.clamp(0., 1.);
+    if transparent && opacity >= 1. {
+        warnings.push(format!(
+            "illum {}: the material is transparent but `d` is 1, rendered opaque",
+            params.illum
+        ));
+    }
+    if refractive {
+        metal.refract_index = params.ni;
+    } else if params.ni != 1. && transparent {
+        warnings.push(format!(
+            "illum {}: `Ni` is ignored without refraction",
+            params.illum
+        ));
+    }
+    let mut transmission = scale(params.tf, 1. - opacity);
+    diffuse = scale(diffuse, opacity);
+    specular = scale(specular, opacity);
+    if params.illum == 0 {
+        transmission = (0., 0., 0.);
+    }
+
+    /* keep the sum of the lobes from reflecting more light than arrives */
+    let total = Vector3D::from(diffuse) + Vector3D::from(specular) + Vector3D::from(transmission);
+    let max = total.x.max(total.y).max(total.z);
+    if max > 1. {
+        diffuse = scale(diffuse, 1. / max);
+        specular = scale(specular, 1. / max);
+        transmission = scale(transmission, 1. / max);
+    }
+
+    /* choose each lobe by its share of the reflected light */
+    let weights = [
+        to_scalar(Vector3D::from(diffuse)),
+        to_scalar(Vector3D::from(specular)),
+        to_scalar(Vector3D::from(transmission)),
+    ];
+    let sum: f64 = weights.iter().sum();
+    if sum > 0. {
+        metal.diffuse_rate = weights[0] / sum;
+        metal.reflect_rate = if weights[1] + weights[2] > 0. {
+            weights[1] / (weights[1] + weights[2])
+        } else {
+            1.
+        };
+    } else {
+        metal.diffuse_rate = 1.;
+    }
+    metal.attenuation = diffuse;
+    metal.specular = specular;
+    metal.transmission = transmission;
+
+    (metal, warnings)
+}
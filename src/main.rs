@@ -1,22 +1,53 @@
+mod animation;
 mod bvh;
+mod camera;
 mod color;
+mod csg;
+mod environment;
+mod error;
+mod illumination;
 mod material;
+mod mesh;
 mod objects;
 mod point;
+mod primitives;
+mod procedural;
 mod ray;
 mod render;
+mod resolver;
+mod scene;
 mod script;
+mod sdf;
+mod sky;
+mod texture;
+mod transform;
+mod triangulate;
 mod vector;
 mod viewport;
 
-use clap::Parser;
-use mtl::{material::Material, parser::parse_mtl};
-use obj::element::Face;
-use objects::{Object, Polygon, Triangle};
+use animation::Animation;
+use camera::Eye;
+use clap::{CommandFactory, Parser, ValueEnum, error::ErrorKind};
+use csg::{Csg, Operation};
+use environment::Environment;
+use error::{Error, Result, read_file};
+use material::{Cutout, TextureSlot};
+use mtl::{
+    material::{Material, TextureMap},
+    parser::parse_mtl,
+};
+use objects::{Instance, MeshObject, Object, Primitive, TriangleMesh};
 use point::Point;
-use script::Instruction;
-use std::{collections::HashMap, io::Result as IOResult, rc::Rc};
-use viewport::ViewportBuilder;
+use procedural::Procedural;
+use resolver::Resolver;
+use scene::Scene;
+use script::{Instruction, Origin, Script};
+use sdf::{Sdf, SdfObject};
+use std::{collections::HashMap, io::Result as IOResult, path::Path, process::ExitCode, rc::Rc};
+use texture::{Filter, ImageTexture, Texture, Wrap};
+use transform::Transform;
+use vector::Vector3D;
+use viewport::{Viewport, ViewportBuilder};
 
 #[derive(Parser)]
 struct Args {
@@ -32,95 +63,604 @@ struct Args {
     /** Max depth */
     #[arg(short = 'd', default_value_t = 10)]
     max_depth: usize,
+    /** Layout of the two eyes of a stereo camera */
+    #[arg(long, value_enum, default_value_t = StereoLayout::SideBySide)]
+    stereo_layout: StereoLayout,
+    /** First frame of an animation, the output path may contain a printf-style `%04d` */
+    #[arg(long)]
+    frame_start: Option<usize>,
+    /** Last frame of an animation (inclusive) */
+    #[arg(long)]
+    frame_end: Option<usize>,
+    /** Threads parsing `.obj` files, with 1 thread files are streamed instead of loaded whole */
+    #[arg(long, default_value_t = 1)]
+    obj_threads: usize,
+    /** Directory searched for files not found next to the file referencing them, may be repeated */
+    #[arg(short = 'I')]
+    search_path: Vec<String>,
 }
 
-/** get fuzz from `Ns` value in `.mtl` file */
-fn get_fuzz(materials: &[Material]) -> f64 {
-    for mtl in materials {
-        if let Material::Ns(ns) = mtl {
-            return -0.001 * *ns + 1.; // map ns (from 0 to 1000) to fuzz (from 1 to 0)
+#[derive(Clone, Copy, ValueEnum)]
+enum StereoLayout {
+    /** left eye on the left half, right eye on the right half */
+    SideBySide,
+    /** left eye on the top half, right eye on the bottom half */
+    TopBottom,
+    /** one file per eye, suffixed with `_left` and `_right` */
+    Separate,
+}
+
+/** insert a suffix between the file stem and the extension of a path */
+fn suffixed_path(path: &str, suffix: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{stem}{suffix}.{}", ext.to_string_lossy()),
+        None => format!("{stem}{suffix}"),
+    };
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/**
+ * Get the output path of a frame by replacing `%d` or `%0Nd` in the path,
+ * the frame number is appended to the file stem if the path contains no pattern.
+ */
+fn frame_path(path: &str, frame: usize) -> String {
+    if let Some(start) = path.find('%')
+        && let Some(len) = path[start + 1..].find('d')
+    {
+        let spec = &path[start + 1..start + 1 + len];
+        if spec.chars().all(|c| c.is_ascii_digit()) {
+            let width = spec.parse().unwrap_or(0);
+            return format!(
+                "{}{frame:0width$}{}",
+                &path[..start],
+                &path[start + len + 2..]
+            );
         }
     }
-    0.
+    suffixed_path(path, &format!(".{frame:04}"))
 }
 
-/** get attenuation from `Kd` value in `.mtl` file */
-fn get_attenuation(materials: &[Material]) -> (f64, f64, f64) {
-    for mtl in materials {
-        if let Material::Kd(r, g, b) = mtl {
-            return (*r, *g, *b);
+fn save_stereo(
+    left: &ppm::Image,
+    right: &ppm::Image,
+    output: &str,
+    layout: StereoLayout,
+) -> IOResult<()> {
+    match layout {
+        StereoLayout::SideBySide => {
+            let mut image = ppm::Image::new(left.width() * 2, left.height());
+            image.paste(left, 0, 0);
+            image.paste(right, left.width(), 0);
+            image.save(output, ppm::PPMType::P6)
+        }
+        StereoLayout::TopBottom => {
+            let mut image = ppm::Image::new(left.width(), left.height() * 2);
+            image.paste(left, 0, 0);
+            image.paste(right, 0, left.height());
+            image.save(output, ppm::PPMType::P6)
+        }
+        StereoLayout::Separate => {
+            left.save(&suffixed_path(output, "_left"), ppm::PPMType::P6)?;
+            right.save(&suffixed_path(output, "_right"), ppm::PPMType::P6)
         }
     }
+}
+
+/** a texture driving a parameter of a material */
+type TextureBinding = (TextureSlot, Rc<dyn Texture>);
 
-    (1., 1., 1.)
+/**
+ * Textures of the scene and how they apply to materials, each texture map is loaded only once and shared.
+ */
+#[derive(Default)]
+struct MaterialLibrary {
+    textures: HashMap<(String, bool), Rc<ImageTexture>>,
+    filter: Filter,
+    /** textures defined in the script, by name */
+    named: HashMap<String, Rc<dyn Texture>>,
+    /** how alpha masks cut out surfaces */
+    cutout: Cutout,
+    /** textures bound to parameters of `.mtl` materials, by material name */
+    bindings: HashMap<String, Vec<TextureBinding>>,
+    /** finds the texture maps of `.mtl` materials */
+    resolver: Resolver,
 }
 
-/** get reflect rate from `d` value in `.mtl` file */
-fn get_reflect(materials: &[Material]) -> f64 {
-    for mtl in materials {
-        if let Material::D(d) = mtl {
-            return *d;
+impl MaterialLibrary {
+    fn get(&mut self, path: &str, clamp: bool) -> Result<Rc<ImageTexture>> {
+        if let Some(texture) = self.textures.get(&(path.to_owned(), clamp)) {
+            return Ok(Rc::clone(texture));
         }
+        let wrap = if clamp { Wrap::Clamp } else { Wrap::Repeat };
+        let image = Rc::new(ppm::Image::open(path).map_err(|err| Error::io(path, err))?);
+        let texture = Rc::new(ImageTexture::new(image, self.filter, wrap));
+        self.textures
+            .insert((path.to_owned(), clamp), Rc::clone(&texture));
+        Ok(texture)
     }
+    fn bind(
+        &mut self,
+        material: &str,
+        slot: TextureSlot,
+        texture: &str,
+    ) -> std::result::Result<(), String> {
+        let texture = Rc::clone(
+            self.named
+                .get(texture)
+                .ok_or_else(|| format!("undefined texture `{texture}`"))?,
+        );
+        self.bindings
+            .entry(material.to_owned())
+            .or_default()
+            .push((slot, texture));
+        Ok(())
+    }
+}
 
-    1.
+/**
+ * A material of a `.mtl` file, its texture maps are relative to the file.
+ */
+#[derive(Default)]
+struct MtlMaterial {
+    mtl: Material,
+    file: Rc<str>,
 }
 
-/** get refract index from `Ni` value in `.mtl` file */
-fn get_refract(materials: &[Material]) -> f64 {
-    for mtl in materials {
-        if let Material::Ni(ni) = mtl {
-            return *ni;
+/** load the image of a texture map of a `.mtl` material, resolved relative to the `.mtl` file */
+fn get_map(
+    map: &Option<TextureMap>,
+    mtl_file: &str,
+    library: &mut MaterialLibrary,
+) -> Result<Option<Rc<dyn Texture>>> {
+    match map {
+        Some(map) => {
+            let path = library.resolver.resolve(&map.path, mtl_file)?;
+            Ok(Some(library.get(&path, map.clamp)? as Rc<dyn Texture>))
         }
+        None => Ok(None),
     }
+}
 
-    1.
+/**
+ * Convert the parameters of a `.mtl` material to a material of the renderer,
+ * textures bound to the material name in the script override the texture maps.
+ */
+fn build_material(
+    material: &MtlMaterial,
+    library: &mut MaterialLibrary,
+) -> Result<material::Material> {
+    let MtlMaterial { mtl, file } = material;
+    let (mut metal, warnings) = illumination::build_material(mtl);
+    for warning in warnings {
+        eprintln!("warning: material `{}`: {warning}", mtl.name);
+    }
+    metal.texture = get_map(&mtl.map_kd, file, library)?;
+    metal.roughness_texture = get_map(&mtl.map_pr, file, library)?;
+    metal.normal_map = get_map(&mtl.norm, file, library)?;
+    metal.bump_map = get_map(&mtl.map_bump, file, library)?.map(|map| {
+        let multiplier = mtl.map_bump.as_ref().unwrap().bump_multiplier;
+        (map, multiplier)
+    });
+    metal.alpha_map = get_map(&mtl.map_d, file, library)?;
+    metal.cutout = library.cutout;
+    for (slot, texture) in library.bindings.get(&mtl.name).into_iter().flatten() {
+        metal.set_texture(*slot, Rc::clone(texture));
+    }
+    Ok(metal)
 }
 
-fn load_obj(objects: &mut Vec<Rc<dyn Object>>, obj_file: &str) -> IOResult<()> {
-    let elements = obj::parser::parse_obj(&std::fs::read_to_string(obj_file)?);
+/**
+ * Read a `.mtl` file, materials are indexed by name. Texture maps are resolved when a material
+ * is built, so maps of unused parameters may be missing.
+ */
+fn load_mtl(mtl_file: &str) -> Result<HashMap<String, MtlMaterial>> {
+    let (materials, warnings) =
+        parse_mtl(&read_file(mtl_file)?).map_err(|err| Error::parse(mtl_file, err))?;
+    error::warn(mtl_file, warnings);
+    let file: Rc<str> = Rc::from(mtl_file);
+    Ok(materials
+        .into_iter()
+        .map(|(name, mtl)| {
+            let file = Rc::clone(&file);
+            (name, MtlMaterial { mtl, file })
+        })
+        .collect())
+}
 
-    for element in &elements {
-        if let Some(face) = element.downcast_ref::<Face>() {
-            if face.vertexes.len() == 3 {
-                let mut metal = material::Material::new_metal();
-                metal.fuzz = get_fuzz(&face.materials);
-                metal.attenuation = get_attenuation(&face.materials);
-                metal.refract_index = get_refract(&face.materials);
-                metal.reflect_rate = get_reflect(&face.materials);
+/**
+ * crease_angle: generate smooth normals between faces within this angle (in radians),
+ * instead of from smoothing groups
+ */
+fn load_obj(
+    obj_file: &str,
+    library: &mut MaterialLibrary,
+    crease_angle: Option<f64>,
+    resolver: &Resolver,
+    threads: usize,
+) -> Result<TriangleMesh> {
+    let parsed = if threads > 1 {
+        obj::parser::parse_obj_parallel(&read_file(obj_file)?, threads)
+    } else {
+        let file = std::fs::File::open(obj_file).map_err(|err| Error::io(obj_file, err))?;
+        obj::parser::read_obj(std::io::BufReader::new(file))
+    };
+    let (obj, warnings) = parsed.map_err(|err| Error::parse(obj_file, err))?;
+    error::warn(obj_file, warnings);
+    let mut mtllib = HashMap::new();
+    for mtl_file in &obj.mtllibs {
+        mtllib.extend(load_mtl(&resolver.resolve(mtl_file, obj_file)?)?);
+    }
+    if !obj.lines.is_empty() || !obj.points.is_empty() {
+        eprintln!("warning: {obj_file}: lines and points are not rendered");
+    }
 
-                objects.push(Rc::new(Triangle::from_obj(face, metal)));
-            } else {
-                let mut metal = material::Material::new_metal();
-                metal.fuzz = get_fuzz(&face.materials);
-                metal.attenuation = get_attenuation(&face.materials);
-                metal.refract_index = get_refract(&face.materials);
-                metal.reflect_rate = get_reflect(&face.materials);
+    /* faces share the material of their `usemtl`, built once per material */
+    let default = MtlMaterial::default();
+    let mut materials = Vec::new();
+    for name in obj.materials.iter().map(Some).chain([None]) {
+        let mtl = name.and_then(|name| mtllib.get(name)).unwrap_or(&default);
+        materials.push(build_material(mtl, library)?);
+    }
+    let (mesh, warnings) = mesh::triangle_mesh(&obj, crease_angle, materials);
+    error::warn(obj_file, warnings);
+    Ok(mesh)
+}
 
-                objects.push(Rc::new(Polygon::from_obj(face, metal)));
-            }
+/**
+ * Build a material loaded by `load-mtl` for an object of the script.
+ */
+fn script_material(
+    name: &str,
+    mtls: &HashMap<String, MtlMaterial>,
+    library: &mut MaterialLibrary,
+    origin: &Origin,
+) -> Result<material::Material> {
+    let mtl = mtls
+        .get(name)
+        .ok_or_else(|| origin.error(format!("undefined material `{name}`")))?;
+    build_material(mtl, library)
+}
+
+/**
+ * Get a distance field defined in the script by name.
+ */
+fn named_sdf(name: &str, sdfs: &HashMap<String, Rc<Sdf>>, origin: &Origin) -> Result<Rc<Sdf>> {
+    sdfs.get(name)
+        .cloned()
+        .ok_or_else(|| origin.error(format!("undefined distance field `{name}`")))
+}
+
+/**
+ * Get a solid defined in the script by name.
+ */
+fn named_solid(
+    name: &str,
+    solids: &HashMap<String, Rc<dyn Object>>,
+    origin: &Origin,
+) -> Result<Rc<dyn Object>> {
+    solids
+        .get(name)
+        .cloned()
+        .ok_or_else(|| origin.error(format!("undefined solid `{name}`")))
+}
+
+fn to_point(p: &(f64, f64, f64)) -> Point {
+    Point::new(p.0, p.1, p.2)
+}
+
+/**
+ * Place an object with the current transformation, objects without transformation are kept as they are.
+ */
+fn place_object(
+    object: Rc<dyn Object>,
+    transform: Transform,
+    transform_end: Option<Transform>,
+    shutter: (f64, f64),
+) -> Rc<dyn Object> {
+    match transform_end {
+        None if transform.is_identity() => object,
+        None => Rc::new(Instance::new(object, transform)),
+        Some(transform_end) => Rc::new(Instance::new_moving(
+            object,
+            transform,
+            transform_end,
+            shutter.0,
+            shutter.1,
+        )),
+    }
+}
+
+/**
+ * Build the environment from the last background instruction, the default is black.
+ */
+fn build_environment(script: &Script) -> Result<Rc<dyn Environment>> {
+    let mut environment: Rc<dyn Environment> = Rc::new(environment::Constant {
+        color: vector::Vector3D::new(0., 0., 0.),
+    });
+    for ins in &script.instructions {
+        if let Instruction::Background { r, g, b } = ins {
+            environment = Rc::new(environment::Constant {
+                color: vector::Vector3D::new(*r, *g, *b),
+            });
+        }
+        if let Instruction::BackgroundGradient { bottom, top } = ins {
+            environment = Rc::new(environment::Gradient {
+                bottom: vector::Vector3D::from(*bottom),
+                top: vector::Vector3D::from(*top),
+            });
+        }
+        if let Instruction::EnvironmentMap {
+            path,
+            intensity,
+            rotation,
+        } = ins
+        {
+            environment = Rc::new(environment::EnvironmentMap::new(
+                ppm::FloatImage::load(path).map_err(|err| Error::io(path, err))?,
+                *intensity,
+                *rotation,
+            ));
+        }
+        if let Instruction::Sky {
+            turbidity,
+            ground_albedo,
+            intensity,
+        } = ins
+        {
+            environment = Rc::new(sky::Sky::new(
+                script.get_sun_direction(),
+                *turbidity,
+                *ground_albedo,
+                *intensity,
+            ));
         }
     }
+    Ok(environment)
+}
 
-    Ok(())
+fn build_viewport(script: &Script, animation: &Animation, frame: f64) -> Viewport {
+    let shutter = script.get_camera_shutter();
+    ViewportBuilder::default()
+        .origin(
+            animation
+                .camera(frame + shutter.0)
+                .unwrap_or(script.get_camera()),
+        )
+        .origin_end(
+            animation
+                .camera(frame + shutter.1)
+                .or(script.get_camera_end()),
+        )
+        .target(
+            animation
+                .camera_target(frame + shutter.0)
+                .or(script.get_camera_target()),
+        )
+        .target_end(
+            animation
+                .camera_target(frame + shutter.1)
+                .or(script.get_camera_target_end()),
+        )
+        .shutter(shutter)
+        .at(script.get_camera_at())
+        .up(script.get_camera_up())
+        .fov(
+            animation
+                .camera_fov(frame + shutter.0)
+                .or(script.get_camera_fov()),
+        )
+        .focal_length(script.get_camera_focal_length())
+        .sensor_width(script.get_camera_sensor())
+        .projection(script.get_camera_type())
+        .ortho_height(script.get_camera_ortho_height())
+        .size(script.get_size())
+        .roll(script.get_camera_scale())
+        .stereo(script.get_camera_stereo())
+        .build()
 }
 
-fn main() -> IOResult<()> {
+fn main() -> ExitCode {
     let args = Args::parse();
-    let script = script::Script::parse(&std::fs::read_to_string(args.script)?);
+    if let (Some(start), Some(end)) = (args.frame_start, args.frame_end)
+        && start > end
+    {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                format!("the first frame {start} is after the last frame {end}"),
+            )
+            .exit();
+    }
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/** deepest nesting of `include`, deeper scripts most likely include each other */
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/**
+ * Read a script and the scripts it includes, the paths of instructions are resolved
+ * relative to the script they are written in.
+ */
+fn load_script(path: &str, resolver: &Resolver, depth: usize) -> Result<Script> {
+    let (script, warnings) =
+        Script::parse(path, &read_file(path)?).map_err(|err| Error::parse(path, err))?;
+    error::warn(path, warnings);
+    let mut instructions = Vec::new();
+    let mut origins = Vec::new();
+    for (mut ins, origin) in script.instructions.into_iter().zip(script.origins) {
+        match &mut ins {
+            Instruction::Include(file) => {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(origin.error(format!(
+                        "`{file}` is nested too deeply, scripts may include each other"
+                    )));
+                }
+                let file = resolver.resolve(file, path)?;
+                let included = load_script(&file, resolver, depth + 1)?;
+                instructions.extend(included.instructions);
+                origins.extend(included.origins);
+                continue;
+            }
+            Instruction::LoadObj(file)
+            | Instruction::LoadMtl(file)
+            | Instruction::ImageTexture { path: file, .. }
+            | Instruction::EnvironmentMap { path: file, .. } => {
+                *file = resolver.resolve(file, path)?;
+            }
+            _ => {}
+        }
+        instructions.push(ins);
+        origins.push(origin);
+    }
+    Ok(Script {
+        instructions,
+        origins,
+    })
+}
+
+/** a solid being built: its name, where it starts and its objects */
+type OpenSolid<'a> = (String, &'a Origin, Vec<Rc<dyn Object>>);
+
+fn run(args: Args) -> Result<()> {
+    let resolver = Resolver::new(&args.search_path);
+    let script = load_script(&args.script, &resolver, 0)?;
+    let animation = Animation::from_script(&script);
 
-    let mut objects: Vec<Rc<dyn Object>> = Vec::new();
+    let mut objects: Vec<Primitive> = Vec::new();
+    /* objects without bounds stay out of the BVH */
+    let mut unbounded: Vec<Primitive> = Vec::new();
+    /* objects of groups with keys, by group name */
+    let mut groups: HashMap<String, Vec<Rc<dyn Object>>> = HashMap::new();
+    let mut group: Option<String> = None;
+    /* closed solids for constructive solid geometry, by name */
+    let mut solids: HashMap<String, Rc<dyn Object>> = HashMap::new();
+    let mut solid: Option<OpenSolid> = None;
 
     let mut mtls = HashMap::new();
+    /* distance fields defined in the script, by name */
+    let mut sdfs: HashMap<String, Rc<Sdf>> = HashMap::new();
+    let mut library = MaterialLibrary {
+        filter: script.get_texture_filter(),
+        cutout: script.get_alpha_cutout(),
+        resolver: Resolver::new(&args.search_path),
+        ..Default::default()
+    };
 
-    for ins in &script.instructions {
+    let shutter = script.get_camera_shutter();
+    let crease_angle = script.get_crease_angle();
+    let mut transform = Transform::default();
+    let mut transform_end = None;
+
+    for (ins, origin) in script.instructions.iter().zip(&script.origins) {
+        let mut added: Vec<Rc<dyn Object>> = Vec::new();
+        let mut mesh = None;
+        if let Instruction::Group(name) = ins {
+            group = Some(name.to_owned());
+        }
+        if let Instruction::GroupEnd = ins {
+            group = None;
+        }
+        if let Instruction::Solid(name) = ins {
+            if let Some((open, ..)) = &solid {
+                return Err(origin.error(format!(
+                    "solid `{name}` starts before the end of solid `{open}`"
+                )));
+            }
+            solid = Some((name.to_owned(), origin, Vec::new()));
+        }
+        if let Instruction::SolidEnd = ins {
+            let Some((name, _, parts)) = solid.take() else {
+                return Err(origin.error("`solid-end` without `solid`"));
+            };
+            /* the objects of a solid are united */
+            let united = parts
+                .into_iter()
+                .reduce(|a, b| Rc::new(Csg::new(Operation::Union, a, b)))
+                .ok_or_else(|| origin.error(format!("solid `{name}` is empty")))?;
+            solids.insert(name, united);
+        }
+        if let Instruction::Csg {
+            name,
+            operation,
+            a,
+            b,
+        } = ins
+        {
+            let (a, b) = (
+                named_solid(a, &solids, origin)?,
+                named_solid(b, &solids, origin)?,
+            );
+            solids.insert(name.to_owned(), Rc::new(Csg::new(*operation, a, b)));
+        }
+        if let Instruction::AddSolid(name) = ins {
+            added.push(named_solid(name, &solids, origin)?);
+        }
+        if let Instruction::Transform(t) = ins {
+            transform = *t;
+            transform_end = None;
+        }
+        if let Instruction::TransformEnd(t) = ins {
+            transform_end = Some(*t);
+        }
+        if let Instruction::TransformReset = ins {
+            transform = Transform::default();
+            transform_end = None;
+        }
+        if let Instruction::Texture {
+            name,
+            pattern,
+            space,
+            scale,
+            color_a,
+            color_b,
+            param,
+        } = ins
+        {
+            let texture = Procedural {
+                pattern: *pattern,
+                space: *space,
+                scale: *scale,
+                color_a: vector::Vector3D::from(*color_a),
+                color_b: vector::Vector3D::from(*color_b),
+                param: *param,
+            };
+            library.named.insert(name.to_owned(), Rc::new(texture));
+        }
+        if let Instruction::ImageTexture { name, path } = ins {
+            let texture = library.get(path, false)?;
+            library.named.insert(name.to_owned(), texture);
+        }
+        if let Instruction::MaterialTexture {
+            material,
+            slot,
+            texture,
+        } = ins
+        {
+            library
+                .bind(material, *slot, texture)
+                .map_err(|message| origin.error(message))?;
+        }
         if let Instruction::LoadObj(obj_file) = ins {
-            load_obj(&mut objects, obj_file)?;
+            mesh = Some(load_obj(
+                obj_file,
+                &mut library,
+                crease_angle,
+                &resolver,
+                args.obj_threads,
+            )?);
         }
         if let Instruction::LoadMtl(mtl_file) = ins {
-            for (name, ctx) in parse_mtl(&std::fs::read_to_string(mtl_file)?).iter() {
-                mtls.insert(name.to_owned(), ctx.clone());
-            }
+            mtls.extend(load_mtl(mtl_file)?);
         }
         if let Instruction::AddSphere {
             x,
@@ -130,36 +670,258 @@ fn main() -> IOResult<()> {
             material,
         } = ins
         {
-            let mut metal = material::Material::new_metal();
-            let mtl = mtls.get(material).unwrap();
-            metal.fuzz = get_fuzz(mtl);
-            metal.attenuation = get_attenuation(mtl);
-            metal.refract_index = get_refract(mtl);
-            metal.reflect_rate = get_reflect(mtl);
-            objects.push(Rc::new(objects::Sphere::new(
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            added.push(Rc::new(objects::Sphere::new(
                 Point::new(*x, *y, *z),
                 *raius,
                 metal,
             )));
         }
+        if let Instruction::AddPlane {
+            point,
+            normal,
+            material,
+        } = ins
+        {
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            added.push(Rc::new(primitives::Plane::new(
+                to_point(point),
+                Vector3D::from(*normal),
+                metal,
+            )));
+        }
+        if let Instruction::AddDisk {
+            center,
+            normal,
+            radius,
+            material,
+        } = ins
+        {
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            added.push(Rc::new(primitives::Disk::new(
+                to_point(center),
+                Vector3D::from(*normal),
+                *radius,
+                metal,
+            )));
+        }
+        if let Instruction::AddQuad {
+            corner,
+            edge_u,
+            edge_v,
+            material,
+        } = ins
+        {
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            added.push(Rc::new(primitives::Quad::new(
+                to_point(corner),
+                Vector3D::from(*edge_u),
+                Vector3D::from(*edge_v),
+                metal,
+            )));
+        }
+        if let Instruction::AddBox {
+            corner_a,
+            corner_b,
+            material,
+        } = ins
+        {
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            added.push(Rc::new(primitives::Cuboid::new(
+                to_point(corner_a),
+                to_point(corner_b),
+                metal,
+            )));
+        }
+        if let Instruction::AddOrientedBox {
+            center,
+            axis_x,
+            axis_y,
+            half_size,
+            material,
+        } = ins
+        {
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            added.push(Rc::new(primitives::OrientedBox::new(
+                to_point(center),
+                Vector3D::from(*axis_x),
+                Vector3D::from(*axis_y),
+                Vector3D::from(*half_size),
+                metal,
+            )));
+        }
+        if let Instruction::AddCone {
+            start,
+            end,
+            radius_start,
+            radius_end,
+            material,
+        } = ins
+        {
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            added.push(Rc::new(primitives::Cone::new(
+                to_point(start),
+                to_point(end),
+                *radius_start,
+                *radius_end,
+                metal,
+            )));
+        }
+        if let Instruction::AddTorus {
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+            material,
+        } = ins
+        {
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            added.push(Rc::new(primitives::Torus::new(
+                to_point(center),
+                Vector3D::from(*axis),
+                *major_radius,
+                *minor_radius,
+                metal,
+            )));
+        }
+        if let Instruction::SdfShape { name, shape } = ins {
+            sdfs.insert(name.to_owned(), Rc::new(Sdf::Shape(*shape)));
+        }
+        if let Instruction::SdfCombine {
+            name,
+            operator,
+            a,
+            b,
+        } = ins
+        {
+            let (a, b) = (named_sdf(a, &sdfs, origin)?, named_sdf(b, &sdfs, origin)?);
+            sdfs.insert(name.to_owned(), Rc::new(Sdf::Combine(*operator, a, b)));
+        }
+        if let Instruction::SdfModify {
+            name,
+            modifier,
+            sdf,
+        } = ins
+        {
+            let sdf = Sdf::modify(*modifier, named_sdf(sdf, &sdfs, origin)?)
+                .map_err(|message| origin.error(format!("`{name}`: {message}")))?;
+            sdfs.insert(name.to_owned(), Rc::new(sdf));
+        }
+        if let Instruction::AddSdf {
+            name,
+            material,
+            bounds,
+        } = ins
+        {
+            let metal = script_material(material, &mtls, &mut library, origin)?;
+            let clip = bounds.map(|(a, b)| (to_point(&a), to_point(&b)));
+            let object = SdfObject::new(named_sdf(name, &sdfs, origin)?, clip, metal)
+                .map_err(|message| origin.error(format!("`{name}`: {message}")))?;
+            added.push(Rc::new(object));
+        }
+
+        /* groups without keys never move, they are static geometry */
+        let keyed_group = group
+            .as_ref()
+            .filter(|name| animation.transforms.contains_key(*name));
+        if let Some(mesh) = mesh {
+            if keyed_group.is_none() && solid.is_none() && transform_end.is_none() {
+                /* the transformation of static meshes is applied once, their triangles need no instances */
+                let mesh = Rc::new(mesh.transformed(&transform));
+                objects.extend(TriangleMesh::triangles(&mesh).map(Primitive::Triangle));
+            } else {
+                /* other meshes are placed as a whole, their BVH is built once */
+                added.push(Rc::new(MeshObject::new(mesh)));
+            }
+        }
+        for object in added {
+            let object = place_object(object, transform, transform_end, shutter);
+            if let Some((name, _, parts)) = &mut solid {
+                if !object.is_closed() {
+                    return Err(origin.error(format!(
+                        "solid `{name}` can only hold closed objects, \
+                         meshes must be watertight with their normals outside"
+                    )));
+                }
+                parts.push(object);
+                continue;
+            }
+            match keyed_group {
+                Some(name) => groups.entry(name.to_owned()).or_default().push(object),
+                None if object.is_bounded() => objects.push(Primitive::Object(object)),
+                None => unbounded.push(Primitive::Object(object)),
+            }
+        }
+    }
+    if let Some((name, origin, _)) = solid {
+        return Err(origin.error(format!("solid `{name}` has no `solid-end`")));
     }
 
-    let bvh = bvh::BVHNode::build(&objects, 20);
+    /* static geometry is built only once for all frames */
+    let static_bvh = Rc::new(bvh::BVHNode::build(&objects, bvh::LEAF_CAPACITY));
+    let environment = build_environment(&script)?;
 
-    let viewport = ViewportBuilder::default()
-        .origin(script.get_camera())
-        .at(script.get_camera_at())
-        .size(script.get_size())
-        .area(4., 4. * 8. / 16.)
-        .scale(script.get_camera_scale())
-        .build();
+    let frames = match (args.frame_start, args.frame_end) {
+        (None, None) => None,
+        (start, end) => Some(start.or(end).unwrap()..=end.or(start).unwrap()),
+    };
 
-    let render = render::RenderBuilder::default()
-        .viewport(viewport)
-        .sample(args.sampling)
-        .max_depth(args.max_depth)
-        .build();
+    for frame in frames.clone().unwrap_or(0..=0) {
+        let time = frame as f64;
 
-    render.render(&bvh).save(&args.output, ppm::PPMType::P6)?;
+        let mut animated = Vec::new();
+        let mut unbounded = unbounded.clone();
+        for (name, objects) in &groups {
+            let start = animation.transform(name, time + shutter.0);
+            let end = animation.transform(name, time + shutter.1);
+            for object in objects {
+                let instance = Primitive::Object(Rc::new(Instance::new_moving(
+                    Rc::clone(object),
+                    start,
+                    end,
+                    shutter.0,
+                    shutter.1,
+                )));
+                if object.is_bounded() {
+                    animated.push(instance);
+                } else {
+                    unbounded.push(instance);
+                }
+            }
+        }
+        let dynamic_bvh = if animated.is_empty() {
+            None
+        } else {
+            Some(bvh::BVHNode::build(&animated, bvh::LEAF_CAPACITY))
+        };
+        let scene = Scene::new(
+            Rc::clone(&static_bvh),
+            dynamic_bvh,
+            unbounded,
+            Rc::clone(&environment),
+        );
+
+        let render = render::RenderBuilder::default()
+            .viewport(build_viewport(&script, &animation, time))
+            .sample(args.sampling)
+            .max_depth(args.max_depth)
+            .build();
+
+        let output = match frames {
+            Some(_) => frame_path(&args.output, frame),
+            None => args.output.clone(),
+        };
+        if script.get_camera_stereo().is_some() {
+            let left = render.render_eye(&scene, Eye::Left);
+            let right = render.render_eye(&scene, Eye::Right);
+            save_stereo(&left, &right, &output, args.stereo_layout)
+                .map_err(|err| Error::io(&output, err))?;
+        } else {
+            render
+                .render(&scene)
+                .save(&output, ppm::PPMType::P6)
+                .map_err(|err| Error::io(&output, err))?;
+        }
+    }
     Ok(())
 }
@@ -1,62 +1,281 @@
-use crate::{ray::Ray, vector::Vector3D};
+use crate::{
+    point::Point,
+    ray::Ray,
+    texture::{Texture, TextureCoord, to_scalar},
+    vector::Vector3D,
+};
+use std::{rc::Rc, str::FromStr};
 
-#[derive(Default, Clone, Copy)]
+/** material parameters which can be driven by a texture */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextureSlot {
+    Color,
+    Roughness,
+    Emission,
+    /** tangent space normal map */
+    Normal,
+    /** height map */
+    Bump,
+    /** alpha mask of cutouts */
+    Alpha,
+}
+
+/** how the alpha mask cuts out the surface */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cutout {
+    /** a hit is kept with the probability of the alpha value */
+    Stochastic,
+    /** a hit is kept if the alpha value reaches the threshold */
+    Threshold(f64),
+}
+
+impl Default for Cutout {
+    fn default() -> Self {
+        Self::Threshold(0.5)
+    }
+}
+
+/** step in texture coordinates of the finite differences of bump maps */
+const BUMP_DELTA: f64 = 1. / 1024.;
+
+impl FromStr for TextureSlot {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "color" => Ok(Self::Color),
+            "roughness" => Ok(Self::Roughness),
+            "emission" => Ok(Self::Emission),
+            "normal" => Ok(Self::Normal),
+            "bump" => Ok(Self::Bump),
+            "alpha" => Ok(Self::Alpha),
+            _ => Err(format!("unknown material parameter `{s}`")),
+        }
+    }
+}
+
+/** the ways a material scatters light */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lobe {
+    Diffuse,
+    Reflect,
+    Refract,
+}
+
+#[derive(Default, Clone)]
 pub struct Material {
+    /** probability of diffuse scattering */
+    pub diffuse_rate: f64,
     /** probability of reflection
      *
-     * The probability of reflection is (reflect_rate), while the probability of refraction is (1 - reflect_rate)
+     * Without diffuse scattering, the probability of reflection is (reflect_rate), while the probability of refraction is (1 - reflect_rate)
      *
      */
     pub reflect_rate: f64,
     /** refractive index of glass */
     pub refract_index: f64,
-    /** roughness of surface */
+    /** roughness of reflection and refraction, fully rough reflection is diffuse */
     pub fuzz: f64,
+    /** color of diffuse scattering */
     pub attenuation: (f64, f64, f64),
+    /** color of reflection */
+    pub specular: (f64, f64, f64),
+    /** color of refraction */
+    pub transmission: (f64, f64, f64),
+    /** radiance emitted by the surface */
+    pub emission: (f64, f64, f64),
+    /** texture multiplied with `attenuation` */
+    pub texture: Option<Rc<dyn Texture>>,
+    /** texture replacing `fuzz`, from the average of its channels */
+    pub roughness_texture: Option<Rc<dyn Texture>>,
+    /** radiance emitted by the surface in addition to `emission` */
+    pub emission_texture: Option<Rc<dyn Texture>>,
+    /** tangent space normal map, the channels from 0 to 1 map to the components from -1 to 1 */
+    pub normal_map: Option<Rc<dyn Texture>>,
+    /** height map and the scale of the heights */
+    pub bump_map: Option<(Rc<dyn Texture>, f64)>,
+    /** alpha mask, the surface is cut out where the average of the channels is low */
+    pub alpha_map: Option<Rc<dyn Texture>>,
+    pub cutout: Cutout,
     /** light intensity */
     pub emit: f64,
     pub is_light: bool,
 }
 
 impl Material {
-    pub fn new_metal() -> Self {
-        Self {
-            reflect_rate: 1.,
-            ..Default::default()
-        }
-    }
-    pub fn new_light(emit: f64) -> Self {
-        Self {
-            emit,
-            is_light: true,
-            ..Default::default()
-        }
-    }
-    pub fn scatter(&self, ray: &Ray, length: f64, normal: &Vector3D) -> Ray {
-        let p = rand::random::<f64>();
-
-        /* reflect */
-        if p < self.reflect_rate {
-            let mut ref_ray = ray.reflect(length, normal);
-            ref_ray.direction += self.fuzz * Vector3D::new_random_unit();
-            ref_ray.direction = ref_ray.direction.unit();
-            ref_ray
-        }
-        /* refract */
-        else {
-            let mut ref_ray = {
-                /* inject into the medium from air */
-                if ray.direction.cdot(normal) < 0. {
-                    ray.refract(length, self.refract_index, normal)
+    pub fn set_texture(&mut self, slot: TextureSlot, texture: Rc<dyn Texture>) {
+        match slot {
+            TextureSlot::Color => self.texture = Some(texture),
+            TextureSlot::Roughness => self.roughness_texture = Some(texture),
+            TextureSlot::Emission => self.emission_texture = Some(texture),
+            TextureSlot::Normal => self.normal_map = Some(texture),
+            TextureSlot::Bump => self.bump_map = Some((texture, 1.)),
+            TextureSlot::Alpha => self.alpha_map = Some(texture),
+        }
+    }
+    pub fn is_textured(&self) -> bool {
+        self.texture.is_some()
+            || self.roughness_texture.is_some()
+            || self.emission_texture.is_some()
+            || self.normal_map.is_some()
+            || self.bump_map.is_some()
+    }
+    /**
+     * Perturb the shading normal by the normal map and the bump map.
+     *
+     * tangents: derivatives of the position by u and v
+     */
+    pub fn perturb_normal(
+        &self,
+        coord: &TextureCoord,
+        normal: Vector3D,
+        (dpdu, dpdv): (Vector3D, Vector3D),
+    ) -> Vector3D {
+        let mut normal = normal;
+        if let Some(map) = &self.normal_map {
+            let tangent = dpdu - dpdu.cdot(&normal) * normal;
+            if tangent.module() > 1e-12 {
+                let tangent = tangent.unit();
+                let mut bitangent = normal * tangent;
+                if bitangent.cdot(&dpdv) < 0. {
+                    bitangent = -bitangent;
                 }
-                /* inject into air from the medium */
-                else {
-                    ray.refract(length, 1. / self.refract_index, normal)
+                let n = 2. * map.sample(coord) - Vector3D::new(1., 1., 1.);
+                let mapped = n.x * tangent + n.y * bitangent + n.z * normal;
+                if mapped.module() > 1e-12 {
+                    normal = mapped.unit();
                 }
+            }
+        }
+        if let Some((map, multiplier)) = &self.bump_map {
+            let height = |du: f64, dv: f64| {
+                let offset = du * dpdu + dv * dpdv;
+                to_scalar(map.sample(&TextureCoord {
+                    uv: (coord.uv.0 + du, coord.uv.1 + dv),
+                    world: Point::from_vec3d(coord.world.point_vec + offset),
+                    object: Point::from_vec3d(coord.object.point_vec + offset),
+                }))
             };
-            ref_ray.direction += self.fuzz * Vector3D::new_random_unit();
-            ref_ray.direction = ref_ray.direction.unit();
-            ref_ray
+            let h = height(0., 0.);
+            let dhdu = multiplier * (height(BUMP_DELTA, 0.) - h) / BUMP_DELTA;
+            let dhdv = multiplier * (height(0., BUMP_DELTA) - h) / BUMP_DELTA;
+            /* normal of the surface displaced along the normal by the height */
+            let bumped = (dpdu + dhdu * normal) * (dpdv + dhdv * normal);
+            if bumped.module() > 1e-12 {
+                normal = if bumped.cdot(&normal) < 0. {
+                    -bumped.unit()
+                } else {
+                    bumped.unit()
+                };
+            }
         }
+        normal
+    }
+    /**
+     * Check if the surface is cut out by the alpha mask at a point.
+     */
+    pub fn is_cut_out(&self, coord: &TextureCoord) -> bool {
+        let Some(map) = &self.alpha_map else {
+            return false;
+        };
+        let alpha = to_scalar(map.sample(coord));
+        match self.cutout {
+            Cutout::Stochastic => rand::random::<f64>() >= alpha,
+            Cutout::Threshold(threshold) => alpha < threshold,
+        }
+    }
+    /**
+     * Get the attenuation at a point of the surface.
+     */
+    pub fn attenuation_at(&self, coord: &TextureCoord) -> (f64, f64, f64) {
+        match &self.texture {
+            Some(texture) => {
+                let color = texture.sample(coord);
+                (
+                    self.attenuation.0 * color.x,
+                    self.attenuation.1 * color.y,
+                    self.attenuation.2 * color.z,
+                )
+            }
+            None => self.attenuation,
+        }
+    }
+    pub fn fuzz_at(&self, coord: &TextureCoord) -> f64 {
+        match &self.roughness_texture {
+            Some(texture) => to_scalar(texture.sample(coord)).clamp(0., 1.),
+            None => self.fuzz,
+        }
+    }
+    pub fn emission_at(&self, coord: &TextureCoord) -> Vector3D {
+        let emission = Vector3D::from(self.emission);
+        match &self.emission_texture {
+            Some(texture) => emission + texture.sample(coord),
+            None => emission,
+        }
+    }
+    /**
+     * Choose how light is scattered, returns the lobe and its probability.
+     */
+    pub fn sample_lobe(&self) -> (Lobe, f64) {
+        if rand::random::<f64>() < self.diffuse_rate {
+            (Lobe::Diffuse, self.diffuse_rate)
+        } else if rand::random::<f64>() < self.reflect_rate {
+            (Lobe::Reflect, (1. - self.diffuse_rate) * self.reflect_rate)
+        } else {
+            (
+                Lobe::Refract,
+                (1. - self.diffuse_rate) * (1. - self.reflect_rate),
+            )
+        }
+    }
+    /**
+     * Get the color of a lobe, `attenuation` is the diffuse color at the hit point.
+     */
+    pub fn lobe_color(&self, lobe: Lobe, attenuation: (f64, f64, f64)) -> (f64, f64, f64) {
+        match lobe {
+            Lobe::Diffuse => attenuation,
+            Lobe::Reflect => self.specular,
+            Lobe::Refract => self.transmission,
+        }
+    }
+    /**
+     * fuzz: the roughness at the hit point
+     */
+    pub fn reflect(&self, ray: &Ray, length: f64, normal: &Vector3D, fuzz: f64) -> Ray {
+        let ref_ray = ray.reflect(length, normal);
+        let direction = ref_ray.direction + fuzz * Vector3D::new_random_unit();
+        Ray::new(ref_ray.origin, direction, ref_ray.time)
+    }
+    /**
+     * fuzz: the roughness at the hit point
+     */
+    pub fn refract(&self, ray: &Ray, length: f64, normal: &Vector3D, fuzz: f64) -> Ray {
+        let ref_ray = {
+            /* inject into the medium from air */
+            if ray.direction.cdot(normal) < 0. {
+                ray.refract(length, self.refract_index, normal)
+            }
+            /* inject into air from the medium */
+            else {
+                ray.refract(length, 1. / self.refract_index, normal)
+            }
+        };
+        let direction = ref_ray.direction + fuzz * Vector3D::new_random_unit();
+        Ray::new(ref_ray.origin, direction, ref_ray.time)
+    }
+}
+
+/**
+ * Sample a direction in the hemisphere around `normal`, with density proportional to the cosine.
+ */
+pub fn random_cosine_direction(normal: &Vector3D) -> Vector3D {
+    /* a uniform point on the unit sphere tangent to the surface */
+    let z = 1. - 2. * rand::random::<f64>();
+    let phi = 2. * std::f64::consts::PI * rand::random::<f64>();
+    let r = (1. - z * z).sqrt();
+    let direction = *normal + Vector3D::new(r * phi.cos(), r * phi.sin(), z);
+    if direction.module() < 1e-9 {
+        *normal
+    } else {
+        direction.unit()
     }
 }
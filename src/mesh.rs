@@ -0,0 +1,185 @@
+use crate::{
+    error::Diagnostic,
+    material::Material,
+    objects::TriangleMesh,
+    point::Point,
+    triangulate::{Defect, Scratch, newell_normal, triangulate},
+    vector::Vector3D,
+};
+use obj::{model::Obj, vertex::Vertex};
+use std::collections::HashMap;
+
+/** faces sharing a position share the vertex, -0 and 0 are the same position */
+fn position_key(v: &Vertex) -> [u64; 3] {
+    [
+        (v.x + 0.).to_bits(),
+        (v.y + 0.).to_bits(),
+        (v.z + 0.).to_bits(),
+    ]
+}
+
+/**
+ * Get the generated vertex normals of every corner, in the order of `obj.corners`.
+ *
+ * Corners of faces whose normals are given by the file have none. Otherwise the normals of the faces
+ * sharing a vertex are averaged, weighted by their areas. With `crease_angle` (in radians) the faces
+ * whose normals differ by at most the angle are averaged, without it the faces of the same smoothing
+ * group are averaged and faces without smoothing group stay flat.
+ */
+pub fn vertex_normals(obj: &Obj, crease_angle: Option<f64>) -> Vec<Option<Vector3D>> {
+    let faces = &obj.faces;
+    let normals = faces
+        .iter()
+        .map(|face| {
+            newell_normal(obj.corners(&face.corners).iter().map(|corner| {
+                let v = obj.vertex(corner);
+                Vector3D::new(v.x, v.y, v.z)
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    /* faces around every vertex */
+    let mut adjacent: HashMap<[u64; 3], Vec<usize>> = HashMap::new();
+    for (i, face) in faces.iter().enumerate() {
+        for corner in obj.corners(&face.corners) {
+            adjacent
+                .entry(position_key(obj.vertex(corner)))
+                .or_default()
+                .push(i);
+        }
+    }
+
+    let smooth_with = |i: usize, j: usize| match crease_angle {
+        Some(angle) => {
+            let (a, b) = (normals[i], normals[j]);
+            let (la, lb) = (a.module(), b.module());
+            la > 0. && lb > 0. && a.cdot(&b) / (la * lb) >= angle.cos() - 1e-9
+        }
+        None => faces[i].smoothing != 0 && faces[i].smoothing == faces[j].smoothing,
+    };
+
+    let mut corner_normals = vec![None; obj.corners.len()];
+    for (i, face) in faces.iter().enumerate() {
+        if obj.has_normals(face) || (crease_angle.is_none() && face.smoothing == 0) {
+            continue;
+        }
+        for c in face.corners.clone() {
+            let mut normal = Vector3D::new(0., 0., 0.);
+            for j in &adjacent[&position_key(obj.vertex(&obj.corners[c as usize]))] {
+                if *j == i || smooth_with(i, *j) {
+                    normal += normals[*j];
+                }
+            }
+            /* degenerate faces fall back to their own normal */
+            corner_normals[c as usize] = Some(if normal.module() > 0. {
+                normal.unit()
+            } else {
+                normals[i]
+            });
+        }
+    }
+    corner_normals
+}
+
+/** number of faces reported for every kind of defect, the others are counted */
+const MAX_REPORTED: usize = 10;
+
+/**
+ * Build a triangle mesh of the faces of an `.obj` file, polygons are split by ear clipping.
+ * Degenerate faces are skipped, they and self-intersecting faces are returned as warnings.
+ *
+ * materials: the material of every `usemtl` name of the file, followed by the material of faces without one
+ */
+pub fn triangle_mesh(
+    obj: &Obj,
+    crease_angle: Option<f64>,
+    materials: Vec<Material>,
+) -> (TriangleMesh, Vec<Diagnostic>) {
+    let generated = vertex_normals(obj, crease_angle);
+
+    /* normals of the file come first, equal generated normals are stored once */
+    let mut normals: Vec<Vector3D> = obj.normals.iter().map(Vector3D::from_obj).collect();
+    let mut generated_index: HashMap<[u64; 3], u32> = HashMap::new();
+    let mut corner_normals = vec![None; obj.corners.len()];
+    for (c, (corner, normal)) in obj.corners.iter().zip(&generated).enumerate() {
+        corner_normals[c] = match normal {
+            Some(n) => Some(
+                *generated_index
+                    .entry([n.x.to_bits(), n.y.to_bits(), n.z.to_bits()])
+                    .or_insert_with(|| {
+                        normals.push(*n);
+                        normals.len() as u32 - 1
+                    }),
+            ),
+            None => corner.normal,
+        };
+    }
+
+    let default_material = materials.len() as u32 - 1;
+    let mut mesh = TriangleMesh::new(
+        obj.vertexes.iter().map(Point::from_obj).collect(),
+        normals,
+        obj.texcoords.iter().map(|vt| (vt.u, vt.v)).collect(),
+        materials,
+    );
+    /* buffers are reused between faces */
+    let mut points = Vec::new();
+    let mut triangles = Vec::new();
+    let mut scratch = Scratch::default();
+    let mut defects: Vec<(u32, Defect)> = Vec::new();
+    for face in &obj.faces {
+        let corners = obj.corners(&face.corners);
+        points.clear();
+        points.extend(corners.iter().map(|c| {
+            let v = obj.vertex(c);
+            Vector3D::new(v.x, v.y, v.z)
+        }));
+        triangles.clear();
+        if let Some(defect) = triangulate(&points, &mut triangles, &mut scratch) {
+            defects.push((face.line, defect));
+        }
+
+        let first = face.corners.start as usize;
+        let has_normals = face
+            .corners
+            .clone()
+            .all(|c| corner_normals[c as usize].is_some());
+        let has_texcoords = obj.has_texcoords(face);
+        for triangle in &triangles {
+            mesh.push_triangle(
+                triangle.map(|i| corners[i].vertex),
+                has_normals.then(|| triangle.map(|i| corner_normals[first + i].unwrap())),
+                has_texcoords.then(|| triangle.map(|i| corners[i].texcoord.unwrap())),
+                face.material.unwrap_or(default_material),
+            );
+        }
+    }
+    (mesh, report(&defects))
+}
+
+fn report(defects: &[(u32, Defect)]) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+    for kind in [Defect::Degenerate, Defect::SelfIntersecting] {
+        let message = match kind {
+            Defect::Degenerate => "face has no area and is skipped",
+            Defect::SelfIntersecting => "face intersects itself and may be split wrongly",
+        };
+        let lines = defects
+            .iter()
+            .filter(|(_, defect)| *defect == kind)
+            .map(|(line, _)| *line as usize)
+            .collect::<Vec<_>>();
+        for line in lines.iter().take(MAX_REPORTED) {
+            warnings.push(Diagnostic::new(*line, 1, message));
+        }
+        if let Some(line) = lines.get(MAX_REPORTED) {
+            warnings.push(Diagnostic::new(
+                *line,
+                1,
+                format!("{} more faces like this", lines.len() - MAX_REPORTED),
+            ));
+        }
+    }
+    warnings.sort_by_key(|d| d.line);
+    warnings
+}
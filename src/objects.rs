@@ -1,19 +1,103 @@
-use crate::{bvh::BoarderDedection, material::Material, point::Point, ray::Ray, vector::Vector3D};
+use crate::{
+    bvh::{BVHNode, BoarderDedection, LEAF_CAPACITY},
+    material::Material,
+    point::Point,
+    ray::Ray,
+    texture::TextureCoord,
+    transform::{Matrix, Transform},
+    vector::Vector3D,
+};
+use std::{collections::HashMap, rc::Rc};
 
 /**
- * Check if a ray hit a plane
-*/
-fn plane_hit(ray: &Ray, p: &Point, normal: &Vector3D) -> Option<f64> {
-    let ap = ray.origin.to_vec3d(p);
-    let t_n = -ap.cdot(normal);
-    let t = -t_n / ray.direction.cdot(normal);
-    if t > 0. { Some(t) } else { None }
+ * What a ray hits on the surface of an object.
+ */
+pub struct HitRecord<'a> {
+    /** distance along the ray */
+    pub t: f64,
+    pub point: Point,
+    /** position of the point before the object was transformed, for object space textures */
+    pub object_point: Point,
+    /** normal of the actual surface, pointing to the outside of the object */
+    pub geometric_normal: Vector3D,
+    /** interpolated normal for shading, on the side of the geometric normal */
+    pub normal: Vector3D,
+    /** whether the ray hits the side the geometric normal points to */
+    pub front_face: bool,
+    pub uv: (f64, f64),
+    /**
+     * derivatives of the position by the texture coordinates u and v,
+     * which build the tangent frame of normal and bump maps
+     */
+    pub tangents: Option<(Vector3D, Vector3D)>,
+    /** index of the hit triangle of a mesh, 0 for other objects */
+    #[allow(dead_code)]
+    pub primitive: u32,
+    pub material: &'a Material,
+}
+
+impl HitRecord<'_> {
+    pub fn texture_coord(&self) -> TextureCoord {
+        TextureCoord {
+            uv: self.uv,
+            world: self.point,
+            object: self.object_point,
+        }
+    }
 }
 
+/** maximum number of hits of a ray reported by `Object::hit_all` */
+const MAX_HITS: usize = 64;
+/** distance to move past a hit to find the next one */
+const HIT_EPSILON: f64 = 1e-6;
+
 pub trait Object: BoarderDedection {
-    fn hit(&self, r: &Ray) -> Option<f64>;
-    fn normal(&self, p: &Point) -> Vector3D;
-    fn material(&self) -> Material;
+    /**
+     * Find the closest hit of a ray whose distance is within the open interval (t_min, t_max).
+     */
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+    /**
+     * Objects without bounds, like infinite planes, are kept out of the BVH.
+     */
+    fn is_bounded(&self) -> bool {
+        true
+    }
+    /**
+     * Closed objects enclose a volume, their hits alternately enter and leave it.
+     */
+    fn is_closed(&self) -> bool {
+        false
+    }
+    /**
+     * Push every hit of a ray beyond t_min in ascending order, up to and including the first hit
+     * at or beyond t_max. That hit tells whether the ray ends inside of a closed object.
+     */
+    fn hit_all<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, hits: &mut Vec<HitRecord<'a>>) {
+        let mut t_min = t_min;
+        let next = std::iter::from_fn(|| {
+            let hit = self.hit(r, t_min, f64::INFINITY)?;
+            t_min = hit.t + HIT_EPSILON;
+            Some(hit)
+        });
+        push_until(hits, t_max, next.take(MAX_HITS));
+    }
+}
+
+/**
+ * Push hits in ascending order up to the first one at or beyond t_max, see `Object::hit_all`.
+ */
+pub fn push_until<'a>(
+    hits: &mut Vec<HitRecord<'a>>,
+    t_max: f64,
+    sorted: impl IntoIterator<Item = HitRecord<'a>>,
+) {
+    for hit in sorted {
+        let beyond = hit.t >= t_max;
+        hits.push(hit);
+        if beyond {
+            break;
+        }
+    }
 }
 
 pub struct Sphere {
@@ -31,6 +115,58 @@ impl Sphere {
             material,
         }
     }
+    /**
+     * Latitude-longitude mapping, v is 1 at the north pole (+Y).
+     *
+     * n: the normal at the point
+     */
+    fn uv(n: &Vector3D) -> (f64, f64) {
+        let u = 0.5 + n.x.atan2(n.z) / (2. * std::f64::consts::PI);
+        let v = 0.5 + n.y.clamp(-1., 1.).asin() / std::f64::consts::PI;
+        (u, v)
+    }
+    fn tangents(&self, n: &Vector3D) -> Option<(Vector3D, Vector3D)> {
+        /* cosine of the latitude, the tangents are undefined at the poles */
+        let cos_theta = (n.x * n.x + n.z * n.z).sqrt();
+        if cos_theta < 1e-9 {
+            return None;
+        }
+        let pi = std::f64::consts::PI;
+        let dpdu = (2. * pi * self.radius) * Vector3D::new(n.z, 0., -n.x);
+        let dpdv = (pi * self.radius)
+            * Vector3D::new(-n.x * n.y / cos_theta, cos_theta, -n.z * n.y / cos_theta);
+        Some((dpdu, dpdv))
+    }
+    /**
+     * Distances where the ray enters and leaves the sphere, a ray touching the sphere
+     * neither enters nor leaves it.
+     */
+    fn intersections(&self, ray: &Ray) -> Option<[f64; 2]> {
+        let ca = self.center.to_vec3d(&ray.origin);
+        let t_d = -ca.cdot(&ray.direction);
+        let distance = (ca + t_d * ray.direction).module();
+        if distance >= self.radius {
+            return None;
+        }
+        let half_chord = (self.radius.powi(2) - distance.powi(2)).sqrt();
+        Some([t_d - half_chord, t_d + half_chord])
+    }
+    fn record(&self, ray: &Ray, t: f64) -> HitRecord<'_> {
+        let point = ray.point_at(t);
+        let n = self.center.to_vec3d(&point).unit();
+        HitRecord {
+            t,
+            point,
+            object_point: point,
+            geometric_normal: n,
+            normal: n,
+            front_face: n.cdot(&ray.direction) < 0.,
+            uv: Self::uv(&n),
+            tangents: self.tangents(&n),
+            primitive: 0,
+            material: &self.material,
+        }
+    }
 }
 
 impl BoarderDedection for Sphere {
@@ -55,238 +191,722 @@ impl BoarderDedection for Sphere {
 }
 
 impl Object for Sphere {
-    fn hit(&self, ray: &Ray) -> Option<f64> {
-        let ca = self.center.to_vec3d(&ray.origin);
-        let t_d = -ca.cdot(&ray.direction);
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        /* the far side is hit from inside the sphere */
+        let t = self
+            .intersections(ray)?
+            .into_iter()
+            .find(|t| *t > t_min && *t < t_max)?;
+        Some(self.record(ray, t))
+    }
+    fn is_closed(&self) -> bool {
+        true
+    }
+    fn hit_all<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, hits: &mut Vec<HitRecord<'a>>) {
+        if let Some(ts) = self.intersections(ray) {
+            let sorted = ts.into_iter().filter(|t| *t > t_min);
+            push_until(hits, t_max, sorted.map(|t| self.record(ray, t)));
+        }
+    }
+}
 
-        let distance = (ca + t_d * ray.direction).module();
+/**
+ * Distance and barycentric coordinates of a hit on a triangle,
+ * `u` and `v` are the weights of the second and the third vertex.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct TriangleHit {
+    pub t: f64,
+    pub u: f64,
+    pub v: f64,
+}
 
-        if t_d > 0. && distance <= self.radius {
-            let t = t_d - (self.radius.powi(2) - distance.powi(2)).sqrt();
-            if t > 0. {
-                Some(t)
-            }
-            /* if the light source is on the sphere */
-            else {
-                let t = t_d + (self.radius.powi(2) - distance.powi(2)).sqrt();
-                Some(t)
-            }
+/**
+ * Shear of the watertight triangle test, it moves vertexes into a space where the ray starts at
+ * the origin and points along +z. It depends only on the direction and is computed once per ray.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Shear {
+    kx: usize,
+    ky: usize,
+    kz: usize,
+    sx: f64,
+    sy: f64,
+    sz: f64,
+}
+
+impl Shear {
+    pub fn new(d: &Vector3D) -> Self {
+        /* the largest component of the direction becomes z */
+        let kz = if d.x.abs() > d.y.abs() {
+            if d.x.abs() > d.z.abs() { 0 } else { 2 }
+        } else if d.y.abs() > d.z.abs() {
+            1
         } else {
-            None
+            2
+        };
+        let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+        /* keep the winding of the triangle */
+        if d[kz] < 0. {
+            (kx, ky) = (ky, kx);
+        }
+        Self {
+            kx,
+            ky,
+            kz,
+            sx: d[kx] / d[kz],
+            sy: d[ky] / d[kz],
+            sz: 1. / d[kz],
         }
-    }
-    fn normal(&self, p: &Point) -> Vector3D {
-        self.center.to_vec3d(p).unit()
-    }
-    fn material(&self) -> Material {
-        self.material
     }
 }
 
-pub struct Triangle {
-    p1: Point,
-    p2: Point,
-    p3: Point,
-    material: Material,
-    normal_vec_cache: Vector3D,
+/**
+ * Watertight ray triangle intersection (Woop, Benthin and Wald 2013).
+ *
+ * The vertexes are moved by the shear of the ray, so edges shared by two triangles are tested
+ * with the same values from both sides and a ray can't pass between them, nor hit both.
+ */
+fn triangle_hit(r: &Ray, p1: &Point, p2: &Point, p3: &Point) -> Option<TriangleHit> {
+    let Shear {
+        kx,
+        ky,
+        kz,
+        sx,
+        sy,
+        sz,
+    } = *r.shear();
+    let a = r.origin.to_vec3d(p1);
+    let b = r.origin.to_vec3d(p2);
+    let c = r.origin.to_vec3d(p3);
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
+
+    /* scaled barycentric coordinates, all of the sign of det inside the triangle */
+    let e1 = cx * by - cy * bx;
+    let e2 = ax * cy - ay * cx;
+    let e3 = bx * ay - by * ax;
+    let det = e1 + e2 + e3;
+    if det == 0. {
+        return None;
+    }
+    /*
+     * A ray on an edge hits the triangle the edge function grows into when the ray is moved
+     * slightly along x, then along y, so a shared edge or vertex is hit only once.
+     */
+    let sign = det.signum();
+    let inside = |e: f64, (fx, fy): (f64, f64), (gx, gy): (f64, f64)| {
+        let (e, dx, dy) = (e * sign, (fy - gy) * sign, (gx - fx) * sign);
+        e > 0. || e == 0. && (dx > 0. || dx == 0. && dy > 0.)
+    };
+    if !(inside(e1, (cx, cy), (bx, by))
+        && inside(e2, (ax, ay), (cx, cy))
+        && inside(e3, (bx, by), (ax, ay)))
+    {
+        return None;
+    }
+    let t = (e1 * a[kz] + e2 * b[kz] + e3 * c[kz]) * sz / det;
+    if t <= 0. {
+        return None;
+    }
+    Some(TriangleHit {
+        t,
+        u: e2 / det,
+        v: e3 / det,
+    })
+}
+
+/**
+ * Triangles sharing buffers of positions, normals and texture coordinates,
+ * every triangle refers to the buffers by index and has the index of its material.
+ */
+#[derive(Default)]
+pub struct TriangleMesh {
+    positions: Vec<Point>,
+    normals: Vec<Vector3D>,
+    uvs: Vec<(f64, f64)>,
+    /** positions of every triangle */
+    indices: Vec<[u32; 3]>,
+    /** vertex normals of every triangle, triangles without vertex normals are flat */
+    normal_indices: Vec<Option<[u32; 3]>>,
+    /** texture coordinates of every triangle */
+    uv_indices: Vec<Option<[u32; 3]>>,
+    /** normal of the plane of every triangle, on the side of its vertex normals */
+    face_normals: Vec<Vector3D>,
+    material_ids: Vec<u32>,
+    materials: Vec<Material>,
+    /** transformation from the positions back to object space, if a transformation was baked in */
+    to_object: Option<Matrix>,
 }
 
-impl Triangle {
-    pub fn new(p1: Point, p2: Point, p3: Point, material: Material) -> Self {
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point>,
+        normals: Vec<Vector3D>,
+        uvs: Vec<(f64, f64)>,
+        materials: Vec<Material>,
+    ) -> Self {
         Self {
-            p1,
-            p2,
-            p3,
-            material,
-            normal_vec_cache: (p1.to_vec3d(&p2) * p1.to_vec3d(&p3)).unit(),
+            positions,
+            normals: normals.iter().map(|n| n.unit()).collect(),
+            uvs,
+            materials,
+            ..Default::default()
         }
     }
-    pub fn from_obj(face: &obj::element::Face, material: Material) -> Self {
-        Self::new(
-            Point::from_obj(&face.vertexes[0]),
-            Point::from_obj(&face.vertexes[1]),
-            Point::from_obj(&face.vertexes[2]),
-            material,
-        )
+    pub fn push_triangle(
+        &mut self,
+        indices: [u32; 3],
+        normals: Option<[u32; 3]>,
+        uvs: Option<[u32; 3]>,
+        material: u32,
+    ) {
+        self.indices.push(indices);
+        self.normal_indices.push(normals);
+        self.uv_indices.push(uvs);
+        self.material_ids.push(material);
+        self.face_normals
+            .push(self.face_normal(self.indices.len() - 1));
+    }
+    /**
+     * Normal of the plane of a triangle, flipped to the side of the vertex normals.
+     */
+    fn face_normal(&self, index: usize) -> Vector3D {
+        let [p1, p2, p3] = self.indices[index].map(|i| self.positions[i as usize]);
+        let normal = (p1.to_vec3d(&p2) * p1.to_vec3d(&p3)).unit();
+        match self.normal_indices[index] {
+            Some(n)
+                if n.iter()
+                    .fold(Vector3D::new(0., 0., 0.), |sum, i| {
+                        sum + self.normals[*i as usize]
+                    })
+                    .cdot(&normal)
+                    < 0. =>
+            {
+                -normal
+            }
+            _ => normal,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+    /**
+     * Whether the triangles enclose a volume with their normals outside: every edge is shared
+     * by two triangles running along it in opposite directions, no face normal is flipped
+     * against the winding and the enclosed volume is positive.
+     */
+    pub fn is_closed(&self) -> bool {
+        let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+        for [a, b, c] in &self.indices {
+            for edge in [(*a, *b), (*b, *c), (*c, *a)] {
+                *edges.entry(edge).or_default() += 1;
+            }
+        }
+        let paired = edges
+            .iter()
+            .all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1));
+        let mut volume = 0.;
+        for (i, indices) in self.indices.iter().enumerate() {
+            let [p1, p2, p3] = indices.map(|i| self.positions[i as usize].point_vec);
+            let winding = (p2 - p1) * (p3 - p1);
+            if winding.cdot(&self.face_normals[i]) < 0. {
+                return false;
+            }
+            volume += p1.cdot(&(p2 * p3));
+        }
+        !self.indices.is_empty() && paired && volume > 0.
+    }
+    /**
+     * Move the positions and normals by a transformation, object space textures keep their
+     * coordinates. Meshes which don't move are transformed once instead of on every hit.
+     */
+    pub fn transformed(mut self, transform: &Transform) -> Self {
+        if transform.is_identity() {
+            return self;
+        }
+        let matrix = transform.matrix();
+        let inverse = matrix.inverse();
+        for p in &mut self.positions {
+            *p = matrix.apply_point(p);
+        }
+        for n in &mut self.normals {
+            *n = inverse.apply_normal(n).unit();
+        }
+        self.face_normals = (0..self.len()).map(|i| self.face_normal(i)).collect();
+        self.to_object = Some(inverse);
+        self
+    }
+    /** the triangles of the mesh, they share the mesh without allocation */
+    pub fn triangles(mesh: &Rc<Self>) -> impl Iterator<Item = MeshTriangle> + '_ {
+        (0..mesh.len() as u32).map(|index| MeshTriangle {
+            mesh: Rc::clone(mesh),
+            index,
+        })
+    }
+}
+
+/**
+ * A triangle of a mesh.
+ */
+#[derive(Clone)]
+pub struct MeshTriangle {
+    mesh: Rc<TriangleMesh>,
+    index: u32,
+}
+
+impl MeshTriangle {
+    fn points(&self) -> [&Point; 3] {
+        self.mesh.indices[self.index as usize].map(|i| &self.mesh.positions[i as usize])
+    }
+    fn vertex_normals(&self) -> Option<[Vector3D; 3]> {
+        self.mesh.normal_indices[self.index as usize]
+            .map(|n| n.map(|i| self.mesh.normals[i as usize]))
     }
     fn get_normal(&self) -> Vector3D {
-        self.normal_vec_cache
+        self.mesh.face_normals[self.index as usize]
+    }
+    fn tangents(&self) -> (Vector3D, Vector3D) {
+        let [p1, p2, p3] = self.points();
+        let e1 = p1.to_vec3d(p2);
+        let e2 = p1.to_vec3d(p3);
+        if let Some(uv) = self.mesh.uv_indices[self.index as usize] {
+            let [uv1, uv2, uv3] = uv.map(|i| self.mesh.uvs[i as usize]);
+            let (du1, dv1) = (uv2.0 - uv1.0, uv2.1 - uv1.1);
+            let (du2, dv2) = (uv3.0 - uv1.0, uv3.1 - uv1.1);
+            let det = du1 * dv2 - dv1 * du2;
+            if det.abs() > 1e-12 {
+                return ((dv2 * e1 - dv1 * e2) / det, (du1 * e2 - du2 * e1) / det);
+            }
+        }
+        /* without texture coordinates u and v follow the edges */
+        (e1, e2)
+    }
+    /**
+     * Intersect the ray with the triangle.
+     */
+    pub fn intersect(&self, r: &Ray) -> Option<TriangleHit> {
+        let [p1, p2, p3] = self.points();
+        triangle_hit(r, p1, p2, p3)
     }
 }
 
-impl BoarderDedection for Triangle {
+impl BoarderDedection for MeshTriangle {
     fn x_max(&self) -> f64 {
-        self.p1.x().max(self.p2.x().max(self.p3.x()))
+        let [p1, p2, p3] = self.points();
+        p1.x().max(p2.x().max(p3.x()))
     }
     fn x_min(&self) -> f64 {
-        self.p1.x().min(self.p2.x().min(self.p3.x()))
+        let [p1, p2, p3] = self.points();
+        p1.x().min(p2.x().min(p3.x()))
     }
     fn y_max(&self) -> f64 {
-        self.p1.y().max(self.p2.y().max(self.p3.y()))
+        let [p1, p2, p3] = self.points();
+        p1.y().max(p2.y().max(p3.y()))
     }
     fn y_min(&self) -> f64 {
-        self.p1.y().min(self.p2.y().min(self.p3.y()))
+        let [p1, p2, p3] = self.points();
+        p1.y().min(p2.y().min(p3.y()))
     }
     fn z_max(&self) -> f64 {
-        self.p1.z().max(self.p2.z().max(self.p3.z()))
+        let [p1, p2, p3] = self.points();
+        p1.z().max(p2.z().max(p3.z()))
     }
     fn z_min(&self) -> f64 {
-        self.p1.z().min(self.p2.z().min(self.p3.z()))
+        let [p1, p2, p3] = self.points();
+        p1.z().min(p2.z().min(p3.z()))
     }
 }
 
-impl Object for Triangle {
-    fn hit(&self, r: &Ray) -> Option<f64> {
-        let vec_a = r.origin.to_vec3d(&self.p1);
-        let vec_b = r.origin.to_vec3d(&self.p2);
-        let vec_c = r.origin.to_vec3d(&self.p3);
-        /*
-        d = |x_a x_b x_c|
-            |y_a y_b y_c|
-            |z_a z_b z_c|
-         */
-        let d =
-            vec_a.x * vec_b.y * vec_c.z + vec_b.x * vec_c.y * vec_a.z + vec_a.y * vec_b.z * vec_c.x
-                - vec_c.x * vec_b.y * vec_a.z
-                - vec_b.x * vec_a.y * vec_c.z
-                - vec_c.y * vec_b.z * vec_a.x;
-        let u = (r.direction.x * vec_b.y * vec_c.z
-            + vec_b.x * vec_c.y * r.direction.z
-            + r.direction.y * vec_b.z * vec_c.x
-            - vec_c.x * vec_b.y * r.direction.z
-            - vec_b.x * r.direction.y * vec_c.z
-            - vec_c.y * vec_b.z * r.direction.x)
-            / d;
-        if u < 0. {
-            return None;
-        }
-        let v = (vec_a.x * r.direction.y * vec_c.z
-            + r.direction.x * vec_c.y * vec_a.z
-            + vec_a.y * r.direction.z * vec_c.x
-            - vec_c.x * r.direction.y * vec_a.z
-            - r.direction.x * vec_a.y * vec_c.z
-            - vec_c.y * r.direction.z * vec_a.x)
-            / d;
-        if v < 0. {
-            return None;
-        }
-        let w = (vec_a.x * vec_b.y * r.direction.z
-            + vec_b.x * r.direction.y * vec_a.z
-            + vec_a.y * vec_b.z * r.direction.x
-            - r.direction.x * vec_b.y * vec_a.z
-            - vec_b.x * vec_a.y * r.direction.z
-            - r.direction.y * vec_b.z * vec_a.x)
-            / d;
-        if w < 0. {
-            return None;
+impl Object for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let hit = self
+            .intersect(r)
+            .filter(|hit| hit.t > t_min && hit.t < t_max)?;
+        let (w1, w2, w3) = (1. - hit.u - hit.v, hit.u, hit.v);
+        let point = r.point_at(hit.t);
+        let geometric_normal = self.get_normal();
+
+        /* triangles without vertex normals are flat */
+        let normal = match self.vertex_normals() {
+            Some([n1, n2, n3]) => {
+                let normal = w1 * n1 + w2 * n2 + w3 * n3;
+                if normal.module() < 1e-9 {
+                    geometric_normal
+                } else {
+                    normal.unit()
+                }
+            }
+            None => geometric_normal,
+        };
+        /* triangles without texture coordinates use the barycentric coordinates */
+        let uv = match self.mesh.uv_indices[self.index as usize] {
+            Some(uv) => {
+                let [uv1, uv2, uv3] = uv.map(|i| self.mesh.uvs[i as usize]);
+                (
+                    w1 * uv1.0 + w2 * uv2.0 + w3 * uv3.0,
+                    w1 * uv1.1 + w2 * uv2.1 + w3 * uv3.1,
+                )
+            }
+            None => (w2, w3),
+        };
+        Some(HitRecord {
+            t: hit.t,
+            point,
+            object_point: match &self.mesh.to_object {
+                Some(to_object) => to_object.apply_point(&point),
+                None => point,
+            },
+            geometric_normal,
+            normal,
+            front_face: geometric_normal.cdot(&r.direction) < 0.,
+            uv,
+            tangents: Some(self.tangents()),
+            primitive: self.index,
+            material: &self.mesh.materials[self.mesh.material_ids[self.index as usize] as usize],
+        })
+    }
+}
+
+/**
+ * A mesh as a single object with a BVH of its own, for meshes which are instanced or animated
+ * as a whole.
+ */
+pub struct MeshObject {
+    bvh: BVHNode,
+    closed: bool,
+}
+
+impl MeshObject {
+    pub fn new(mesh: TriangleMesh) -> Self {
+        let closed = mesh.is_closed();
+        let mesh = Rc::new(mesh);
+        let triangles = TriangleMesh::triangles(&mesh)
+            .map(Primitive::Triangle)
+            .collect::<Vec<_>>();
+        Self {
+            bvh: BVHNode::build(&triangles, LEAF_CAPACITY),
+            closed,
         }
-        plane_hit(r, &self.p1, &self.normal(&self.p1))
     }
-    fn material(&self) -> Material {
-        self.material
+}
+
+impl BoarderDedection for MeshObject {
+    fn x_max(&self) -> f64 {
+        self.bvh.max.x()
     }
-    fn normal(&self, _p: &Point) -> Vector3D {
-        self.get_normal()
+    fn x_min(&self) -> f64 {
+        self.bvh.min.x()
+    }
+    fn y_max(&self) -> f64 {
+        self.bvh.max.y()
+    }
+    fn y_min(&self) -> f64 {
+        self.bvh.min.y()
+    }
+    fn z_max(&self) -> f64 {
+        self.bvh.max.z()
+    }
+    fn z_min(&self) -> f64 {
+        self.bvh.min.z()
     }
 }
 
-pub struct Polygon {
-    triangles: Vec<Triangle>,
-    material: Material,
+impl Object for MeshObject {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        self.bvh.closest_hit(r, t_min, t_max)
+    }
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+/**
+ * An element of a BVH, triangles of meshes are stored by value so they need no allocation.
+ */
+#[derive(Clone)]
+pub enum Primitive {
+    Object(Rc<dyn Object>),
+    Triangle(MeshTriangle),
 }
 
-impl Polygon {
-    pub fn new(points: &[Point], material: Material) -> Self {
-        let mut triangles = Vec::new();
-        let mut p = 1;
-        while p + 1 < points.len() {
-            triangles.push(Triangle::new(points[0], points[p], points[p + 1], material));
-            p += 1;
+impl Primitive {
+    pub fn object(&self) -> &dyn Object {
+        match self {
+            Self::Object(object) => object.as_ref(),
+            Self::Triangle(triangle) => triangle,
+        }
+    }
+}
+
+/**
+ * An object placed in the scene by a transformation, which may move during the shutter interval.
+ */
+pub struct Instance {
+    object: Rc<dyn Object>,
+    /** transformation at `time_start` */
+    start: Transform,
+    /** transformation at `time_end` */
+    end: Transform,
+    time_start: f64,
+    time_end: f64,
+    /** object to world and world to object matrices of an instance which doesn't move */
+    fixed: Option<(Matrix, Matrix)>,
+    /** cached bounding box enclosing the whole motion */
+    aabb: (Point, Point),
+}
+
+impl Instance {
+    /** number of time steps used to enclose the motion in a bounding box */
+    const MOTION_STEPS: usize = 8;
+
+    pub fn new(object: Rc<dyn Object>, transform: Transform) -> Self {
+        Self::new_moving(object, transform, transform, 0., 0.)
+    }
+    pub fn new_moving(
+        object: Rc<dyn Object>,
+        start: Transform,
+        end: Transform,
+        time_start: f64,
+        time_end: f64,
+    ) -> Self {
+        let moving = start != end && time_end > time_start;
+        let corners: [Vector3D; 8] = std::array::from_fn(|i| {
+            Vector3D::new(
+                if i & 1 == 0 {
+                    object.x_min()
+                } else {
+                    object.x_max()
+                },
+                if i & 2 == 0 {
+                    object.y_min()
+                } else {
+                    object.y_max()
+                },
+                if i & 4 == 0 {
+                    object.z_min()
+                } else {
+                    object.z_max()
+                },
+            )
+        });
+
+        let mut min = Vector3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = -min;
+        let steps = if moving { Self::MOTION_STEPS } else { 0 };
+        for step in 0..=steps {
+            let matrix = start.lerp(&end, step as f64 / steps.max(1) as f64).matrix();
+            for corner in &corners {
+                let p = matrix.apply_point(&Point::from_vec3d(*corner));
+                min = Vector3D::new(min.x.min(p.x()), min.y.min(p.y()), min.z.min(p.z()));
+                max = Vector3D::new(max.x.max(p.x()), max.y.max(p.y()), max.z.max(p.z()));
+            }
         }
+        /*
+         * Without rotation the corners move on straight lines between the steps. Rotating corners
+         * move on arcs around the origin of the object, which stay within half the distance they
+         * travel in a step from the positions at the steps.
+         */
+        let turn = end.rotate - start.rotate;
+        let turn = (turn.x.abs() + turn.y.abs() + turn.z.abs()).to_radians();
+        if moving && turn > 0. {
+            let scaled = |scale: Vector3D, c: &Vector3D| {
+                Vector3D::new(scale.x * c.x, scale.y * c.y, scale.z * c.z)
+            };
+            let padding = corners
+                .iter()
+                .map(|c| {
+                    let (a, b) = (scaled(start.scale, c), scaled(end.scale, c));
+                    let travel = (end.translate - start.translate).module()
+                        + (b - a).module()
+                        + a.module().max(b.module()) * turn;
+                    travel / (2 * steps) as f64
+                })
+                .fold(0., f64::max);
+            min = min - padding;
+            max = max + padding;
+        }
+
         Self {
-            triangles,
-            material,
+            object,
+            start,
+            end,
+            time_start,
+            time_end,
+            fixed: (!moving).then(|| (start.matrix(), start.matrix().inverse())),
+            aabb: (Point::from_vec3d(min), Point::from_vec3d(max)),
         }
     }
-    pub fn from_obj(face: &obj::element::Face, material: Material) -> Self {
-        let mut points = Vec::new();
-        for v in &face.vertexes {
-            points.push(Point::from_obj(v));
+    /**
+     * Get the matrices transforming object space to world space and back at the given time,
+     * they are only interpolated if the instance moves.
+     */
+    fn matrices(&self, time: f64) -> (Matrix, Matrix) {
+        if let Some(matrices) = self.fixed {
+            return matrices;
         }
-        Self::new(&points, material)
+        let t = ((time - self.time_start) / (self.time_end - self.time_start)).clamp(0., 1.);
+        let forward = self.start.lerp(&self.end, t).matrix();
+        (forward, forward.inverse())
+    }
+    /**
+     * The ray in object space, distances in object space are scaled by the module of the
+     * transformed direction.
+     */
+    fn local_ray(r: &Ray, inverse: &Matrix) -> (Ray, f64) {
+        let direction = inverse.apply_vector(&r.direction);
+        let local = Ray::new(inverse.apply_point(&r.origin), direction, r.time);
+        (local, direction.module())
+    }
+    /** move a hit of the ray in object space back to world space */
+    fn to_world(hit: &mut HitRecord, r: &Ray, scale: f64, forward: &Matrix, inverse: &Matrix) {
+        hit.t /= scale;
+        hit.point = r.point_at(hit.t);
+        hit.geometric_normal = inverse.apply_normal(&hit.geometric_normal);
+        hit.normal = inverse.apply_normal(&hit.normal);
+        hit.tangents = hit
+            .tangents
+            .map(|(dpdu, dpdv)| (forward.apply_vector(&dpdu), forward.apply_vector(&dpdv)));
     }
 }
 
-impl BoarderDedection for Polygon {
+impl BoarderDedection for Instance {
     fn x_max(&self) -> f64 {
-        let mut max = self.triangles[0].x_max();
-        for tri in &self.triangles {
-            if tri.x_max() > max {
-                max = tri.x_max();
-            }
-        }
-        max
+        self.aabb.1.x()
     }
     fn x_min(&self) -> f64 {
-        let mut min = self.triangles[0].x_min();
-        for tri in &self.triangles {
-            if tri.x_min() > min {
-                min = tri.x_min();
-            }
-        }
-        min
+        self.aabb.0.x()
     }
     fn y_max(&self) -> f64 {
-        let mut max = self.triangles[0].y_max();
-        for tri in &self.triangles {
-            if tri.y_max() > max {
-                max = tri.y_max();
-            }
-        }
-        max
+        self.aabb.1.y()
     }
     fn y_min(&self) -> f64 {
-        let mut min = self.triangles[0].y_min();
-        for tri in &self.triangles {
-            if tri.y_min() > min {
-                min = tri.y_min();
-            }
-        }
-        min
+        self.aabb.0.y()
     }
     fn z_max(&self) -> f64 {
-        let mut max = self.triangles[0].z_max();
-        for tri in &self.triangles {
-            if tri.z_max() > max {
-                max = tri.z_max();
-            }
-        }
-        max
+        self.aabb.1.z()
     }
     fn z_min(&self) -> f64 {
-        let mut min = self.triangles[0].z_min();
-        for tri in &self.triangles {
-            if tri.z_min() > min {
-                min = tri.z_min();
-            }
+        self.aabb.0.z()
+    }
+}
+
+impl Object for Instance {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (forward, inverse) = self.matrices(r.time);
+        let (local, scale) = Self::local_ray(r, &inverse);
+        let mut hit = self.object.hit(&local, t_min * scale, t_max * scale)?;
+        Self::to_world(&mut hit, r, scale, &forward, &inverse);
+        Some(hit)
+    }
+    fn hit_all<'a>(&'a self, r: &Ray, t_min: f64, t_max: f64, hits: &mut Vec<HitRecord<'a>>) {
+        let (forward, inverse) = self.matrices(r.time);
+        let (local, scale) = Self::local_ray(r, &inverse);
+        let start = hits.len();
+        self.object
+            .hit_all(&local, t_min * scale, t_max * scale, hits);
+        for hit in &mut hits[start..] {
+            Self::to_world(hit, r, scale, &forward, &inverse);
         }
-        min
+    }
+    fn is_bounded(&self) -> bool {
+        self.object.is_bounded()
+    }
+    fn is_closed(&self) -> bool {
+        self.object.is_closed()
     }
 }
 
-impl Object for Polygon {
-    fn hit(&self, r: &Ray) -> Option<f64> {
-        for t in &self.triangles {
-            if let Some(t) = t.hit(r) {
-                return Some(t);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64) -> Point {
+        Point::new(x, y, 0.)
+    }
+
+    /** rays with directions along every axis and both signs, so every shear is used */
+    const DIRECTIONS: [(f64, f64, f64); 8] = [
+        (0., 0., -1.),
+        (0., 0., 1.),
+        (0.3, -0.2, -1.),
+        (-0.5, 0.7, 1.),
+        (1., 0.5, -0.2),
+        (-1., -0.3, 0.4),
+        (0.1, -1., 0.3),
+        (-0.2, 1., -0.6),
+    ];
+
+    /** a ray reaching `target` at distance `t` */
+    fn ray_to(target: &Point, direction: (f64, f64, f64), t: f64) -> Ray {
+        let d = Vector3D::from(direction).unit();
+        let origin = Point::from_vec3d(target.point_vec - t * d);
+        Ray::new(origin, d, 0.)
+    }
+
+    fn count_hits(r: &Ray, triangles: &[[Point; 3]]) -> usize {
+        triangles
+            .iter()
+            .filter(|[p1, p2, p3]| triangle_hit(r, p1, p2, p3).is_some())
+            .count()
+    }
+
+    #[test]
+    fn triangle_hit_distance_and_barycentric_coordinates() {
+        let [p1, p2, p3] = [point(0., 0.), point(1., 0.), point(0., 1.)];
+        for direction in DIRECTIONS {
+            let r = ray_to(&point(0.25, 0.5), direction, 2.);
+            let hit = triangle_hit(&r, &p1, &p2, &p3).unwrap();
+            assert!((hit.t - 2.).abs() < 1e-9, "{direction:?}: {}", hit.t);
+            assert!((hit.u - 0.25).abs() < 1e-9, "{direction:?}: {}", hit.u);
+            assert!((hit.v - 0.5).abs() < 1e-9, "{direction:?}: {}", hit.v);
+            /* the winding doesn't matter */
+            let hit = triangle_hit(&r, &p1, &p3, &p2).unwrap();
+            assert!((hit.u - 0.5).abs() < 1e-9 && (hit.v - 0.25).abs() < 1e-9);
+
+            assert!(
+                triangle_hit(&ray_to(&point(0.6, 0.6), direction, 2.), &p1, &p2, &p3).is_none()
+            );
+            /* the triangle is behind the ray */
+            assert!(
+                triangle_hit(&ray_to(&point(0.25, 0.5), direction, -2.), &p1, &p2, &p3).is_none()
+            );
         }
-        None
     }
-    fn material(&self) -> Material {
-        self.material
+
+    #[test]
+    fn shared_edge_is_hit_once() {
+        /* a square split along its diagonal */
+        let square = [
+            [point(0., 0.), point(1., 0.), point(1., 1.)],
+            [point(0., 0.), point(1., 1.), point(0., 1.)],
+        ];
+        for direction in DIRECTIONS {
+            for target in [point(0.5, 0.5), point(0.125, 0.125), point(0.3, 0.3)] {
+                let r = ray_to(&target, direction, 3.);
+                assert_eq!(count_hits(&r, &square), 1, "{direction:?} {target:?}");
+            }
+        }
     }
-    fn normal(&self, _p: &Point) -> Vector3D {
-        self.triangles[0].get_normal()
+
+    #[test]
+    fn shared_vertex_is_hit_once() {
+        /* a fan of triangles around the origin */
+        let ring = [
+            point(1., 0.),
+            point(1., 1.),
+            point(0., 1.),
+            point(-1., 0.),
+            point(-1., -1.),
+            point(0., -1.),
+        ];
+        let fan = (0..ring.len())
+            .map(|i| [point(0., 0.), ring[i], ring[(i + 1) % ring.len()]])
+            .collect::<Vec<_>>();
+        for direction in DIRECTIONS {
+            let r = ray_to(&point(0., 0.), direction, 3.);
+            assert_eq!(count_hits(&r, &fan), 1, "{direction:?}");
+        }
     }
 }
@@ -25,15 +25,6 @@ impl Point {
     pub fn z(&self) -> f64 {
         self.point_vec.z
     }
-    pub fn set_x(&mut self, value: f64) {
-        self.point_vec.x = value;
-    }
-    pub fn set_y(&mut self, value: f64) {
-        self.point_vec.y = value;
-    }
-    pub fn set_z(&mut self, value: f64) {
-        self.point_vec.z = value;
-    }
     pub fn to_vec3d(self, other: &Self) -> Vector3D {
         -self.point_vec + other.point_vec
     }
@@ -0,0 +1,925 @@
+use crate::{
+    bvh::{self, BoarderDedection, bounds_from_aabb},
+    material::Material,
+    objects::{HitRecord, Object, push_until},
+    point::Point,
+    ray::Ray,
+    vector::Vector3D,
+};
+use std::f64::consts::PI;
+
+/**
+ * An orthonormal frame, shapes are intersected in the coordinates of their frame,
+ * where they lie around the z axis.
+ */
+#[derive(Clone, Copy)]
+struct Frame {
+    origin: Point,
+    u: Vector3D,
+    v: Vector3D,
+    w: Vector3D,
+}
+
+impl Frame {
+    /** w: the z axis of the frame */
+    fn new(origin: Point, w: Vector3D) -> Self {
+        let w = w.unit();
+        let (u, v) = w.basis();
+        Self { origin, u, v, w }
+    }
+    /** x and y: the x axis of the frame and a direction in its xy plane */
+    fn from_axes(origin: Point, x: Vector3D, y: Vector3D) -> Self {
+        let u = x.unit();
+        let w = (x * y).unit();
+        Self {
+            origin,
+            u,
+            v: w * u,
+            w,
+        }
+    }
+    fn point_to_local(&self, p: &Point) -> Vector3D {
+        self.vector_to_local(&self.origin.to_vec3d(p))
+    }
+    fn vector_to_local(&self, d: &Vector3D) -> Vector3D {
+        Vector3D::new(d.cdot(&self.u), d.cdot(&self.v), d.cdot(&self.w))
+    }
+    fn vector_to_world(&self, d: &Vector3D) -> Vector3D {
+        d.x * self.u + d.y * self.v + d.z * self.w
+    }
+    /** a ray in the coordinates of the frame, distances are kept */
+    fn ray_to_local(&self, ray: &Ray) -> (Vector3D, Vector3D) {
+        (
+            self.point_to_local(&ray.origin),
+            self.vector_to_local(&ray.direction),
+        )
+    }
+    /**
+     * Bounding box of a circle around the z axis at the height z.
+     */
+    fn circle_bounds(&self, z: f64, radius: f64) -> (Point, Point) {
+        let center = self.origin.point_vec + z * self.w;
+        let extent = |w: f64| radius * (1. - w * w).max(0.).sqrt();
+        let extent = Vector3D::new(extent(self.w.x), extent(self.w.y), extent(self.w.z));
+        (
+            Point::from_vec3d(center - extent),
+            Point::from_vec3d(center + extent),
+        )
+    }
+    /** build the hit record of a hit in the coordinates of the frame */
+    fn record<'a>(
+        &self,
+        ray: &Ray,
+        (t, normal, uv, (dpdu, dpdv)): Candidate,
+        material: &'a Material,
+    ) -> HitRecord<'a> {
+        record(
+            ray,
+            t,
+            self.vector_to_world(&normal),
+            uv,
+            Some((self.vector_to_world(&dpdu), self.vector_to_world(&dpdv))),
+            material,
+        )
+    }
+}
+
+/** grow a bounding box, so flat shapes keep a volume */
+fn pad((min, max): (Point, Point)) -> (Point, Point) {
+    const PADDING: f64 = 1e-9;
+    (
+        Point::from_vec3d(min.point_vec - PADDING),
+        Point::from_vec3d(max.point_vec + PADDING),
+    )
+}
+
+/**
+ * Build the hit record of a shape, normal: the outward normal in world space
+ */
+fn record<'a>(
+    ray: &Ray,
+    t: f64,
+    normal: Vector3D,
+    uv: (f64, f64),
+    tangents: Option<(Vector3D, Vector3D)>,
+    material: &'a Material,
+) -> HitRecord<'a> {
+    let point = ray.point_at(t);
+    HitRecord {
+        t,
+        point,
+        object_point: point,
+        geometric_normal: normal,
+        normal,
+        front_face: normal.cdot(&ray.direction) < 0.,
+        uv,
+        tangents,
+        primitive: 0,
+        material,
+    }
+}
+
+/** distance, normal, texture coordinates and tangents of a hit in the coordinates of a shape */
+type Candidate = (f64, Vector3D, (f64, f64), (Vector3D, Vector3D));
+
+/** distance where a ray crosses a box, with the axis of the face and whether the ray enters there */
+pub type Slab = (f64, usize, bool);
+
+/**
+ * Where a ray enters and leaves the box from min to max, (o, d): the ray in the coordinates of the box.
+ */
+pub fn slabs((o, d): (Vector3D, Vector3D), (min, max): (Vector3D, Vector3D)) -> Option<[Slab; 2]> {
+    let (mut near, mut near_axis) = (f64::NEG_INFINITY, 0);
+    let (mut far, mut far_axis) = (f64::INFINITY, 0);
+    for axis in 0..3 {
+        if d[axis] == 0. {
+            if o[axis] < min[axis] || o[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t0 = (min[axis] - o[axis]) / d[axis];
+        let t1 = (max[axis] - o[axis]) / d[axis];
+        let (t0, t1) = (t0.min(t1), t0.max(t1));
+        if t0 > near {
+            (near, near_axis) = (t0, axis);
+        }
+        if t1 < far {
+            (far, far_axis) = (t1, axis);
+        }
+    }
+    if near > far {
+        return None;
+    }
+    Some([(near, near_axis, true), (far, far_axis, false)])
+}
+
+/**
+ * Hit on a face of the box from min to max, every face is mapped to the whole texture.
+ */
+fn box_face(
+    (o, d): (Vector3D, Vector3D),
+    (min, max): (Vector3D, Vector3D),
+    (t, axis, entering): Slab,
+) -> Candidate {
+    let sign = if (d[axis] > 0.) == entering { -1. } else { 1. };
+    let unit = |axis: usize| {
+        Vector3D::new(
+            (axis == 0) as u8 as f64,
+            (axis == 1) as u8 as f64,
+            (axis == 2) as u8 as f64,
+        )
+    };
+    let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+    let p = o + t * d;
+    let size = max - min;
+    (
+        t,
+        sign * unit(axis),
+        (
+            (p[a] - min[a]) / size[a].max(1e-12),
+            (p[b] - min[b]) / size[b].max(1e-12),
+        ),
+        (size[a] * unit(a), size[b] * unit(b)),
+    )
+}
+
+/**
+ * Solve `a t^2 + b t + c = 0`, the roots are in ascending order.
+ */
+fn quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    /* avoid subtracting close numbers */
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (t0, t1) = if q == 0. { (0., 0.) } else { (q / a, c / q) };
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+fn polynomial(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0., |sum, c| sum * x + c)
+}
+
+/**
+ * Find the real roots of a polynomial up to degree four within [lo, hi] in ascending order,
+ * the coefficients start at the constant term. The roots of the derivative split the interval
+ * into monotonic parts, each part holds at most one root which is found by bisection.
+ * Returns the roots with their count.
+ */
+fn polynomial_roots(coefficients: &[f64], lo: f64, hi: f64) -> ([f64; 4], usize) {
+    let mut roots = [0.; 4];
+    let mut count = 0;
+    match coefficients {
+        [] | [_] => return (roots, 0),
+        [c, b] => {
+            let x = -c / b;
+            if *b != 0. && x >= lo && x <= hi {
+                roots[0] = x;
+                count = 1;
+            }
+            return (roots, count);
+        }
+        _ => {}
+    }
+    let degree = coefficients.len() - 1;
+    let mut derivative = [0.; 4];
+    for (i, c) in coefficients[1..].iter().enumerate() {
+        derivative[i] = (i + 1) as f64 * c;
+    }
+    let (extrema, extrema_count) = polynomial_roots(&derivative[..degree], lo, hi);
+    let mut bounds = [0.; 6];
+    bounds[0] = lo;
+    bounds[1..=extrema_count].copy_from_slice(&extrema[..extrema_count]);
+    bounds[extrema_count + 1] = hi;
+
+    for part in bounds[..extrema_count + 2].windows(2) {
+        let (mut a, mut b) = (part[0], part[1]);
+        let (fa, fb) = (polynomial(coefficients, a), polynomial(coefficients, b));
+        let root = if fa == 0. {
+            if count > 0 && roots[count - 1] == a {
+                continue;
+            }
+            a
+        } else if fb == 0. {
+            b
+        } else if fa.signum() == fb.signum() {
+            continue;
+        } else {
+            for _ in 0..100 {
+                let mid = (a + b) / 2.;
+                if mid <= a || mid >= b {
+                    break;
+                }
+                if polynomial(coefficients, mid).signum() == fa.signum() {
+                    a = mid;
+                } else {
+                    b = mid;
+                }
+            }
+            (a + b) / 2.
+        };
+        roots[count] = root;
+        count += 1;
+    }
+    (roots, count)
+}
+
+/**
+ * An infinite plane, it has no bounds and is kept out of the BVH.
+ */
+pub struct Plane {
+    frame: Frame,
+    material: Material,
+}
+
+impl Plane {
+    pub fn new(point: Point, normal: Vector3D, material: Material) -> Self {
+        Self {
+            frame: Frame::new(point, normal),
+            material,
+        }
+    }
+}
+
+impl BoarderDedection for Plane {
+    fn x_max(&self) -> f64 {
+        f64::INFINITY
+    }
+    fn x_min(&self) -> f64 {
+        f64::NEG_INFINITY
+    }
+    fn y_max(&self) -> f64 {
+        f64::INFINITY
+    }
+    fn y_min(&self) -> f64 {
+        f64::NEG_INFINITY
+    }
+    fn z_max(&self) -> f64 {
+        f64::INFINITY
+    }
+    fn z_min(&self) -> f64 {
+        f64::NEG_INFINITY
+    }
+}
+
+impl Object for Plane {
+    /**
+     * The texture coordinates are the distances along the axes of the plane.
+     */
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = self.frame.ray_to_local(ray);
+        let t = -o.z / d.z;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let p = o + t * d;
+        Some(record(
+            ray,
+            t,
+            self.frame.w,
+            (p.x, p.y),
+            Some((self.frame.u, self.frame.v)),
+            &self.material,
+        ))
+    }
+    fn is_bounded(&self) -> bool {
+        false
+    }
+}
+
+/**
+ * A flat disk facing its normal.
+ */
+pub struct Disk {
+    frame: Frame,
+    radius: f64,
+    material: Material,
+    aabb: (Point, Point),
+}
+
+impl Disk {
+    pub fn new(center: Point, normal: Vector3D, radius: f64, material: Material) -> Self {
+        let frame = Frame::new(center, normal);
+        Self {
+            frame,
+            radius,
+            material,
+            aabb: pad(frame.circle_bounds(0., radius)),
+        }
+    }
+}
+
+bounds_from_aabb!(Disk);
+
+impl Object for Disk {
+    /**
+     * The texture is mapped onto the square around the disk.
+     */
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = self.frame.ray_to_local(ray);
+        let t = -o.z / d.z;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let p = o + t * d;
+        if p.x * p.x + p.y * p.y > self.radius * self.radius {
+            return None;
+        }
+        let size = 2. * self.radius;
+        Some(record(
+            ray,
+            t,
+            self.frame.w,
+            (0.5 + p.x / size, 0.5 + p.y / size),
+            Some((size * self.frame.u, size * self.frame.v)),
+            &self.material,
+        ))
+    }
+}
+
+/**
+ * A parallelogram spanned by two edges from a corner.
+ */
+pub struct Quad {
+    corner: Point,
+    edge_u: Vector3D,
+    edge_v: Vector3D,
+    /** `edge_u * edge_v`, divided by its squared module to get the coordinates of a point */
+    w: Vector3D,
+    normal: Vector3D,
+    material: Material,
+    aabb: (Point, Point),
+}
+
+impl Quad {
+    pub fn new(corner: Point, edge_u: Vector3D, edge_v: Vector3D, material: Material) -> Self {
+        let n = edge_u * edge_v;
+        let c = corner.point_vec;
+        let aabb = [c + edge_u, c + edge_v, c + edge_u + edge_v]
+            .iter()
+            .fold((corner, corner), |aabb, p| {
+                bvh::union(&aabb, &(Point::from_vec3d(*p), Point::from_vec3d(*p)))
+            });
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            w: n / n.cdot(&n),
+            normal: n.unit(),
+            material,
+            aabb: pad(aabb),
+        }
+    }
+}
+
+bounds_from_aabb!(Quad);
+
+impl Object for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let denominator = self.normal.cdot(&ray.direction);
+        if denominator.abs() < 1e-12 {
+            return None;
+        }
+        let t = ray.origin.to_vec3d(&self.corner).cdot(&self.normal) / denominator;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let p = self.corner.to_vec3d(&ray.point_at(t));
+        let u = self.w.cdot(&(p * self.edge_v));
+        let v = self.w.cdot(&(self.edge_u * p));
+        if !(0. ..=1.).contains(&u) || !(0. ..=1.).contains(&v) {
+            return None;
+        }
+        Some(record(
+            ray,
+            t,
+            self.normal,
+            (u, v),
+            Some((self.edge_u, self.edge_v)),
+            &self.material,
+        ))
+    }
+}
+
+/**
+ * An axis-aligned box, see `OrientedBox` for boxes in other orientations.
+ */
+pub struct Cuboid {
+    aabb: (Point, Point),
+    material: Material,
+}
+
+impl Cuboid {
+    /** a and b: two opposite corners */
+    pub fn new(a: Point, b: Point, material: Material) -> Self {
+        Self {
+            aabb: bvh::union(&(a, a), &(b, b)),
+            material,
+        }
+    }
+    fn bounds(&self) -> (Vector3D, Vector3D) {
+        (self.aabb.0.point_vec, self.aabb.1.point_vec)
+    }
+    fn record(&self, ray: &Ray, slab: Slab) -> HitRecord<'_> {
+        let local = (ray.origin.point_vec, ray.direction);
+        let (t, normal, uv, tangents) = box_face(local, self.bounds(), slab);
+        record(ray, t, normal, uv, Some(tangents), &self.material)
+    }
+}
+
+bounds_from_aabb!(Cuboid);
+
+impl Object for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        /* the far side is hit from inside the box */
+        let slab = slabs((ray.origin.point_vec, ray.direction), self.bounds())?
+            .into_iter()
+            .find(|(t, ..)| *t > t_min && *t < t_max)?;
+        Some(self.record(ray, slab))
+    }
+    fn is_closed(&self) -> bool {
+        true
+    }
+    fn hit_all<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, hits: &mut Vec<HitRecord<'a>>) {
+        if let Some(slabs) = slabs((ray.origin.point_vec, ray.direction), self.bounds()) {
+            let sorted = slabs.into_iter().filter(|(t, ..)| *t > t_min);
+            push_until(hits, t_max, sorted.map(|slab| self.record(ray, slab)));
+        }
+    }
+}
+
+/**
+ * A box around a center along the axes of its frame.
+ */
+pub struct OrientedBox {
+    frame: Frame,
+    /** half of the size along each axis of the frame */
+    half: Vector3D,
+    material: Material,
+    aabb: (Point, Point),
+}
+
+impl OrientedBox {
+    /** axis_x and axis_y: the x axis of the box and a direction in its xy plane */
+    pub fn new(
+        center: Point,
+        axis_x: Vector3D,
+        axis_y: Vector3D,
+        half: Vector3D,
+        material: Material,
+    ) -> Self {
+        let frame = Frame::from_axes(center, axis_x, axis_y);
+        let extent = |i: usize| {
+            frame.u[i].abs() * half.x + frame.v[i].abs() * half.y + frame.w[i].abs() * half.z
+        };
+        let extent = Vector3D::new(extent(0), extent(1), extent(2));
+        Self {
+            frame,
+            half,
+            material,
+            aabb: (
+                Point::from_vec3d(center.point_vec - extent),
+                Point::from_vec3d(center.point_vec + extent),
+            ),
+        }
+    }
+    fn record(&self, ray: &Ray, local: (Vector3D, Vector3D), slab: Slab) -> HitRecord<'_> {
+        let face = box_face(local, (-self.half, self.half), slab);
+        self.frame.record(ray, face, &self.material)
+    }
+}
+
+bounds_from_aabb!(OrientedBox);
+
+impl Object for OrientedBox {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let local = self.frame.ray_to_local(ray);
+        let slab = slabs(local, (-self.half, self.half))?
+            .into_iter()
+            .find(|(t, ..)| *t > t_min && *t < t_max)?;
+        Some(self.record(ray, local, slab))
+    }
+    fn is_closed(&self) -> bool {
+        true
+    }
+    fn hit_all<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, hits: &mut Vec<HitRecord<'a>>) {
+        let local = self.frame.ray_to_local(ray);
+        if let Some(slabs) = slabs(local, (-self.half, self.half)) {
+            let sorted = slabs.into_iter().filter(|(t, ..)| *t > t_min);
+            push_until(
+                hits,
+                t_max,
+                sorted.map(|slab| self.record(ray, local, slab)),
+            );
+        }
+    }
+}
+
+/**
+ * A cone frustum from one point to another, closed by disks at both ends.
+ * Cylinders are cones of equal radii.
+ */
+pub struct Cone {
+    frame: Frame,
+    height: f64,
+    /** radius at the start */
+    radius_start: f64,
+    /** radius at the end */
+    radius_end: f64,
+    material: Material,
+    aabb: (Point, Point),
+}
+
+impl Cone {
+    pub fn new(
+        start: Point,
+        end: Point,
+        radius_start: f64,
+        radius_end: f64,
+        material: Material,
+    ) -> Self {
+        let axis = start.to_vec3d(&end);
+        let frame = Frame::new(start, axis);
+        let height = axis.module();
+        Self {
+            frame,
+            height,
+            radius_start,
+            radius_end,
+            material,
+            aabb: pad(bvh::union(
+                &frame.circle_bounds(0., radius_start),
+                &frame.circle_bounds(height, radius_end),
+            )),
+        }
+    }
+}
+
+bounds_from_aabb!(Cone);
+
+impl Cone {
+    /**
+     * Hits of the side and of the ends in ascending order, in the coordinates of the frame.
+     * The side is mapped by the angle around the axis and the height, the ends are mapped like disks.
+     */
+    fn candidates(&self, ray: &Ray) -> impl Iterator<Item = Candidate> {
+        let (o, d) = self.frame.ray_to_local(ray);
+        let h = self.height;
+        /* radius grows by k along the axis */
+        let k = (self.radius_end - self.radius_start) / h;
+        let radius_at = |z: f64| self.radius_start + k * z;
+
+        let mut candidates: [Option<Candidate>; 4] = [None; 4];
+
+        /* side, x^2 + y^2 = (r0 + k z)^2 */
+        let r0 = radius_at(o.z);
+        if let Some((t0, t1)) = quadratic(
+            d.x * d.x + d.y * d.y - k * k * d.z * d.z,
+            2. * (o.x * d.x + o.y * d.y - k * r0 * d.z),
+            o.x * o.x + o.y * o.y - r0 * r0,
+        ) {
+            for (candidate, t) in candidates.iter_mut().zip([t0, t1]) {
+                let p = o + t * d;
+                if !(0. ..=h).contains(&p.z) {
+                    continue;
+                }
+                let radius = radius_at(p.z);
+                let normal = Vector3D::new(p.x, p.y, -k * radius);
+                let normal = if normal.module() > 1e-12 {
+                    normal.unit()
+                } else {
+                    Vector3D::new(0., 0., -k.signum())
+                };
+                let (dpdv_x, dpdv_y) = if radius > 1e-12 {
+                    (k * p.x / radius, k * p.y / radius)
+                } else {
+                    (0., 0.)
+                };
+                *candidate = Some((
+                    t,
+                    normal,
+                    (0.5 + p.y.atan2(p.x) / (2. * PI), p.z / h),
+                    (
+                        2. * PI * Vector3D::new(-p.y, p.x, 0.),
+                        h * Vector3D::new(dpdv_x, dpdv_y, 1.),
+                    ),
+                ));
+            }
+        }
+
+        /* the closing disks */
+        for (candidate, (z, radius, normal)) in candidates[2..].iter_mut().zip([
+            (0., self.radius_start, Vector3D::new(0., 0., -1.)),
+            (h, self.radius_end, Vector3D::new(0., 0., 1.)),
+        ]) {
+            if d.z == 0. || radius <= 0. {
+                continue;
+            }
+            let t = (z - o.z) / d.z;
+            let p = o + t * d;
+            if p.x * p.x + p.y * p.y <= radius * radius {
+                let size = 2. * radius;
+                *candidate = Some((
+                    t,
+                    normal,
+                    (0.5 + p.x / size, 0.5 + p.y / size),
+                    (Vector3D::new(size, 0., 0.), Vector3D::new(0., size, 0.)),
+                ));
+            }
+        }
+
+        let t = |candidate: &Option<Candidate>| candidate.map_or(f64::INFINITY, |(t, ..)| t);
+        candidates.sort_by(|a, b| t(a).total_cmp(&t(b)));
+        candidates.into_iter().flatten()
+    }
+    fn record(&self, ray: &Ray, candidate: Candidate) -> HitRecord<'_> {
+        self.frame.record(ray, candidate, &self.material)
+    }
+}
+
+impl Object for Cone {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let candidate = self
+            .candidates(ray)
+            .find(|(t, ..)| *t > t_min && *t < t_max)?;
+        Some(self.record(ray, candidate))
+    }
+    fn is_closed(&self) -> bool {
+        true
+    }
+    fn hit_all<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64, hits: &mut Vec<HitRecord<'a>>) {
+        let sorted = self.candidates(ray).filter(|(t, ..)| *t > t_min);
+        push_until(
+            hits,
+            t_max,
+            sorted.map(|candidate| self.record(ray, candidate)),
+        );
+    }
+}
+
+/**
+ * A torus around an axis, the tube of radius `minor` follows a circle of radius `major`.
+ */
+pub struct Torus {
+    frame: Frame,
+    major: f64,
+    minor: f64,
+    material: Material,
+    aabb: (Point, Point),
+}
+
+impl Torus {
+    pub fn new(center: Point, axis: Vector3D, major: f64, minor: f64, material: Material) -> Self {
+        let frame = Frame::new(center, axis);
+        let (min, max) = frame.circle_bounds(0., major);
+        Self {
+            frame,
+            major,
+            minor,
+            material,
+            aabb: (
+                Point::from_vec3d(min.point_vec - minor),
+                Point::from_vec3d(max.point_vec + minor),
+            ),
+        }
+    }
+}
+
+bounds_from_aabb!(Torus);
+
+impl Object for Torus {
+    /**
+     * u goes around the axis and v around the tube.
+     */
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (o, d) = self.frame.ray_to_local(ray);
+        let (major, minor) = (self.major, self.minor);
+
+        /* the roots are searched within the bounding sphere, starting from its entry for precision */
+        let b = o.cdot(&d);
+        let c = o.cdot(&o) - (major + minor).powi(2);
+        let discriminant = b * b - c;
+        if discriminant < 0. {
+            return None;
+        }
+        let lo = (-b - discriminant.sqrt()).max(t_min);
+        let hi = (-b + discriminant.sqrt()).min(t_max);
+        if lo >= hi {
+            return None;
+        }
+        let o = o + lo * d;
+
+        /* (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) along the ray */
+        let a = o.cdot(&d);
+        let k = o.cdot(&o) + major * major - minor * minor;
+        let r2 = 4. * major * major;
+        let coefficients = [
+            k * k - r2 * (o.x * o.x + o.y * o.y),
+            4. * a * k - 2. * r2 * (o.x * d.x + o.y * d.y),
+            4. * a * a + 2. * k - r2 * (d.x * d.x + d.y * d.y),
+            4. * a,
+            1.,
+        ];
+        let (roots, count) = polynomial_roots(&coefficients, 0., hi - lo);
+        let s = *roots[..count].iter().find(|s| lo + *s > t_min)?;
+
+        let p = o + s * d;
+        let theta = p.y.atan2(p.x);
+        let ring = Vector3D::new(major * theta.cos(), major * theta.sin(), 0.);
+        let normal = (p - ring).unit();
+        let phi = normal
+            .z
+            .atan2(normal.x * theta.cos() + normal.y * theta.sin());
+        let dpdu = 2. * PI * Vector3D::new(-p.y, p.x, 0.);
+        let dpdv = (2. * PI * minor)
+            * Vector3D::new(
+                -phi.sin() * theta.cos(),
+                -phi.sin() * theta.sin(),
+                phi.cos(),
+            );
+        Some(record(
+            ray,
+            lo + s,
+            self.frame.vector_to_world(&normal),
+            (0.5 + theta / (2. * PI), 0.5 + phi / (2. * PI)),
+            Some((
+                self.frame.vector_to_world(&dpdu),
+                self.frame.vector_to_world(&dpdv),
+            )),
+            &self.material,
+        ))
+    }
+    fn is_closed(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{actual:?} != {expected:?}");
+        }
+    }
+
+    fn roots(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+        let (roots, count) = polynomial_roots(coefficients, lo, hi);
+        roots[..count].to_vec()
+    }
+
+    fn ray(origin: (f64, f64, f64), direction: (f64, f64, f64)) -> Ray {
+        Ray::new(Point::from_vec3d(origin.into()), direction.into(), 0.)
+    }
+
+    /** distance and normal of every hit */
+    fn hits(object: &dyn Object, r: &Ray) -> Vec<(f64, (f64, f64, f64))> {
+        let mut hits = Vec::new();
+        object.hit_all(r, 1e-9, f64::INFINITY, &mut hits);
+        hits.iter().map(|hit| (hit.t, hit.normal.into())).collect()
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_eq!(quadratic(2., -6., 4.), Some((1., 2.)));
+        assert_eq!(quadratic(1., 0., -4.), Some((-2., 2.)));
+        assert_eq!(quadratic(1., 2., 1.), Some((-1., -1.)));
+        assert_eq!(quadratic(1., 0., 1.), None);
+        /* a linear equation has a single root */
+        assert_eq!(quadratic(0., 2., -4.), Some((2., 2.)));
+        assert_eq!(quadratic(0., 0., 1.), None);
+        /* the small root keeps its precision */
+        let (small, large) = quadratic(1., -1e8, 1.).unwrap();
+        assert!((small * 1e8 - 1.).abs() < 1e-12, "{small}");
+        assert!((large * 1e-8 - 1.).abs() < 1e-12, "{large}");
+    }
+
+    #[test]
+    fn polynomial_roots_within_the_interval() {
+        /* (x - 1)(x - 2)(x - 3)(x - 4) */
+        let quartic = [24., -50., 35., -10., 1.];
+        assert_close(&roots(&quartic, 0., 5.), &[1., 2., 3., 4.]);
+        assert_close(&roots(&quartic, 2.5, 10.), &[3., 4.]);
+        assert_close(&roots(&quartic, 4.5, 10.), &[]);
+        /* (x - 1)^2 (x - 3), the double root is found once */
+        assert_close(&roots(&[-3., 7., -5., 1.], 0., 5.), &[1., 3.]);
+        assert_close(&roots(&[1., 0., 1.], -10., 10.), &[]);
+        assert_close(&roots(&[-1., 2.], 0., 1.), &[0.5]);
+        assert_close(&roots(&[-1., 2.], 0.6, 1.), &[]);
+        assert_close(&roots(&[1.], 0., 1.), &[]);
+    }
+
+    #[test]
+    fn slabs_of_a_box() {
+        let bounds = (Vector3D::new(-1., -1., -1.), Vector3D::new(1., 2., 3.));
+        let along_x = (Vector3D::new(-5., 0., 0.), Vector3D::new(1., 0., 0.));
+        assert_eq!(
+            slabs(along_x, bounds),
+            Some([(4., 0, true), (6., 0, false)])
+        );
+        let diagonal = (Vector3D::new(-2., -2., -2.), Vector3D::new(1., 1., 1.));
+        assert_eq!(
+            slabs(diagonal, bounds),
+            Some([(1., 0, true), (3., 0, false)])
+        );
+        /* a ray parallel to a pair of faces only hits between them */
+        let outside = (Vector3D::new(-5., 2.5, 0.), Vector3D::new(1., 0., 0.));
+        assert_eq!(slabs(outside, bounds), None);
+        let past = (Vector3D::new(-5., 0., 0.), Vector3D::new(1., 1., 0.));
+        assert_eq!(slabs(past, bounds), None);
+    }
+
+    #[test]
+    fn cuboid_hits() {
+        let cuboid = Cuboid::new(
+            Point::new(1., 1., 1.),
+            Point::new(-1., -1., -1.),
+            Material::default(),
+        );
+        let hit = hits(&cuboid, &ray((0., 0., 5.), (0., 0., -1.)));
+        assert_eq!(hit, [(4., (0., 0., 1.)), (6., (0., 0., -1.))]);
+        let r = ray((0., 0., 0.), (0., 1., 0.));
+        let hit = cuboid.hit(&r, 1e-9, f64::INFINITY).unwrap();
+        assert_eq!((hit.t, hit.front_face), (1., false));
+        assert!(cuboid.hit(&r, 1e-9, 0.5).is_none());
+    }
+
+    #[test]
+    fn torus_hits() {
+        let torus = Torus::new(
+            Point::new(0., 0., 1.),
+            Vector3D::new(0., 0., 1.),
+            2.,
+            0.5,
+            Material::default(),
+        );
+        /* the ray along the axis passes through the hole */
+        assert!(hits(&torus, &ray((0., 0., 10.), (0., 0., -1.))).is_empty());
+        /* parallel to the axis through the tube */
+        let tube = hits(&torus, &ray((2., 0., 10.), (0., 0., -1.)));
+        assert_close(&tube.iter().map(|h| h.0).collect::<Vec<_>>(), &[8.5, 9.5]);
+        assert!((tube[0].1.2 - 1.).abs() < 1e-6 && (tube[1].1.2 + 1.).abs() < 1e-6);
+        /* across the ring, in and out of the tube on both sides of the hole */
+        let across = hits(&torus, &ray((-10., 0., 1.), (1., 0., 0.)));
+        assert_close(
+            &across.iter().map(|h| h.0).collect::<Vec<_>>(),
+            &[7.5, 8.5, 11.5, 12.5],
+        );
+        let normals = across.iter().map(|h| h.1.0).collect::<Vec<_>>();
+        assert_close(&normals, &[-1., 1., -1., 1.]);
+        /* a ray starting inside the tube leaves it */
+        let inside = hits(&torus, &ray((2., 0., 1.), (1., 0., 0.)));
+        assert_close(&inside.iter().map(|h| h.0).collect::<Vec<_>>(), &[0.5]);
+    }
+}
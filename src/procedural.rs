@@ -0,0 +1,291 @@
+use crate::{
+    texture::{Texture, TextureCoord},
+    vector::Vector3D,
+};
+use std::str::FromStr;
+
+/** the space a procedural texture is evaluated in */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Space {
+    #[default]
+    Object,
+    World,
+    /** texture coordinates, with w = 0 */
+    Uv,
+}
+
+impl FromStr for Space {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "object" => Ok(Self::Object),
+            "world" => Ok(Self::World),
+            "uv" => Ok(Self::Uv),
+            _ => Err(format!("unknown texture space `{s}`")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    Checker,
+    Stripes,
+    Gradient,
+    /** fractal Perlin noise */
+    Noise,
+    /** fractal simplex noise */
+    Simplex,
+    /** distance to the closest feature point of cellular noise */
+    Worley,
+    Marble,
+    Wood,
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "checker" => Ok(Self::Checker),
+            "stripes" => Ok(Self::Stripes),
+            "gradient" => Ok(Self::Gradient),
+            "noise" => Ok(Self::Noise),
+            "simplex" => Ok(Self::Simplex),
+            "worley" => Ok(Self::Worley),
+            "marble" => Ok(Self::Marble),
+            "wood" => Ok(Self::Wood),
+            _ => Err(format!("unknown texture pattern `{s}`")),
+        }
+    }
+}
+
+impl Pattern {
+    /** default of the extra parameter, see `Procedural::param` */
+    pub fn default_param(&self) -> f64 {
+        match self {
+            Self::Noise | Self::Simplex => 4.,
+            Self::Marble => 5.,
+            Self::Wood => 8.,
+            _ => 0.,
+        }
+    }
+}
+
+/**
+ * Hash of an integer lattice point, from 0 to 255.
+ */
+fn hash(x: i64, y: i64, z: i64) -> usize {
+    let mut h =
+        (x.wrapping_mul(73856093) ^ y.wrapping_mul(19349663) ^ z.wrapping_mul(83492791)) as u64;
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    (h & 0xff) as usize
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+/** dot product of a pseudo random gradient with (x, y, z) */
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/**
+ * Improved Perlin noise, from -1 to 1.
+ */
+pub fn perlin(p: Vector3D) -> f64 {
+    let (xi, yi, zi) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+    let (x, y, z) = (p.x - p.x.floor(), p.y - p.y.floor(), p.z - p.z.floor());
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let g = |dx: i64, dy: i64, dz: i64| {
+        grad(
+            hash(xi + dx, yi + dy, zi + dz),
+            x - dx as f64,
+            y - dy as f64,
+            z - dz as f64,
+        )
+    };
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, g(0, 0, 0), g(1, 0, 0)),
+            lerp(u, g(0, 1, 0), g(1, 1, 0)),
+        ),
+        lerp(
+            v,
+            lerp(u, g(0, 0, 1), g(1, 0, 1)),
+            lerp(u, g(0, 1, 1), g(1, 1, 1)),
+        ),
+    )
+}
+
+/**
+ * Simplex noise, from about -1 to 1. The space is split into tetrahedra instead of the cubes
+ * of `perlin`, every point is influenced by 4 corners instead of 8 and there are no
+ * artifacts along the axes.
+ */
+pub fn simplex(p: Vector3D) -> f64 {
+    const SKEW: f64 = 1. / 3.;
+    const UNSKEW: f64 = 1. / 6.;
+    /* the skewed space is split into cubes of 6 tetrahedra each */
+    let s = (p.x + p.y + p.z) * SKEW;
+    let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+    let t = (i + j + k) * UNSKEW;
+    let d = p - Vector3D::new(i - t, j - t, k - t);
+    /* the tetrahedron is found by the order of the coordinates within the cube */
+    let (second, third) = if d.x >= d.y {
+        if d.y >= d.z {
+            ((1, 0, 0), (1, 1, 0))
+        } else if d.x >= d.z {
+            ((1, 0, 0), (1, 0, 1))
+        } else {
+            ((0, 0, 1), (1, 0, 1))
+        }
+    } else if d.y < d.z {
+        ((0, 0, 1), (0, 1, 1))
+    } else if d.x < d.z {
+        ((0, 1, 0), (0, 1, 1))
+    } else {
+        ((0, 1, 0), (1, 1, 0))
+    };
+
+    let mut sum = 0.;
+    for (n, (di, dj, dk)) in [(0, 0, 0), second, third, (1, 1, 1)]
+        .into_iter()
+        .enumerate()
+    {
+        let unskew = n as f64 * UNSKEW;
+        let corner = d - Vector3D::new(di as f64 - unskew, dj as f64 - unskew, dk as f64 - unskew);
+        /* the influence of a corner falls off radially */
+        let falloff = 0.6 - corner.cdot(&corner);
+        if falloff > 0. {
+            let h = hash(i as i64 + di, j as i64 + dj, k as i64 + dk);
+            sum += falloff.powi(4) * grad(h, corner.x, corner.y, corner.z);
+        }
+    }
+    32. * sum
+}
+
+/**
+ * Sum of `octaves` layers of noise, each with double frequency and half amplitude.
+ */
+pub fn fractal(p: Vector3D, octaves: usize, noise: fn(Vector3D) -> f64) -> f64 {
+    let (mut sum, mut amplitude, mut frequency) = (0., 1., 1.);
+    for _ in 0..octaves.max(1) {
+        sum += amplitude * noise(frequency * p);
+        amplitude /= 2.;
+        frequency *= 2.;
+    }
+    sum
+}
+
+/**
+ * Like `fractal`, but with the absolute value of every layer.
+ */
+pub fn turbulence(p: Vector3D, octaves: usize) -> f64 {
+    let (mut sum, mut amplitude, mut frequency) = (0., 1., 1.);
+    for _ in 0..octaves.max(1) {
+        sum += amplitude * perlin(frequency * p).abs();
+        amplitude /= 2.;
+        frequency *= 2.;
+    }
+    sum
+}
+
+/**
+ * Distance to the closest feature point, every unit cell contains one feature point.
+ */
+pub fn worley(p: Vector3D) -> f64 {
+    let (xi, yi, zi) = (p.x.floor() as i64, p.y.floor() as i64, p.z.floor() as i64);
+    let mut closest = f64::INFINITY;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            for dz in -1..=1 {
+                let (cx, cy, cz) = (xi + dx, yi + dy, zi + dz);
+                let feature = Vector3D::new(
+                    cx as f64 + hash(cx, cy, cz) as f64 / 255.,
+                    cy as f64 + hash(cy, cz, cx) as f64 / 255.,
+                    cz as f64 + hash(cz, cx, cy) as f64 / 255.,
+                );
+                closest = closest.min((feature - p).module());
+            }
+        }
+    }
+    closest
+}
+
+/**
+ * A texture blending two colors by a procedural pattern.
+ */
+pub struct Procedural {
+    pub pattern: Pattern,
+    pub space: Space,
+    /** size of one period of the pattern */
+    pub scale: f64,
+    pub color_a: Vector3D,
+    pub color_b: Vector3D,
+    /** octaves of noise, turbulence of marble or rings per period of wood */
+    pub param: f64,
+}
+
+impl Procedural {
+    /**
+     * Get the blend factor between the two colors, from 0 to 1.
+     */
+    fn blend(&self, p: Vector3D) -> f64 {
+        match self.pattern {
+            Pattern::Checker => {
+                let sum = p.x.floor() + p.y.floor() + p.z.floor();
+                if sum.rem_euclid(2.) < 1. { 0. } else { 1. }
+            }
+            Pattern::Stripes => {
+                if p.x.rem_euclid(1.) < 0.5 {
+                    0.
+                } else {
+                    1.
+                }
+            }
+            Pattern::Gradient => p.x.rem_euclid(1.),
+            Pattern::Noise => (fractal(p, self.param as usize, perlin) + 1.) / 2.,
+            Pattern::Simplex => (fractal(p, self.param as usize, simplex) + 1.) / 2.,
+            Pattern::Worley => worley(p),
+            Pattern::Marble => {
+                ((p.x + self.param * turbulence(p, 6)) * std::f64::consts::PI).sin() / 2. + 0.5
+            }
+            Pattern::Wood => {
+                /* rings around the Y axis, disturbed by noise */
+                let r = (p.x * p.x + p.z * p.z).sqrt() + 0.1 * perlin(4. * p);
+                (r * self.param).rem_euclid(1.)
+            }
+        }
+        .clamp(0., 1.)
+    }
+}
+
+impl Texture for Procedural {
+    fn sample(&self, coord: &TextureCoord) -> Vector3D {
+        let p = match self.space {
+            Space::Object => coord.object.point_vec,
+            Space::World => coord.world.point_vec,
+            Space::Uv => Vector3D::new(coord.uv.0, coord.uv.1, 0.),
+        } / self.scale;
+        let t = self.blend(p);
+        self.color_a + t * (self.color_b - self.color_a)
+    }
+}
@@ -1,43 +1,206 @@
-use crate::{bvh::BVHNode, color::Color, point::Point, vector::Vector3D};
+use crate::{
+    color::Color,
+    material::{Lobe, random_cosine_direction},
+    objects::Shear,
+    point::Point,
+    scene::Scene,
+    vector::Vector3D,
+};
+use std::f64::consts::PI;
+
+/** distance to offset secondary rays from surfaces to avoid self intersection */
+const EPSILON: f64 = 1e-6;
+
+/**
+ * Power heuristic of multiple importance sampling.
+ */
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    if pdf <= 0. {
+        0.
+    } else {
+        pdf.powi(2) / (pdf.powi(2) + other_pdf.powi(2))
+    }
+}
+
+/**
+ * Bend the shading normal towards the geometric normal until the mirror reflection of the ray
+ * stays above the geometric surface, interpolated normals may face away from the ray otherwise.
+ */
+fn valid_shading_normal(shading: Vector3D, geometric: &Vector3D, direction: &Vector3D) -> Vector3D {
+    if shading == *geometric {
+        return shading;
+    }
+    /* orient both normals towards the incoming ray */
+    let sign = if geometric.cdot(direction) > 0. {
+        -1.
+    } else {
+        1.
+    };
+    let (s, g) = (sign * shading, sign * *geometric);
+    if s.cdot(&g) <= 0. {
+        return *geometric;
+    }
+    let is_valid = |n: Vector3D| (*direction - 2. * direction.cdot(&n) * n).cdot(&g) > 1e-4;
+    if is_valid(s) {
+        return shading;
+    }
+    /* search the smallest blend towards the geometric normal */
+    let (mut low, mut high) = (0., 1.);
+    for _ in 0..10 {
+        let mid = (low + high) / 2.;
+        if is_valid((s + mid * (g - s)).unit()) {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    sign * (s + high * (g - s)).unit()
+}
 
 #[derive(Clone)]
 pub struct Ray {
     pub origin: Point,
+    /** rays of other directions are built by `Ray::new`, which computes their shear */
     pub direction: Vector3D,
+    /** the moment within the shutter interval when the ray is cast */
+    pub time: f64,
+    shear: Shear,
 }
 
 impl Ray {
-    pub fn new(origin: Point, direction: Vector3D) -> Self {
+    pub fn new(origin: Point, direction: Vector3D, time: f64) -> Self {
+        let direction = direction.unit();
         Self {
             origin,
-            direction: direction.unit(),
+            direction,
+            time,
+            shear: Shear::new(&direction),
         }
     }
+    /** the shear of the triangle tests along this ray */
+    pub fn shear(&self) -> &Shear {
+        &self.shear
+    }
     /**
      * Do ray tracing
      */
-    pub fn trace(&self, bvh: &BVHNode, depth: usize) -> Color {
-        if depth > 0
-            && let Some((t, object)) = bvh.find_closest_hit(self)
+    pub fn trace(&self, scene: &Scene, depth: usize) -> Color {
+        self.trace_mis(scene, depth, None)
+    }
+    /**
+     * bsdf_pdf: the density of the diffuse bounce which generated this ray,
+     * used to weight the environment against next-event estimation.
+     */
+    fn trace_mis(&self, scene: &Scene, depth: usize, bsdf_pdf: Option<f64>) -> Color {
+        let mut color = Color::new();
+        if depth == 0 {
+            color.color_vec = Vector3D::new(0., 0., 0.);
+            return color;
+        }
+
+        let Some(hit) = scene.find_closest_hit(self) else {
+            /* the ray escapes to the environment */
+            let environment = scene.environment();
+            let weight = match bsdf_pdf {
+                Some(pdf) => power_heuristic(pdf, environment.pdf(&self.direction)),
+                None => 1.,
+            };
+            color.color_vec = weight * environment.radiance(&self.direction);
+            return color;
+        };
+
+        let (t, p) = (hit.t, hit.point);
+        let material = hit.material;
+        let coord = material.is_textured().then(|| hit.texture_coord());
+        let (attenuation, fuzz, emission) = match &coord {
+            Some(coord) => (
+                material.attenuation_at(coord),
+                material.fuzz_at(coord),
+                material.emission_at(coord),
+            ),
+            None => (
+                material.attenuation,
+                material.fuzz,
+                Vector3D::from(material.emission),
+            ),
+        };
+
+        /* the geometric normal offsets secondary rays, the shading normal scatters them */
+        let geometric = hit.geometric_normal;
+        let mut normal = hit.normal;
+        if let Some(coord) = &coord
+            && let Some(tangents) = hit.tangents
         {
-            let normal = object.normal(&self.point_at(t));
+            normal = material.perturb_normal(coord, normal, tangents);
+        }
+        let normal = valid_shading_normal(normal, &geometric, &self.direction);
 
-            if object.material().is_light {
-                let emit = object.material().emit;
+        /* the sampled lobe is weighted by the inverse of its probability */
+        let (lobe, probability) = material.sample_lobe();
+        let color_of_lobe = material.lobe_color(lobe, attenuation);
+        let weight = (
+            color_of_lobe.0 / probability,
+            color_of_lobe.1 / probability,
+            color_of_lobe.2 / probability,
+        );
 
-                let mut color = Color::new();
-                color.color_vec = Vector3D::from((emit, emit, emit));
-                return color;
+        if material.is_light {
+            let emit = material.emit;
+            color.color_vec = Vector3D::from((emit, emit, emit));
+        }
+        /* a fully rough reflection scatters light like a Lambertian surface */
+        else if lobe == Lobe::Diffuse || (lobe == Lobe::Reflect && fuzz >= 1.) {
+            /* face the normals towards the incoming ray */
+            let (normal, geometric) = if !hit.front_face {
+                (-normal, -geometric)
             } else {
-                let ref_ray = object.material().scatter(self, t, &normal);
-                let mut color = ref_ray.trace(bvh, depth - 1);
-                color.apply_attenuate(object.material().attenuation);
-                return color;
+                (normal, geometric)
+            };
+            let origin = Point::from_vec3d(p.point_vec + EPSILON * geometric);
+            color.color_vec = Vector3D::new(0., 0., 0.);
+
+            /* next-event estimation of the environment */
+            if let Some((direction, radiance, light_pdf)) = scene.environment().sample() {
+                let cos = direction.cdot(&normal);
+                if cos > 0.
+                    && direction.cdot(&geometric) > 0.
+                    && scene
+                        .find_closest_hit(&Ray::new(origin, direction, self.time))
+                        .is_none()
+                {
+                    let pdf = cos / PI;
+                    color.color_vec +=
+                        (pdf / light_pdf * power_heuristic(light_pdf, pdf)) * radiance;
+                }
             }
-        }
 
-        let mut color = Color::new();
-        color.color_vec = Vector3D::new(0., 0., 0.);
+            let mut direction = random_cosine_direction(&normal);
+            /* directions below the geometric surface are mirrored above it */
+            if direction.cdot(&geometric) <= 0. {
+                direction = direction - 2. * direction.cdot(&geometric) * geometric;
+            }
+            let pdf = (direction.cdot(&normal) / PI).max(0.);
+            color.color_vec += Ray::new(origin, direction, self.time)
+                .trace_mis(scene, depth - 1, Some(pdf))
+                .color_vec;
+            color.apply_attenuate(weight);
+        } else {
+            let mut ref_ray = if lobe == Lobe::Reflect {
+                material.reflect(self, t, &normal, fuzz)
+            } else {
+                material.refract(self, t, &normal, fuzz)
+            };
+            /* move the origin off the surface, to the side the ray leaves to */
+            let offset = if ref_ray.direction.cdot(&geometric) > 0. {
+                EPSILON * geometric
+            } else {
+                -EPSILON * geometric
+            };
+            ref_ray.origin = Point::from_vec3d(p.point_vec + offset);
+            color = ref_ray.trace(scene, depth - 1);
+            color.apply_attenuate(weight);
+        }
+        color.color_vec += emission;
         color
     }
     /**
@@ -49,6 +212,8 @@ impl Ray {
         Self {
             origin: self.point_at(length),
             direction,
+            time: self.time,
+            shear: Shear::new(&direction),
         }
     }
     /**
@@ -61,6 +226,8 @@ impl Ray {
         Self {
             origin: self.point_at(length),
             direction,
+            time: self.time,
+            shear: Shear::new(&direction),
         }
     }
     pub fn point_at(&self, t: f64) -> Point {
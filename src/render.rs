@@ -1,4 +1,4 @@
-use crate::{bvh::BVHNode, color::Color, viewport::Viewport};
+use crate::{camera::Eye, color::Color, scene::Scene, viewport::Viewport};
 
 pub struct Render {
     viewport: Viewport,
@@ -7,7 +7,13 @@ pub struct Render {
 }
 
 impl Render {
-    pub fn render(&self, bvh: &BVHNode) -> ppm::Image {
+    pub fn render(&self, scene: &Scene) -> ppm::Image {
+        self.render_eye(scene, Eye::Mono)
+    }
+    /**
+     * Render the image seen by one eye of a stereo camera.
+     */
+    pub fn render_eye(&self, scene: &Scene, eye: Eye) -> ppm::Image {
         let mut image = ppm::Image::new(self.viewport.pixel_x, self.viewport.pixel_y);
 
         for y in 0..self.viewport.pixel_y {
@@ -16,14 +22,14 @@ impl Render {
                     let mut color = Color::new();
                     color.color_vec = super::vector::Vector3D::new(0., 0., 0.);
                     for _ in 0..self.sample {
-                        let ray = self.viewport.get_ray_random(x, y);
-                        color.color_vec += ray.trace(bvh, self.max_depth).color_vec;
+                        if let Some(ray) = self.viewport.get_ray_random(x, y, eye) {
+                            color.color_vec += ray.trace(scene, self.max_depth).color_vec;
+                        }
                     }
                     color.color_vec = color.color_vec / self.sample as f64;
                     image.set_pixel(x, y, color.to_rgb());
-                } else {
-                    let ray = self.viewport.get_ray_central(x, y);
-                    let color = ray.trace(bvh, self.max_depth);
+                } else if let Some(ray) = self.viewport.get_ray_central(x, y, eye) {
+                    let color = ray.trace(scene, self.max_depth);
                     image.set_pixel(x, y, color.to_rgb());
                 }
             }
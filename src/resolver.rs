@@ -0,0 +1,35 @@
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/**
+ * Find the files referenced by scene files.
+ *
+ * A relative path is looked up next to the file referencing it, then in every search path.
+ */
+#[derive(Default)]
+pub struct Resolver {
+    search_paths: Vec<PathBuf>,
+}
+
+impl Resolver {
+    pub fn new(search_paths: &[String]) -> Self {
+        Self {
+            search_paths: search_paths.iter().map(PathBuf::from).collect(),
+        }
+    }
+    /**
+     * Resolve a path written in `referrer`, missing files are errors of the referrer.
+     */
+    pub fn resolve(&self, path: &str, referrer: &str) -> Result<String> {
+        let base = Path::new(referrer).parent().unwrap_or(Path::new(""));
+        let candidates =
+            std::iter::once(base).chain(self.search_paths.iter().map(PathBuf::as_path));
+        for dir in candidates {
+            let candidate = dir.join(path);
+            if candidate.is_file() {
+                return Ok(candidate.to_string_lossy().into_owned());
+            }
+        }
+        Err(Error::new(referrer, format!("file `{path}` not found")))
+    }
+}
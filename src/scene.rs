@@ -0,0 +1,55 @@
+use crate::{
+    bvh::{self, BVHNode},
+    environment::Environment,
+    objects::{HitRecord, Primitive},
+    ray::Ray,
+};
+use std::rc::Rc;
+
+/**
+ * Geometry of one frame.
+ *
+ * Static geometry is shared between frames, while animated geometry is rebuilt for every frame.
+ * Objects without bounds can't be put in a BVH, they are tested one by one.
+ */
+pub struct Scene {
+    static_bvh: Rc<BVHNode>,
+    dynamic_bvh: Option<BVHNode>,
+    unbounded: Vec<Primitive>,
+    environment: Rc<dyn Environment>,
+}
+
+impl Scene {
+    pub fn new(
+        static_bvh: Rc<BVHNode>,
+        dynamic_bvh: Option<BVHNode>,
+        unbounded: Vec<Primitive>,
+        environment: Rc<dyn Environment>,
+    ) -> Self {
+        Self {
+            static_bvh,
+            dynamic_bvh,
+            unbounded,
+            environment,
+        }
+    }
+    pub fn environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+    /**
+     * Find the closest hit in both static and animated geometry.
+     */
+    pub fn find_closest_hit(&self, ray: &Ray) -> Option<HitRecord<'_>> {
+        let mut closest = self.static_bvh.find_closest_hit(ray, f64::INFINITY);
+        let t_max = closest.as_ref().map_or(f64::INFINITY, |hit| hit.t);
+        if let Some(hit) = self
+            .dynamic_bvh
+            .as_ref()
+            .and_then(|bvh| bvh.find_closest_hit(ray, t_max))
+        {
+            closest = Some(hit);
+        }
+        let t_max = closest.as_ref().map_or(f64::INFINITY, |hit| hit.t);
+        bvh::find_closest_hit(ray, &self.unbounded, t_max).or(closest)
+    }
+}
@@ -1,8 +1,27 @@
-use crate::{point::Point, vector::Vector3D};
+use crate::{
+    animation::{Interpolation, KeyProperty},
+    camera::{Projection, Stereo},
+    csg::Operation,
+    error::Error,
+    material::{Cutout, TextureSlot},
+    point::Point,
+    procedural::{Pattern, Space},
+    sdf::{Modifier, Operator, Shape},
+    sky::sun_direction,
+    texture::Filter,
+    transform::Transform,
+    vector::Vector3D,
+    viewport::DEFAULT_SENSOR_WIDTH,
+};
+use diagnostic::{Diagnostic, Tokens};
+use std::rc::Rc;
 
 const DEFAULT_WIDTH: usize = 1920;
 const DEFAULT_HEIGHT: usize = 1080;
 
+/** two opposite corners of an axis-aligned box */
+type Corners = ((f64, f64, f64), (f64, f64, f64));
+
 #[derive(Debug)]
 pub enum Instruction {
     Camera {
@@ -16,12 +35,119 @@ pub enum Instruction {
         z: f64,
     },
     CameraScale(f64),
+    CameraTarget {
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    CameraUp {
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    CameraFov(f64),
+    CameraFocalLength(f64),
+    CameraSensor(f64),
+    CameraType(Projection),
+    CameraOrthoHeight(f64),
+    CameraStereo(Stereo),
+    CameraEnd {
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    CameraTargetEnd {
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    CameraShutter {
+        open: f64,
+        close: f64,
+    },
+    /** transformation of the following objects */
+    Transform(Transform),
+    /** transformation of the following objects when the shutter closes */
+    TransformEnd(Transform),
+    TransformReset,
+    Background {
+        r: f64,
+        g: f64,
+        b: f64,
+    },
+    /** vertical gradient from the bottom color to the top color */
+    BackgroundGradient {
+        bottom: (f64, f64, f64),
+        top: (f64, f64, f64),
+    },
+    /** lat-long `.pfm` or `.hdr` environment map */
+    EnvironmentMap {
+        path: String,
+        intensity: f64,
+        rotation: f64,
+    },
+    /** Preetham daylight sky */
+    Sky {
+        turbidity: f64,
+        ground_albedo: f64,
+        intensity: f64,
+    },
+    SunDirection {
+        x: f64,
+        y: f64,
+        z: f64,
+    },
+    /** position of the sun from the location on earth and the local time */
+    SunPosition {
+        latitude: f64,
+        longitude: f64,
+        day: f64,
+        hour: f64,
+        timezone: f64,
+    },
+    TextureFilter(Filter),
+    /** how alpha masks cut out surfaces */
+    AlphaCutout(Cutout),
+    /** generate smooth normals of meshes between faces within this angle in degrees */
+    CreaseAngle(f64),
+    /** procedural texture blending two colors */
+    Texture {
+        name: String,
+        pattern: Pattern,
+        space: Space,
+        scale: f64,
+        color_a: (f64, f64, f64),
+        color_b: (f64, f64, f64),
+        param: f64,
+    },
+    /** texture from a `.ppm` image, mapped by texture coordinates */
+    ImageTexture {
+        name: String,
+        path: String,
+    },
+    /** drive a parameter of a `.mtl` material by a named texture */
+    MaterialTexture {
+        material: String,
+        slot: TextureSlot,
+        texture: String,
+    },
+    /** the following objects belong to an animated group */
+    Group(String),
+    GroupEnd,
+    Key {
+        frame: f64,
+        interpolation: Interpolation,
+        property: KeyProperty,
+        values: Vec<f64>,
+    },
     Size {
         width: usize,
         height: usize,
     },
     LoadObj(String),
     LoadMtl(String),
+    /** instructions of another script, in place of the instruction */
+    Include(String),
     AddSphere {
         x: f64,
         y: f64,
@@ -29,55 +155,589 @@ pub enum Instruction {
         raius: f64,
         material: String,
     },
+    /** infinite plane through a point */
+    AddPlane {
+        point: (f64, f64, f64),
+        normal: (f64, f64, f64),
+        material: String,
+    },
+    AddDisk {
+        center: (f64, f64, f64),
+        normal: (f64, f64, f64),
+        radius: f64,
+        material: String,
+    },
+    /** parallelogram spanned by two edges from a corner */
+    AddQuad {
+        corner: (f64, f64, f64),
+        edge_u: (f64, f64, f64),
+        edge_v: (f64, f64, f64),
+        material: String,
+    },
+    /** axis-aligned box between two opposite corners */
+    AddBox {
+        corner_a: (f64, f64, f64),
+        corner_b: (f64, f64, f64),
+        material: String,
+    },
+    /** box around a center, turned to an x axis and a direction in its xy plane */
+    AddOrientedBox {
+        center: (f64, f64, f64),
+        axis_x: (f64, f64, f64),
+        axis_y: (f64, f64, f64),
+        half_size: (f64, f64, f64),
+        material: String,
+    },
+    /** closed cone frustum from one point to another, cylinders have equal radii */
+    AddCone {
+        start: (f64, f64, f64),
+        end: (f64, f64, f64),
+        radius_start: f64,
+        radius_end: f64,
+        material: String,
+    },
+    AddTorus {
+        center: (f64, f64, f64),
+        axis: (f64, f64, f64),
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    /** named distance field of a primitive shape */
+    SdfShape {
+        name: String,
+        shape: Shape,
+    },
+    /** named distance field combining two named fields */
+    SdfCombine {
+        name: String,
+        operator: Operator,
+        a: String,
+        b: String,
+    },
+    /** named distance field modifying the space of a named field */
+    SdfModify {
+        name: String,
+        modifier: Modifier,
+        sdf: String,
+    },
+    /** the following objects build a named closed solid instead of being added to the scene */
+    Solid(String),
+    SolidEnd,
+    /** named solid combining two named solids */
+    Csg {
+        name: String,
+        operation: Operation,
+        a: String,
+        b: String,
+    },
+    AddSolid(String),
+    /** object of a named distance field, within a bounding box given by two opposite corners */
+    AddSdf {
+        name: String,
+        material: String,
+        bounds: Option<Corners>,
+    },
+}
+
+type ParseResult<T> = Result<T, Diagnostic>;
+
+/**
+ * Arguments of the script beyond the parsing shared with the other parsers.
+ */
+trait Arguments {
+    /** parse three required numbers from `i` on */
+    fn parse_triple(&self, i: usize) -> ParseResult<(f64, f64, f64)>;
+    /** parse three numbers of a direction, which must not be zero */
+    fn parse_direction(&self, i: usize) -> ParseResult<(f64, f64, f64)>;
+    /** parse a required number above zero, or at least zero if `zero` is allowed */
+    fn parse_positive(&self, i: usize, zero: bool) -> ParseResult<f64>;
+}
+
+impl Arguments for Tokens<'_> {
+    fn parse_triple(&self, i: usize) -> ParseResult<(f64, f64, f64)> {
+        Ok((self.parse(i)?, self.parse(i + 1)?, self.parse(i + 2)?))
+    }
+    fn parse_direction(&self, i: usize) -> ParseResult<(f64, f64, f64)> {
+        let direction = self.parse_triple(i)?;
+        if direction == (0., 0., 0.) {
+            return Err(self.error(i, "the direction must not be zero"));
+        }
+        Ok(direction)
+    }
+    fn parse_positive(&self, i: usize, zero: bool) -> ParseResult<f64> {
+        let value: f64 = self.parse(i)?;
+        if value < 0. || (value == 0. && !zero) {
+            return Err(self.error(i, format!("`{value}` must be positive")));
+        }
+        Ok(value)
+    }
+}
+
+/**
+ * Parse `tx ty tz rx ry rz sx sy sz`, missing components keep their default values.
+ */
+fn parse_transform(line: &Tokens, start: usize) -> ParseResult<Transform> {
+    let values = (start..line.len())
+        .map(|i| line.parse(i))
+        .collect::<ParseResult<Vec<f64>>>()?;
+    let mut transform = Transform::default();
+    let get = |i: usize, default: f64| values.get(i).copied().unwrap_or(default);
+    transform.translate = Vector3D::new(get(0, 0.), get(1, 0.), get(2, 0.));
+    transform.rotate = Vector3D::new(get(3, 0.), get(4, 0.), get(5, 0.));
+    transform.scale = Vector3D::new(get(6, 1.), get(7, 1.), get(8, 1.));
+    Ok(transform)
+}
+
+/**
+ * Parse the ends of a cone or cylinder, `x0 y0 z0 x1 y1 z1`, which must differ.
+ *
+ * material: index of the material name
+ */
+fn parse_cone(
+    line: &Tokens,
+    radius_start: f64,
+    radius_end: f64,
+    material: usize,
+) -> ParseResult<Instruction> {
+    let (start, end) = (line.parse_triple(1)?, line.parse_triple(4)?);
+    if start == end {
+        return Err(line.error(4, "the ends must differ"));
+    }
+    Ok(Instruction::AddCone {
+        start,
+        end,
+        radius_start,
+        radius_end,
+        material: line.text(material)?.to_owned(),
+    })
+}
+
+/**
+ * Parse `sdf name kind ...`, a primitive shape, a combination of two fields or a modified field.
+ */
+fn parse_sdf(line: &Tokens) -> ParseResult<Instruction> {
+    let name = line.text(1)?.to_owned();
+    let kind = line.text(2)?;
+    let shape = match kind {
+        "sphere" => Some(Shape::Sphere {
+            radius: line.parse_positive(3, false)?,
+        }),
+        "box" => Some(Shape::Box {
+            half: Vector3D::new(
+                line.parse_positive(3, true)?,
+                line.parse_positive(4, true)?,
+                line.parse_positive(5, true)?,
+            ),
+            round: match line.get(6) {
+                Some(_) => line.parse_positive(6, true)?,
+                None => 0.,
+            },
+        }),
+        "torus" => Some(Shape::Torus {
+            major_radius: line.parse_positive(3, false)?,
+            minor_radius: line.parse_positive(4, false)?,
+        }),
+        "cylinder" => Some(Shape::Cylinder {
+            radius: line.parse_positive(3, false)?,
+            half_height: line.parse_positive(4, false)?,
+        }),
+        "capsule" => Some(Shape::Capsule {
+            a: Vector3D::from(line.parse_triple(3)?),
+            b: Vector3D::from(line.parse_triple(6)?),
+            radius: line.parse_positive(9, false)?,
+        }),
+        "mandelbulb" => {
+            let power = line.parse_or(3, 8.)?;
+            if power < 2. {
+                return Err(line.error(3, "the power must be at least 2"));
+            }
+            Some(Shape::Mandelbulb {
+                power,
+                iterations: line.parse_or(4, 12)?,
+            })
+        }
+        _ => None,
+    };
+    if let Some(shape) = shape {
+        return Ok(Instruction::SdfShape { name, shape });
+    }
+
+    let operator = match kind {
+        "union" => Some(Operator::Union),
+        "intersection" => Some(Operator::Intersection),
+        "difference" => Some(Operator::Difference),
+        "smooth-union" => Some(Operator::SmoothUnion(line.parse_positive(5, false)?)),
+        "smooth-difference" => Some(Operator::SmoothDifference(line.parse_positive(5, false)?)),
+        _ => None,
+    };
+    if let Some(operator) = operator {
+        return Ok(Instruction::SdfCombine {
+            name,
+            operator,
+            a: line.text(3)?.to_owned(),
+            b: line.text(4)?.to_owned(),
+        });
+    }
+
+    let modifier = match kind {
+        "translate" => Modifier::Translate(Vector3D::from(line.parse_triple(4)?)),
+        "scale" => Modifier::Scale(line.parse_positive(4, false)?),
+        "twist" => Modifier::Twist(line.parse::<f64>(4)?.to_radians()),
+        "repeat" => {
+            let period = (
+                line.parse_positive(4, true)?,
+                line.parse_positive(5, true)?,
+                line.parse_positive(6, true)?,
+            );
+            if period == (0., 0., 0.) {
+                return Err(line.error(4, "at least one period must be above zero"));
+            }
+            Modifier::Repeat(Vector3D::from(period))
+        }
+        kind => return Err(line.error(2, format!("unknown distance field `{kind}`"))),
+    };
+    Ok(Instruction::SdfModify {
+        name,
+        modifier,
+        sdf: line.text(3)?.to_owned(),
+    })
+}
+
+/**
+ * Where an instruction is written, the names it uses are only looked up when the scene is built.
+ */
+#[derive(Clone, Debug)]
+pub struct Origin {
+    pub path: Rc<str>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Origin {
+    pub fn error(&self, message: impl Into<String>) -> Error {
+        Error::parse(&self.path, Diagnostic::new(self.line, self.column, message))
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct Script {
     pub instructions: Vec<Instruction>,
+    /** the origin of every instruction */
+    pub origins: Vec<Origin>,
 }
 
 impl Script {
-    pub fn parse(script_src: &str) -> Script {
+    /**
+     * Parse a script, lines starting with `#` are comments.
+     * Unknown instructions are skipped and returned as warnings.
+     */
+    pub fn parse(path: &str, script_src: &str) -> ParseResult<(Script, Vec<Diagnostic>)> {
         let mut script = Script::default();
+        let mut warnings = Vec::new();
+        let path = Rc::<str>::from(path);
 
-        for ins in script_src.split('\n') {
-            let line = ins.split(' ').collect::<Vec<&str>>();
-            match line[0] {
+        for (number, ins) in script_src.lines().enumerate() {
+            let line = Tokens::new(number + 1, ins);
+            let Some(keyword) = line.get(0) else {
+                continue;
+            };
+            match keyword {
                 "camera" => script.instructions.push(Instruction::Camera {
-                    x: line[1].parse().unwrap(),
-                    y: line[2].parse().unwrap(),
-                    z: line[3].parse().unwrap(),
+                    x: line.parse(1)?,
+                    y: line.parse(2)?,
+                    z: line.parse(3)?,
                 }),
                 "camera-at" => script.instructions.push(Instruction::CameraAt {
-                    x: line[1].parse().unwrap(),
-                    y: line[2].parse().unwrap(),
-                    z: line[3].parse().unwrap(),
+                    x: line.parse(1)?,
+                    y: line.parse(2)?,
+                    z: line.parse(3)?,
                 }),
                 "camera-scale" => script
                     .instructions
-                    .push(Instruction::CameraScale(line[1].parse().unwrap())),
+                    .push(Instruction::CameraScale(line.parse(1)?)),
+                "camera-target" => script.instructions.push(Instruction::CameraTarget {
+                    x: line.parse(1)?,
+                    y: line.parse(2)?,
+                    z: line.parse(3)?,
+                }),
+                "camera-up" => script.instructions.push(Instruction::CameraUp {
+                    x: line.parse(1)?,
+                    y: line.parse(2)?,
+                    z: line.parse(3)?,
+                }),
+                "camera-fov" => script
+                    .instructions
+                    .push(Instruction::CameraFov(line.parse(1)?)),
+                "camera-focal-length" => script
+                    .instructions
+                    .push(Instruction::CameraFocalLength(line.parse(1)?)),
+                "camera-sensor" => script
+                    .instructions
+                    .push(Instruction::CameraSensor(line.parse(1)?)),
+                "camera-type" => script
+                    .instructions
+                    .push(Instruction::CameraType(line.parse(1)?)),
+                "camera-ortho-height" => script
+                    .instructions
+                    .push(Instruction::CameraOrthoHeight(line.parse(1)?)),
+                "camera-stereo" => script.instructions.push(Instruction::CameraStereo(Stereo {
+                    interocular: line.parse(1)?,
+                    convergence: line.parse(2)?,
+                    mode: line.parse(3)?,
+                })),
+                "camera-end" => script.instructions.push(Instruction::CameraEnd {
+                    x: line.parse(1)?,
+                    y: line.parse(2)?,
+                    z: line.parse(3)?,
+                }),
+                "camera-target-end" => script.instructions.push(Instruction::CameraTargetEnd {
+                    x: line.parse(1)?,
+                    y: line.parse(2)?,
+                    z: line.parse(3)?,
+                }),
+                "camera-shutter" => script.instructions.push(Instruction::CameraShutter {
+                    open: line.parse(1)?,
+                    close: line.parse(2)?,
+                }),
+                "transform" => script
+                    .instructions
+                    .push(Instruction::Transform(parse_transform(&line, 1)?)),
+                "transform-end" => script
+                    .instructions
+                    .push(Instruction::TransformEnd(parse_transform(&line, 1)?)),
+                "transform-reset" => script.instructions.push(Instruction::TransformReset),
+                "background" => script.instructions.push(Instruction::Background {
+                    r: line.parse(1)?,
+                    g: line.parse(2)?,
+                    b: line.parse(3)?,
+                }),
+                "background-gradient" => {
+                    script.instructions.push(Instruction::BackgroundGradient {
+                        bottom: (line.parse(1)?, line.parse(2)?, line.parse(3)?),
+                        top: (line.parse(4)?, line.parse(5)?, line.parse(6)?),
+                    })
+                }
+                "environment-map" => script.instructions.push(Instruction::EnvironmentMap {
+                    path: line.text(1)?.to_owned(),
+                    intensity: line.parse_or(2, 1.)?,
+                    rotation: line.parse_or(3, 0.)?,
+                }),
+                "sky" => script.instructions.push(Instruction::Sky {
+                    turbidity: line.parse(1)?,
+                    ground_albedo: line.parse(2)?,
+                    intensity: line.parse_or(3, 1.)?,
+                }),
+                "sun-direction" => script.instructions.push(Instruction::SunDirection {
+                    x: line.parse(1)?,
+                    y: line.parse(2)?,
+                    z: line.parse(3)?,
+                }),
+                "sun-position" => script.instructions.push(Instruction::SunPosition {
+                    latitude: line.parse(1)?,
+                    longitude: line.parse(2)?,
+                    day: line.parse(3)?,
+                    hour: line.parse(4)?,
+                    timezone: line.parse_or(5, 0.)?,
+                }),
+                "texture-filter" => script
+                    .instructions
+                    .push(Instruction::TextureFilter(line.parse(1)?)),
+                "alpha-cutout" => {
+                    let cutout = match line.text(1)? {
+                        "stochastic" => Cutout::Stochastic,
+                        "threshold" => Cutout::Threshold(line.parse_or(2, 0.5)?),
+                        mode => return Err(line.error(1, format!("unknown alpha cutout `{mode}`"))),
+                    };
+                    script.instructions.push(Instruction::AlphaCutout(cutout))
+                }
+                "crease-angle" => script
+                    .instructions
+                    .push(Instruction::CreaseAngle(line.parse(1)?)),
+                "texture" if line.get(2) == Some("image") => {
+                    script.instructions.push(Instruction::ImageTexture {
+                        name: line.text(1)?.to_owned(),
+                        path: line.text(3)?.to_owned(),
+                    })
+                }
+                "texture" => {
+                    let pattern: Pattern = line.parse(2)?;
+                    script.instructions.push(Instruction::Texture {
+                        name: line.text(1)?.to_owned(),
+                        pattern,
+                        space: line.parse(3)?,
+                        scale: line.parse(4)?,
+                        color_a: (line.parse(5)?, line.parse(6)?, line.parse(7)?),
+                        color_b: (line.parse(8)?, line.parse(9)?, line.parse(10)?),
+                        param: line.parse_or(11, pattern.default_param())?,
+                    })
+                }
+                "material-texture" => script.instructions.push(Instruction::MaterialTexture {
+                    material: line.text(1)?.to_owned(),
+                    slot: line.parse(2)?,
+                    texture: line.text(3)?.to_owned(),
+                }),
+                "group" => script
+                    .instructions
+                    .push(Instruction::Group(line.text(1)?.to_owned())),
+                "group-end" => script.instructions.push(Instruction::GroupEnd),
+                "key" => {
+                    let (property, values) = match line.text(3)? {
+                        "camera" => (
+                            KeyProperty::Camera,
+                            vec![line.parse(4)?, line.parse(5)?, line.parse(6)?],
+                        ),
+                        "camera-target" => (
+                            KeyProperty::CameraTarget,
+                            vec![line.parse(4)?, line.parse(5)?, line.parse(6)?],
+                        ),
+                        "camera-fov" => (KeyProperty::CameraFov, vec![line.parse(4)?]),
+                        "transform" => {
+                            let t = parse_transform(&line, 5)?;
+                            (
+                                KeyProperty::Transform(line.text(4)?.to_owned()),
+                                vec![
+                                    t.translate.x,
+                                    t.translate.y,
+                                    t.translate.z,
+                                    t.rotate.x,
+                                    t.rotate.y,
+                                    t.rotate.z,
+                                    t.scale.x,
+                                    t.scale.y,
+                                    t.scale.z,
+                                ],
+                            )
+                        }
+                        property => {
+                            return Err(
+                                line.error(3, format!("unknown animated property `{property}`"))
+                            );
+                        }
+                    };
+                    script.instructions.push(Instruction::Key {
+                        frame: line.parse(1)?,
+                        interpolation: line.parse(2)?,
+                        property,
+                        values,
+                    });
+                }
                 "size" => script.instructions.push(Instruction::Size {
-                    width: line[1].parse().unwrap(),
-                    height: line[2].parse().unwrap(),
+                    width: line.parse(1)?,
+                    height: line.parse(2)?,
                 }),
                 "load-obj" => script
                     .instructions
-                    .push(Instruction::LoadObj(line[1].to_owned())),
+                    .push(Instruction::LoadObj(line.text(1)?.to_owned())),
                 "load-mtl" => script
                     .instructions
-                    .push(Instruction::LoadMtl(line[1].to_owned())),
+                    .push(Instruction::LoadMtl(line.text(1)?.to_owned())),
+                "include" => script
+                    .instructions
+                    .push(Instruction::Include(line.text(1)?.to_owned())),
                 "add-sphere" => script.instructions.push(Instruction::AddSphere {
-                    x: line[1].parse().unwrap(),
-                    y: line[2].parse().unwrap(),
-                    z: line[3].parse().unwrap(),
-                    raius: line[4].parse().unwrap(),
-                    material: line[5].to_owned(),
+                    x: line.parse(1)?,
+                    y: line.parse(2)?,
+                    z: line.parse(3)?,
+                    raius: line.parse(4)?,
+                    material: line.text(5)?.to_owned(),
+                }),
+                "add-plane" => script.instructions.push(Instruction::AddPlane {
+                    point: line.parse_triple(1)?,
+                    normal: line.parse_direction(4)?,
+                    material: line.text(7)?.to_owned(),
+                }),
+                "add-disk" => script.instructions.push(Instruction::AddDisk {
+                    center: line.parse_triple(1)?,
+                    normal: line.parse_direction(4)?,
+                    radius: line.parse_positive(7, false)?,
+                    material: line.text(8)?.to_owned(),
+                }),
+                "add-quad" => script.instructions.push(Instruction::AddQuad {
+                    corner: line.parse_triple(1)?,
+                    edge_u: line.parse_direction(4)?,
+                    edge_v: line.parse_direction(7)?,
+                    material: line.text(10)?.to_owned(),
                 }),
-                _ => {}
+                "add-box" => script.instructions.push(Instruction::AddBox {
+                    corner_a: line.parse_triple(1)?,
+                    corner_b: line.parse_triple(4)?,
+                    material: line.text(7)?.to_owned(),
+                }),
+                "add-oriented-box" => {
+                    let (axis_x, axis_y) = (line.parse_direction(4)?, line.parse_direction(7)?);
+                    if (Vector3D::from(axis_x) * Vector3D::from(axis_y)).module() == 0. {
+                        return Err(line.error(7, "the axes of a box must not be parallel"));
+                    }
+                    script.instructions.push(Instruction::AddOrientedBox {
+                        center: line.parse_triple(1)?,
+                        axis_x,
+                        axis_y,
+                        half_size: (
+                            line.parse_positive(10, false)?,
+                            line.parse_positive(11, false)?,
+                            line.parse_positive(12, false)?,
+                        ),
+                        material: line.text(13)?.to_owned(),
+                    })
+                }
+                "add-cylinder" => {
+                    let radius = line.parse_positive(7, false)?;
+                    script
+                        .instructions
+                        .push(parse_cone(&line, radius, radius, 8)?)
+                }
+                "add-cone" => {
+                    let (radius_start, radius_end) =
+                        (line.parse_positive(7, true)?, line.parse_positive(8, true)?);
+                    if radius_start == 0. && radius_end == 0. {
+                        return Err(line.error(7, "a cone needs a radius above zero"));
+                    }
+                    script
+                        .instructions
+                        .push(parse_cone(&line, radius_start, radius_end, 9)?)
+                }
+                "add-torus" => script.instructions.push(Instruction::AddTorus {
+                    center: line.parse_triple(1)?,
+                    axis: line.parse_direction(4)?,
+                    major_radius: line.parse_positive(7, false)?,
+                    minor_radius: line.parse_positive(8, false)?,
+                    material: line.text(9)?.to_owned(),
+                }),
+                "solid" => script
+                    .instructions
+                    .push(Instruction::Solid(line.text(1)?.to_owned())),
+                "solid-end" => script.instructions.push(Instruction::SolidEnd),
+                "csg" => script.instructions.push(Instruction::Csg {
+                    name: line.text(1)?.to_owned(),
+                    operation: line.parse(2)?,
+                    a: line.text(3)?.to_owned(),
+                    b: line.text(4)?.to_owned(),
+                }),
+                "add-solid" => script
+                    .instructions
+                    .push(Instruction::AddSolid(line.text(1)?.to_owned())),
+                "sdf" => script.instructions.push(parse_sdf(&line)?),
+                "add-sdf" => script.instructions.push(Instruction::AddSdf {
+                    name: line.text(1)?.to_owned(),
+                    material: line.text(2)?.to_owned(),
+                    bounds: match line.get(3) {
+                        Some(_) => Some((line.parse_triple(3)?, line.parse_triple(6)?)),
+                        None => None,
+                    },
+                }),
+                keyword if keyword.starts_with('#') => {}
+                keyword => warnings.push(line.error(0, format!("unknown instruction `{keyword}`"))),
             }
+            let origin = Origin {
+                path: path.clone(),
+                line: line.line,
+                column: line.column(0),
+            };
+            script.origins.resize(script.instructions.len(), origin);
         }
 
-        script
+        Ok((script, warnings))
     }
     pub fn get_camera(&self) -> Point {
         for i in &self.instructions {
@@ -106,6 +766,156 @@ impl Script {
 
         0.
     }
+    pub fn get_camera_target(&self) -> Option<Point> {
+        for i in &self.instructions {
+            if let Instruction::CameraTarget { x, y, z } = i {
+                return Some(Point::new(*x, *y, *z));
+            }
+        }
+
+        None
+    }
+    pub fn get_camera_up(&self) -> Vector3D {
+        for i in &self.instructions {
+            if let Instruction::CameraUp { x, y, z } = i {
+                return Vector3D::new(*x, *y, *z);
+            }
+        }
+
+        Vector3D::new(0., 1., 0.)
+    }
+    /** vertical field of view in radians */
+    pub fn get_camera_fov(&self) -> Option<f64> {
+        for i in &self.instructions {
+            if let Instruction::CameraFov(fov) = i {
+                return Some(fov.to_radians());
+            }
+        }
+
+        None
+    }
+    pub fn get_camera_focal_length(&self) -> Option<f64> {
+        for i in &self.instructions {
+            if let Instruction::CameraFocalLength(focal_length) = i {
+                return Some(*focal_length);
+            }
+        }
+
+        None
+    }
+    pub fn get_camera_sensor(&self) -> f64 {
+        for i in &self.instructions {
+            if let Instruction::CameraSensor(width) = i {
+                return *width;
+            }
+        }
+
+        DEFAULT_SENSOR_WIDTH
+    }
+    pub fn get_camera_type(&self) -> Projection {
+        for i in &self.instructions {
+            if let Instruction::CameraType(projection) = i {
+                return *projection;
+            }
+        }
+
+        Projection::default()
+    }
+    pub fn get_camera_ortho_height(&self) -> f64 {
+        for i in &self.instructions {
+            if let Instruction::CameraOrthoHeight(height) = i {
+                return *height;
+            }
+        }
+
+        2.
+    }
+    pub fn get_camera_stereo(&self) -> Option<Stereo> {
+        for i in &self.instructions {
+            if let Instruction::CameraStereo(stereo) = i {
+                return Some(*stereo);
+            }
+        }
+
+        None
+    }
+    pub fn get_camera_end(&self) -> Option<Point> {
+        for i in &self.instructions {
+            if let Instruction::CameraEnd { x, y, z } = i {
+                return Some(Point::new(*x, *y, *z));
+            }
+        }
+
+        None
+    }
+    pub fn get_camera_target_end(&self) -> Option<Point> {
+        for i in &self.instructions {
+            if let Instruction::CameraTargetEnd { x, y, z } = i {
+                return Some(Point::new(*x, *y, *z));
+            }
+        }
+
+        None
+    }
+    pub fn get_camera_shutter(&self) -> (f64, f64) {
+        for i in &self.instructions {
+            if let Instruction::CameraShutter { open, close } = i {
+                return (*open, *close);
+            }
+        }
+
+        (0., 0.)
+    }
+    /**
+     * Get the direction towards the sun, the default is 45° above the southern horizon.
+     */
+    pub fn get_sun_direction(&self) -> Vector3D {
+        for i in &self.instructions {
+            if let Instruction::SunDirection { x, y, z } = i {
+                return Vector3D::new(*x, *y, *z).unit();
+            }
+            if let Instruction::SunPosition {
+                latitude,
+                longitude,
+                day,
+                hour,
+                timezone,
+            } = i
+            {
+                return sun_direction(*latitude, *longitude, *day, *hour, *timezone);
+            }
+        }
+
+        Vector3D::new(0., 1., 1.).unit()
+    }
+    pub fn get_texture_filter(&self) -> Filter {
+        for i in &self.instructions {
+            if let Instruction::TextureFilter(filter) = i {
+                return *filter;
+            }
+        }
+
+        Filter::default()
+    }
+    pub fn get_alpha_cutout(&self) -> Cutout {
+        for i in &self.instructions {
+            if let Instruction::AlphaCutout(cutout) = i {
+                return *cutout;
+            }
+        }
+
+        Cutout::default()
+    }
+    /** crease angle in radians */
+    pub fn get_crease_angle(&self) -> Option<f64> {
+        for i in &self.instructions {
+            if let Instruction::CreaseAngle(angle) = i {
+                return Some(angle.to_radians());
+            }
+        }
+
+        None
+    }
     pub fn get_size(&self) -> (usize, usize) {
         for i in &self.instructions {
             if let Instruction::Size { width, height } = i {
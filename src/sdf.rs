@@ -0,0 +1,582 @@
+use crate::{
+    bvh::bounds_from_aabb,
+    material::Material,
+    objects::{HitRecord, Object},
+    point::Point,
+    primitives::slabs,
+    ray::Ray,
+    vector::Vector3D,
+};
+use std::rc::Rc;
+
+/**
+ * Primitive distance fields, centered at the origin, round shapes lie around the y axis.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Sphere {
+        radius: f64,
+    },
+    /** box of half extents, its edges are rounded by `round` */
+    Box {
+        half: Vector3D,
+        round: f64,
+    },
+    Torus {
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Cylinder {
+        radius: f64,
+        half_height: f64,
+    },
+    /** segment from `a` to `b` grown by the radius */
+    Capsule {
+        a: Vector3D,
+        b: Vector3D,
+        radius: f64,
+    },
+    /** power-n Mandelbulb fractal, by its distance estimator */
+    Mandelbulb {
+        power: f64,
+        iterations: u32,
+    },
+}
+
+/** boolean combination of two distance fields */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Union,
+    Intersection,
+    /** the first field minus the second */
+    Difference,
+    /** union blending the fields within the distance k */
+    SmoothUnion(f64),
+    SmoothDifference(f64),
+}
+
+/** change of the space a distance field is evaluated in */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Modifier {
+    Translate(Vector3D),
+    /** uniform scale, above zero */
+    Scale(f64),
+    /** rotation around the y axis growing with the height, in radians per unit */
+    Twist(f64),
+    /** infinite repetition with a period per axis, axes with a period of 0 are not repeated */
+    Repeat(Vector3D),
+}
+
+/**
+ * A signed distance field, negative inside of the surface.
+ */
+pub enum Sdf {
+    Shape(Shape),
+    Combine(Operator, Rc<Sdf>, Rc<Sdf>),
+    Modify(Modifier, Rc<Sdf>),
+}
+
+/** a box by its minimum and maximum, axes without bounds are infinite */
+type Bounds = (Vector3D, Vector3D);
+
+fn map2(a: Vector3D, b: Vector3D, f: impl Fn(f64, f64) -> f64) -> Vector3D {
+    Vector3D::new(f(a.x, b.x), f(a.y, b.y), f(a.z, b.z))
+}
+
+/** replace the components of a bound along the repeated axes by `f` of the period */
+fn repeated(bound: Vector3D, period: Vector3D, f: impl Fn(f64) -> f64) -> Vector3D {
+    map2(
+        bound,
+        period,
+        |bound, period| {
+            if period > 0. { f(period) } else { bound }
+        },
+    )
+}
+
+/** polynomial smooth minimum */
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    let h = (k - (a - b).abs()).max(0.) / k;
+    a.min(b) - h * h * k / 4.
+}
+
+/** xz radius of a box around the y axis */
+fn radius_around_y((min, max): &Bounds) -> f64 {
+    (min.x.abs().max(max.x.abs()).powi(2) + min.z.abs().max(max.z.abs()).powi(2)).sqrt()
+}
+
+impl Shape {
+    fn distance(&self, p: Vector3D) -> f64 {
+        match *self {
+            Self::Sphere { radius } => p.module() - radius,
+            Self::Box { half, round } => {
+                let q = map2(map2(p, p, |x, _| x.abs()), half, |p, h| p - h);
+                map2(q, q, |q, _| q.max(0.)).module() + q.x.max(q.y).max(q.z).min(0.) - round
+            }
+            Self::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let q = (p.x.hypot(p.z) - major_radius).hypot(p.y);
+                q - minor_radius
+            }
+            Self::Cylinder {
+                radius,
+                half_height,
+            } => {
+                let (dr, dy) = (p.x.hypot(p.z) - radius, p.y.abs() - half_height);
+                dr.max(dy).min(0.) + dr.max(0.).hypot(dy.max(0.))
+            }
+            Self::Capsule { a, b, radius } => {
+                let (pa, ba) = (p - a, b - a);
+                let h = (pa.cdot(&ba) / ba.cdot(&ba)).clamp(0., 1.);
+                (pa - h * ba).module() - radius
+            }
+            Self::Mandelbulb { power, iterations } => {
+                let (mut z, mut dr, mut r) = (p, 1., p.module());
+                for _ in 0..iterations {
+                    if r > 2. {
+                        return 0.5 * r.ln() * r / dr;
+                    }
+                    dr = power * r.powf(power - 1.) * dr + 1.;
+                    z = if r > 0. {
+                        let theta = (z.y / r).acos() * power;
+                        let phi = z.z.atan2(z.x) * power;
+                        r.powf(power)
+                            * Vector3D::new(
+                                theta.sin() * phi.cos(),
+                                theta.cos(),
+                                theta.sin() * phi.sin(),
+                            )
+                            + p
+                    } else {
+                        p
+                    };
+                    r = z.module();
+                }
+                /* the points that never escape are inside */
+                -0.5 * r.max(f64::MIN_POSITIVE).ln().abs() * r / dr
+            }
+        }
+    }
+    fn bounds(&self) -> Bounds {
+        let cube = |half: f64| {
+            (
+                Vector3D::new(-half, -half, -half),
+                Vector3D::new(half, half, half),
+            )
+        };
+        match *self {
+            Self::Sphere { radius } => cube(radius),
+            Self::Box { half, round } => (-1. * (half + round), half + round),
+            Self::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let half = Vector3D::new(
+                    major_radius + minor_radius,
+                    minor_radius,
+                    major_radius + minor_radius,
+                );
+                (-half, half)
+            }
+            Self::Cylinder {
+                radius,
+                half_height,
+            } => {
+                let half = Vector3D::new(radius, half_height, radius);
+                (-half, half)
+            }
+            Self::Capsule { a, b, radius } => {
+                (map2(a, b, f64::min) - radius, map2(a, b, f64::max) + radius)
+            }
+            /* the points farther than 2 escape */
+            Self::Mandelbulb { .. } => cube(2.),
+        }
+    }
+}
+
+impl Modifier {
+    /** map a point into the space of the modified field */
+    fn apply(&self, p: Vector3D) -> Vector3D {
+        match *self {
+            Self::Translate(offset) => p - offset,
+            Self::Scale(scale) => p / scale,
+            Self::Twist(rate) => {
+                let (sin, cos) = (rate * p.y).sin_cos();
+                Vector3D::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z)
+            }
+            Self::Repeat(period) => map2(p, period, |p, period| {
+                if period > 0. {
+                    p - period * (p / period).round()
+                } else {
+                    p
+                }
+            }),
+        }
+    }
+    /** region the modified field is evaluated in, for points in `bounds` */
+    fn region(&self, bounds: &Bounds) -> Bounds {
+        let (min, max) = *bounds;
+        match *self {
+            Self::Translate(offset) => (min - offset, max - offset),
+            Self::Scale(scale) => (min / scale, max / scale),
+            Self::Twist(_) => {
+                let r = radius_around_y(bounds);
+                (Vector3D::new(-r, min.y, -r), Vector3D::new(r, max.y, r))
+            }
+            Self::Repeat(period) => (
+                repeated(min, period, |period| -period / 2.),
+                repeated(max, period, |period| period / 2.),
+            ),
+        }
+    }
+}
+
+impl Sdf {
+    /**
+     * Modify a field, a repeated field must fit within its period around the origin
+     * or the copies would be cut.
+     */
+    pub fn modify(modifier: Modifier, sdf: Rc<Sdf>) -> Result<Self, String> {
+        if let Modifier::Repeat(period) = modifier {
+            let (min, max) = sdf.bounds();
+            for (axis, name) in ["x", "y", "z"].into_iter().enumerate() {
+                let half = period[axis] / 2.;
+                if period[axis] > 0. && (min[axis] < -half || max[axis] > half) {
+                    return Err(format!(
+                        "the repeated field exceeds its period along {name}, \
+                        it must lie within {} to {half}",
+                        -half
+                    ));
+                }
+            }
+        }
+        Ok(Self::Modify(modifier, sdf))
+    }
+    pub fn distance(&self, p: Vector3D) -> f64 {
+        match self {
+            Self::Shape(shape) => shape.distance(p),
+            Self::Combine(operator, a, b) => {
+                let (a, b) = (a.distance(p), b.distance(p));
+                match *operator {
+                    Operator::Union => a.min(b),
+                    Operator::Intersection => a.max(b),
+                    Operator::Difference => a.max(-b),
+                    Operator::SmoothUnion(k) => smooth_min(a, b, k),
+                    Operator::SmoothDifference(k) => -smooth_min(-a, b, k),
+                }
+            }
+            Self::Modify(Modifier::Scale(scale), sdf) => sdf.distance(p / *scale) * scale,
+            Self::Modify(modifier, sdf) => sdf.distance(modifier.apply(p)),
+        }
+    }
+    /**
+     * Get the box holding the surface, repeated fields have no bounds along the repeated axes.
+     */
+    pub fn bounds(&self) -> Bounds {
+        match self {
+            Self::Shape(shape) => shape.bounds(),
+            Self::Combine(operator, a, b) => {
+                let ((a_min, a_max), (b_min, b_max)) = (a.bounds(), b.bounds());
+                match *operator {
+                    Operator::Union => (map2(a_min, b_min, f64::min), map2(a_max, b_max, f64::max)),
+                    /* blending grows the union by at most a quarter of k */
+                    Operator::SmoothUnion(k) => (
+                        map2(a_min, b_min, f64::min) - k / 4.,
+                        map2(a_max, b_max, f64::max) + k / 4.,
+                    ),
+                    Operator::Intersection => {
+                        (map2(a_min, b_min, f64::max), map2(a_max, b_max, f64::min))
+                    }
+                    Operator::Difference | Operator::SmoothDifference(_) => (a_min, a_max),
+                }
+            }
+            Self::Modify(modifier, sdf) => {
+                let (min, max) = sdf.bounds();
+                match *modifier {
+                    Modifier::Translate(offset) => (min + offset, max + offset),
+                    Modifier::Scale(scale) => (scale * min, scale * max),
+                    Modifier::Twist(_) => {
+                        let r = radius_around_y(&(min, max));
+                        (Vector3D::new(-r, min.y, -r), Vector3D::new(r, max.y, r))
+                    }
+                    Modifier::Repeat(period) => (
+                        repeated(min, period, |_| f64::NEG_INFINITY),
+                        repeated(max, period, |_| f64::INFINITY),
+                    ),
+                }
+            }
+        }
+    }
+    /**
+     * Get how much faster than the distance to the surface the field may change within `region`,
+     * twisting bends the space and steps must shrink.
+     */
+    fn lipschitz(&self, region: &Bounds) -> f64 {
+        match self {
+            Self::Shape(_) => 1.,
+            Self::Combine(_, a, b) => a.lipschitz(region).max(b.lipschitz(region)),
+            Self::Modify(modifier, sdf) => {
+                let inner = sdf.lipschitz(&modifier.region(region));
+                match *modifier {
+                    /* largest singular value of the jacobian of the twist */
+                    Modifier::Twist(rate) => {
+                        let shear = rate.abs() * radius_around_y(region) / 2.;
+                        inner * (shear + (1. + shear * shear).sqrt())
+                    }
+                    _ => inner,
+                }
+            }
+        }
+    }
+}
+
+/** steps of a ray through the bounding box before it is given up */
+const MAX_STEPS: usize = 1024;
+/** shortest step relative to the size of the bounding box, thinner features may be missed */
+const MIN_STEP: f64 = 1e-5;
+/** distance to the surface the bisection stops at, relative to the size of the bounding box */
+const PRECISION: f64 = 1e-9;
+/** distance of the samples of the gradient relative to the size of the bounding box */
+const GRADIENT_STEP: f64 = 1e-6;
+
+/**
+ * An object whose surface is the zero set of a distance field, rendered by sphere tracing
+ * inside its bounding box.
+ */
+pub struct SdfObject {
+    sdf: Rc<Sdf>,
+    aabb: (Point, Point),
+    /** distances are divided by it, so steps never pass the surface */
+    lipschitz: f64,
+    size: f64,
+    material: Material,
+}
+
+impl SdfObject {
+    /**
+     * Build the object within the bounds of the field, clipped by `clip`.
+     * Fields without finite bounds need a clipping box.
+     */
+    pub fn new(
+        sdf: Rc<Sdf>,
+        clip: Option<(Point, Point)>,
+        material: Material,
+    ) -> Result<Self, String> {
+        let (mut min, mut max) = sdf.bounds();
+        if let Some((a, b)) = clip {
+            let (a, b) = (a.point_vec, b.point_vec);
+            min = map2(min, map2(a, b, f64::min), f64::max);
+            max = map2(max, map2(a, b, f64::max), f64::min);
+        }
+        if ![min.x, min.y, min.z, max.x, max.y, max.z]
+            .iter()
+            .all(|v| v.is_finite())
+        {
+            return Err("the distance field has no bounds, give it a bounding box".to_owned());
+        }
+        if min.x > max.x || min.y > max.y || min.z > max.z {
+            return Err("the bounding box doesn't overlap the distance field".to_owned());
+        }
+        let size = (max - min).module().max(1e-9);
+        /* the padding keeps flat fields inside of the box */
+        let (min, max) = (min - MIN_STEP * size, max + MIN_STEP * size);
+        Ok(Self {
+            lipschitz: sdf.lipschitz(&(min, max)),
+            sdf,
+            aabb: (Point::from_vec3d(min), Point::from_vec3d(max)),
+            size,
+            material,
+        })
+    }
+    /** the interval where a ray is inside of the bounding box */
+    fn clip(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let bounds = (self.aabb.0.point_vec, self.aabb.1.point_vec);
+        let [(near, ..), (far, ..)] = slabs((ray.origin.point_vec, ray.direction), bounds)?;
+        Some((near, far))
+    }
+    /** outward normal from the gradient, by central differences */
+    fn normal(&self, p: Vector3D) -> Vector3D {
+        let h = GRADIENT_STEP * self.size;
+        let axis = |x, y, z| {
+            let offset = Vector3D::new(x, y, z);
+            self.sdf.distance(p + offset) - self.sdf.distance(p - offset)
+        };
+        let gradient = Vector3D::new(axis(h, 0., 0.), axis(0., h, 0.), axis(0., 0., h));
+        if gradient.module() > 0. {
+            gradient.unit()
+        } else {
+            Vector3D::new(0., 1., 0.)
+        }
+    }
+}
+
+bounds_from_aabb!(SdfObject);
+
+impl Object for SdfObject {
+    /**
+     * March along the ray by the distance to the surface until the sign of the field changes,
+     * then find the surface by bisection. Rays starting inside find the way out.
+     * Distance fields have no texture coordinates, they are textured in object space.
+     */
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (near, far) = self.clip(ray)?;
+        let (start, end) = (near.max(t_min), far.min(t_max));
+        if start > end {
+            return None;
+        }
+        let length = ray.direction.module();
+        let field = |t: f64| self.sdf.distance(ray.point_at(t).point_vec);
+        let side = if field(start) < 0. { -1. } else { 1. };
+        let min_step = MIN_STEP * self.size / length;
+
+        let (mut before, mut t) = (start, start);
+        let mut crossed = None;
+        for _ in 0..MAX_STEPS {
+            let distance = side * field(t);
+            if distance < 0. {
+                crossed = Some((before, t));
+                break;
+            }
+            if t >= end {
+                break;
+            }
+            before = t;
+            t = (t + (distance / (self.lipschitz * length)).max(min_step)).min(end);
+        }
+
+        let (mut a, mut b) = crossed?;
+        while (b - a) * length > PRECISION * self.size {
+            let mid = (a + b) / 2.;
+            if mid <= a || mid >= b {
+                break;
+            }
+            if side * field(mid) < 0. {
+                b = mid;
+            } else {
+                a = mid;
+            }
+        }
+        let t = (a + b) / 2.;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let point = ray.point_at(t);
+        let normal = self.normal(point.point_vec);
+        Some(HitRecord {
+            t,
+            point,
+            object_point: point,
+            geometric_normal: normal,
+            normal,
+            front_face: side > 0.,
+            uv: (0., 0.),
+            tangents: None,
+            primitive: 0,
+            material: &self.material,
+        })
+    }
+    fn is_closed(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Sphere;
+
+    fn sphere(radius: f64) -> Rc<Sdf> {
+        Rc::new(Sdf::Shape(Shape::Sphere { radius }))
+    }
+
+    fn modified(modifier: Modifier, sdf: Rc<Sdf>) -> Rc<Sdf> {
+        Rc::new(Sdf::modify(modifier, sdf).unwrap())
+    }
+
+    fn vector(x: f64, y: f64, z: f64) -> Vector3D {
+        Vector3D::new(x, y, z)
+    }
+
+    #[test]
+    fn bounds_of_modified_and_combined_fields() {
+        let moved = modified(Modifier::Translate(vector(1., 2., 3.)), sphere(1.));
+        assert_eq!(moved.bounds(), (vector(0., 1., 2.), vector(2., 3., 4.)));
+        let scaled = modified(Modifier::Scale(2.), sphere(1.));
+        assert_eq!(scaled.bounds(), (vector(-2., -2., -2.), vector(2., 2., 2.)));
+
+        let combine = |operator| Sdf::Combine(operator, Rc::clone(&moved), sphere(1.5)).bounds();
+        assert_eq!(
+            combine(Operator::Union),
+            (vector(-1.5, -1.5, -1.5), vector(2., 3., 4.))
+        );
+        assert_eq!(
+            combine(Operator::SmoothUnion(0.4)),
+            (vector(-1.6, -1.6, -1.6), vector(2.1, 3.1, 4.1))
+        );
+        assert_eq!(
+            combine(Operator::Intersection),
+            (vector(0., 1., 2.), vector(1.5, 1.5, 1.5))
+        );
+        assert_eq!(combine(Operator::Difference), moved.bounds());
+
+        let repeated = modified(Modifier::Repeat(vector(3., 0., 0.)), sphere(1.));
+        let (min, max) = repeated.bounds();
+        assert_eq!((min.x, max.x), (f64::NEG_INFINITY, f64::INFINITY));
+        assert_eq!((min.y, max.y, min.z, max.z), (-1., 1., -1., 1.));
+    }
+
+    #[test]
+    fn repeats_must_fit_their_period() {
+        let Err(err) = Sdf::modify(Modifier::Repeat(vector(1.5, 0., 0.)), sphere(1.)) else {
+            panic!("the sphere exceeds its period");
+        };
+        assert!(err.contains("along x"), "{err}");
+        let moved = modified(Modifier::Translate(vector(0., 0., 0.5)), sphere(1.));
+        let Err(err) = Sdf::modify(Modifier::Repeat(vector(0., 2., 2.)), moved) else {
+            panic!("the moved sphere exceeds its period");
+        };
+        assert!(err.contains("along z"), "{err}");
+        assert!(Sdf::modify(Modifier::Repeat(vector(2., 2., 0.)), sphere(1.)).is_ok());
+
+        /* a field repeated without end needs a bounding box */
+        let repeated = modified(Modifier::Repeat(vector(2., 0., 0.)), sphere(1.));
+        assert!(SdfObject::new(Rc::clone(&repeated), None, Material::default()).is_err());
+        let clip = (Point::new(-3., -3., -3.), Point::new(3., 3., 3.));
+        assert!(SdfObject::new(repeated, Some(clip), Material::default()).is_ok());
+    }
+
+    #[test]
+    fn sphere_field_hits_like_the_sphere() {
+        let center = vector(0.5, -0.25, 1.);
+        let field = modified(Modifier::Translate(center), sphere(1.));
+        let object = SdfObject::new(field, None, Material::default()).unwrap();
+        let analytic = Sphere::new(Point::from_vec3d(center), 1., Material::default());
+        let rays = [
+            ((0., 0., 10.), (0., 0., -1.)),
+            ((-5., 0.3, 0.), (1., -0.05, 0.2)),
+            ((3., 3., 3.), (-1., -1., -0.7)),
+            /* starting inside */
+            ((0.5, 0., 1.), (0.2, 1., 0.)),
+        ];
+        for (origin, direction) in rays {
+            let r = Ray::new(Point::from_vec3d(origin.into()), direction.into(), 0.);
+            let expected = analytic.hit(&r, 1e-9, f64::INFINITY).unwrap();
+            let hit = object.hit(&r, 1e-9, f64::INFINITY).unwrap();
+            assert!(
+                (hit.t - expected.t).abs() < 1e-6,
+                "{} != {}",
+                hit.t,
+                expected.t
+            );
+            assert!((hit.normal - expected.geometric_normal).module() < 1e-4);
+            assert_eq!(hit.front_face, expected.front_face);
+        }
+        let miss = Ray::new(Point::new(0., 5., 0.), vector(1., 0., 0.), 0.);
+        assert!(object.hit(&miss, 1e-9, f64::INFINITY).is_none());
+        let short = Ray::new(Point::new(0.5, -0.25, 10.), vector(0., 0., -1.), 0.);
+        assert!(object.hit(&short, 1e-9, 7.).is_none());
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{environment::Environment, vector::Vector3D};
+use std::f64::consts::{FRAC_PI_2, PI};
+
+/** angular radius of the sun in radians */
+const SUN_RADIUS: f64 = 0.004654;
+/** luminance of the sun outside the atmosphere in kcd/m², the unit of the sky model */
+const SUN_LUMINANCE: f64 = 1.6e6;
+/** scale from kcd/m² to the radiance unit of the renderer */
+const SKY_SCALE: f64 = 0.05;
+/** probability of sampling the sun disc instead of the whole sphere */
+const SUN_SAMPLE_RATE: f64 = 0.5;
+
+/**
+ * Perez luminance distribution, `theta` is the zenith angle of the view direction
+ * and `gamma` is the angle between the view direction and the sun.
+ */
+fn perez(c: &[f64; 5], theta: f64, gamma: f64) -> f64 {
+    (1. + c[0] * (c[1] / theta.cos().max(0.01)).exp())
+        * (1. + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos().powi(2))
+}
+
+/** convert CIE xyY to linear sRGB */
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Vector3D {
+    let cx = x / y * luminance;
+    let cz = (1. - x - y) / y * luminance;
+    Vector3D::new(
+        3.2406 * cx - 1.5372 * luminance - 0.4986 * cz,
+        -0.9689 * cx + 1.8758 * luminance + 0.0415 * cz,
+        0.0557 * cx - 0.2040 * luminance + 1.0570 * cz,
+    )
+}
+
+/**
+ * Direction of the sun, from the position on earth and the time.
+ *
+ * latitude, longitude: in degrees, north and east are positive,
+ * day: day of the year from 1 to 365, hour: local standard time in hours,
+ * timezone: offset from UTC in hours.
+ *
+ * The world is oriented with +Y up, -Z north and +X east.
+ */
+pub fn sun_direction(
+    latitude: f64,
+    longitude: f64,
+    day: f64,
+    hour: f64,
+    timezone: f64,
+) -> Vector3D {
+    let latitude = latitude.to_radians();
+    /* equation of time and the offset from the standard meridian */
+    let solar_time = hour + 0.170 * (4. * PI * (day - 80.) / 373.).sin()
+        - 0.129 * (2. * PI * (day - 8.) / 355.).sin()
+        + (longitude - timezone * 15.) / 15.;
+    let declination = 0.4093 * (2. * PI * (day - 81.) / 368.).sin();
+    let hour_angle = PI * (solar_time - 12.) / 12.;
+
+    let altitude = (latitude.sin() * declination.sin()
+        + latitude.cos() * declination.cos() * hour_angle.cos())
+    .asin();
+    /* azimuth from north towards east */
+    let azimuth = (-declination.cos() * hour_angle.sin()).atan2(
+        declination.sin() * latitude.cos() - declination.cos() * latitude.sin() * hour_angle.cos(),
+    );
+
+    Vector3D::new(
+        azimuth.sin() * altitude.cos(),
+        altitude.sin(),
+        -azimuth.cos() * altitude.cos(),
+    )
+}
+
+/**
+ * Preetham daylight sky with a matching sun disc.
+ */
+pub struct Sky {
+    sun: Vector3D,
+    intensity: f64,
+    /** Perez coefficients of Y, x and y */
+    coefficients: [[f64; 5]; 3],
+    /** zenith Y, x and y divided by the Perez function at the zenith */
+    zenith: [f64; 3],
+    sun_radiance: Vector3D,
+    ground_radiance: Vector3D,
+}
+
+impl Sky {
+    /**
+     * sun: direction towards the sun, turbidity: from 2 (clear) to 10 (hazy),
+     * ground_albedo: reflectance of the ground below the horizon.
+     */
+    pub fn new(sun: Vector3D, turbidity: f64, ground_albedo: f64, intensity: f64) -> Self {
+        let sun = sun.unit();
+        let t = turbidity;
+        /* the model is only valid with the sun above the horizon */
+        let theta_s = sun.y.clamp(-1., 1.).acos().min(FRAC_PI_2 - 0.01);
+
+        let coefficients = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let (s, s2, s3) = (theta_s, theta_s.powi(2), theta_s.powi(3));
+        let zenith_x = t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+        let zenith_y = t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+        let zenith = [
+            zenith_luminance.max(0.) / perez(&coefficients[0], 0., theta_s),
+            zenith_x / perez(&coefficients[1], 0., theta_s),
+            zenith_y / perez(&coefficients[2], 0., theta_s),
+        ];
+
+        /* attenuation of the sun by Rayleigh and aerosol scattering at red, green and blue wavelengths */
+        let sun_radiance = if sun.y > 0. {
+            let theta_deg = theta_s.to_degrees();
+            let mass = 1. / (theta_s.cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+            let beta = 0.04608 * t - 0.04586;
+            let transmittance = |lambda: f64| {
+                (-mass * 0.008735 * lambda.powf(-4.08)).exp()
+                    * (-mass * beta * lambda.powf(-1.3)).exp()
+            };
+            SUN_LUMINANCE
+                * SKY_SCALE
+                * intensity
+                * Vector3D::new(
+                    transmittance(0.65),
+                    transmittance(0.57),
+                    transmittance(0.475),
+                )
+        } else {
+            Vector3D::new(0., 0., 0.)
+        };
+
+        let mut sky = Self {
+            sun,
+            intensity,
+            coefficients,
+            zenith,
+            sun_radiance,
+            ground_radiance: Vector3D::new(0., 0., 0.),
+        };
+
+        /* the ground is a diffuse plane lit by the sun and the sky */
+        let mut irradiance = sky_irradiance(&sky);
+        let sun_solid_angle = 2. * PI * (1. - SUN_RADIUS.cos());
+        irradiance += (sun_solid_angle * sun.y.max(0.)) * sky.sun_radiance;
+        sky.ground_radiance = (ground_albedo / PI) * irradiance;
+        sky
+    }
+    /** radiance of the sky dome without the sun disc */
+    fn sky_radiance(&self, direction: &Vector3D) -> Vector3D {
+        let theta = direction.y.clamp(0., 1.).acos();
+        let gamma = direction.cdot(&self.sun).clamp(-1., 1.).acos();
+        let luminance = self.zenith[0] * perez(&self.coefficients[0], theta, gamma);
+        let x = self.zenith[1] * perez(&self.coefficients[1], theta, gamma);
+        let y = self.zenith[2] * perez(&self.coefficients[2], theta, gamma);
+        let rgb = xyy_to_rgb(x, y, luminance);
+        (SKY_SCALE * self.intensity) * Vector3D::new(rgb.x.max(0.), rgb.y.max(0.), rgb.z.max(0.))
+    }
+    fn in_sun_disc(&self, direction: &Vector3D) -> bool {
+        self.sun.y > 0. && direction.cdot(&self.sun) >= SUN_RADIUS.cos()
+    }
+}
+
+/**
+ * Integrate the cosine weighted sky radiance over the upper hemisphere.
+ */
+fn sky_irradiance(sky: &Sky) -> Vector3D {
+    const THETA_STEPS: usize = 16;
+    const PHI_STEPS: usize = 32;
+    let mut irradiance = Vector3D::new(0., 0., 0.);
+    for i in 0..THETA_STEPS {
+        let theta = (i as f64 + 0.5) / THETA_STEPS as f64 * FRAC_PI_2;
+        for j in 0..PHI_STEPS {
+            let phi = (j as f64 + 0.5) / PHI_STEPS as f64 * 2. * PI;
+            let direction = Vector3D::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            );
+            let solid_angle =
+                theta.sin() * (FRAC_PI_2 / THETA_STEPS as f64) * (2. * PI / PHI_STEPS as f64);
+            irradiance += (theta.cos() * solid_angle) * sky.sky_radiance(&direction);
+        }
+    }
+    irradiance
+}
+
+impl Environment for Sky {
+    fn radiance(&self, direction: &Vector3D) -> Vector3D {
+        if direction.y < 0. {
+            return self.ground_radiance;
+        }
+        let mut radiance = self.sky_radiance(direction);
+        if self.in_sun_disc(direction) {
+            radiance += self.sun_radiance;
+        }
+        radiance
+    }
+    fn sample(&self) -> Option<(Vector3D, Vector3D, f64)> {
+        let direction = if self.sun.y > 0. && rand::random::<f64>() < SUN_SAMPLE_RATE {
+            /* uniform direction within the cone of the sun disc */
+            let cos_theta = 1. - rand::random::<f64>() * (1. - SUN_RADIUS.cos());
+            let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+            let phi = 2. * PI * rand::random::<f64>();
+            let (t, b) = self.sun.basis();
+            sin_theta * phi.cos() * t + sin_theta * phi.sin() * b + cos_theta * self.sun
+        } else {
+            /* uniform direction on the sphere */
+            let z = 1. - 2. * rand::random::<f64>();
+            let r = (1. - z * z).sqrt();
+            let phi = 2. * PI * rand::random::<f64>();
+            Vector3D::new(r * phi.cos(), z, r * phi.sin())
+        };
+        Some((direction, self.radiance(&direction), self.pdf(&direction)))
+    }
+    fn pdf(&self, direction: &Vector3D) -> f64 {
+        let uniform = 1. / (4. * PI);
+        if self.sun.y <= 0. {
+            return uniform;
+        }
+        let cone = if self.in_sun_disc(direction) {
+            1. / (2. * PI * (1. - SUN_RADIUS.cos()))
+        } else {
+            0.
+        };
+        SUN_SAMPLE_RATE * cone + (1. - SUN_SAMPLE_RATE) * uniform
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{point::Point, vector::Vector3D};
+use std::{rc::Rc, str::FromStr};
+
+/**
+ * Coordinates of a point on a surface, in every space a texture may be evaluated in.
+ */
+pub struct TextureCoord {
+    pub uv: (f64, f64),
+    pub world: Point,
+    /** position before the transformation of the object */
+    pub object: Point,
+}
+
+pub trait Texture {
+    fn sample(&self, coord: &TextureCoord) -> Vector3D;
+}
+
+/** average of the channels, used when a texture drives a scalar parameter */
+pub fn to_scalar(color: Vector3D) -> f64 {
+    (color.x + color.y + color.z) / 3.
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(Self::Nearest),
+            "bilinear" => Ok(Self::Bilinear),
+            _ => Err(format!("unknown texture filter `{s}`")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Wrap {
+    /** repeat the texture outside [0, 1] */
+    #[default]
+    Repeat,
+    /** extend the edge pixels outside [0, 1] */
+    Clamp,
+}
+
+/**
+ * A texture sampled from an image, the image may be shared between textures.
+ */
+pub struct ImageTexture {
+    image: Rc<ppm::Image>,
+    filter: Filter,
+    wrap: Wrap,
+}
+
+impl ImageTexture {
+    pub fn new(image: Rc<ppm::Image>, filter: Filter, wrap: Wrap) -> Self {
+        Self {
+            image,
+            filter,
+            wrap,
+        }
+    }
+    /** get the color of a pixel, the coordinates are wrapped into the image */
+    fn texel(&self, x: isize, y: isize) -> Vector3D {
+        let (width, height) = (self.image.width() as isize, self.image.height() as isize);
+        let (x, y) = match self.wrap {
+            Wrap::Repeat => (x.rem_euclid(width), y.rem_euclid(height)),
+            Wrap::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+        };
+        let pixel = self.image.get_pixel(x as usize, y as usize);
+        Vector3D::new(pixel.r as f64, pixel.g as f64, pixel.b as f64) / 255.
+    }
+    /**
+     * Sample the color at texture coordinates (u, v), v points up from the bottom of the image.
+     */
+    fn sample_uv(&self, u: f64, v: f64) -> Vector3D {
+        /* position in pixels, the centers of pixels are at .5 */
+        let x = u * self.image.width() as f64;
+        let y = (1. - v) * self.image.height() as f64;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as isize, y.floor() as isize),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as isize, y0 as isize);
+                let top = (1. - tx) * self.texel(x0, y0) + tx * self.texel(x0 + 1, y0);
+                let bottom = (1. - tx) * self.texel(x0, y0 + 1) + tx * self.texel(x0 + 1, y0 + 1);
+                (1. - ty) * top + ty * bottom
+            }
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn sample(&self, coord: &TextureCoord) -> Vector3D {
+        self.sample_uv(coord.uv.0, coord.uv.1)
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{point::Point, vector::Vector3D};
+
+/**
+ * Affine transformation matrix, the last row is always (0, 0, 0, 1).
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Matrix {
+    pub m: [[f64; 4]; 3],
+}
+
+impl Matrix {
+    pub fn translate(v: Vector3D) -> Self {
+        Self {
+            m: [[1., 0., 0., v.x], [0., 1., 0., v.y], [0., 0., 1., v.z]],
+        }
+    }
+    pub fn scale(v: Vector3D) -> Self {
+        Self {
+            m: [[v.x, 0., 0., 0.], [0., v.y, 0., 0.], [0., 0., v.z, 0.]],
+        }
+    }
+    /**
+     * Rotation in radians around the x, y and z axis, applied in this order.
+     */
+    pub fn rotate(v: Vector3D) -> Self {
+        let (sx, cx) = v.x.sin_cos();
+        let (sy, cy) = v.y.sin_cos();
+        let (sz, cz) = v.z.sin_cos();
+        let rx = Self {
+            m: [[1., 0., 0., 0.], [0., cx, -sx, 0.], [0., sx, cx, 0.]],
+        };
+        let ry = Self {
+            m: [[cy, 0., sy, 0.], [0., 1., 0., 0.], [-sy, 0., cy, 0.]],
+        };
+        let rz = Self {
+            m: [[cz, -sz, 0., 0.], [sz, cz, 0., 0.], [0., 0., 1., 0.]],
+        };
+        rz.mul(&ry).mul(&rx)
+    }
+    /**
+     * Matrix product, `rhs` is applied first.
+     */
+    pub fn mul(&self, rhs: &Self) -> Self {
+        let mut m = [[0.; 4]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+            }
+            row[3] += self.m[i][3];
+        }
+        Self { m }
+    }
+    pub fn inverse(&self) -> Self {
+        let m = &self.m;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+
+        let mut inv = [[0.; 4]; 3];
+        inv[0][0] = (m[1][1] * m[2][2] - m[1][2] * m[2][1]) / det;
+        inv[0][1] = (m[0][2] * m[2][1] - m[0][1] * m[2][2]) / det;
+        inv[0][2] = (m[0][1] * m[1][2] - m[0][2] * m[1][1]) / det;
+        inv[1][0] = (m[1][2] * m[2][0] - m[1][0] * m[2][2]) / det;
+        inv[1][1] = (m[0][0] * m[2][2] - m[0][2] * m[2][0]) / det;
+        inv[1][2] = (m[0][2] * m[1][0] - m[0][0] * m[1][2]) / det;
+        inv[2][0] = (m[1][0] * m[2][1] - m[1][1] * m[2][0]) / det;
+        inv[2][1] = (m[0][1] * m[2][0] - m[0][0] * m[2][1]) / det;
+        inv[2][2] = (m[0][0] * m[1][1] - m[0][1] * m[1][0]) / det;
+        /* the inverse translation is -inv * t */
+        for row in &mut inv {
+            row[3] = -(row[0] * m[0][3] + row[1] * m[1][3] + row[2] * m[2][3]);
+        }
+        Self { m: inv }
+    }
+    pub fn apply_vector(&self, v: &Vector3D) -> Vector3D {
+        let m = &self.m;
+        Vector3D::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+    pub fn apply_point(&self, p: &Point) -> Point {
+        let v = self.apply_vector(&p.point_vec);
+        Point::new(v.x + self.m[0][3], v.y + self.m[1][3], v.z + self.m[2][3])
+    }
+    /**
+     * Transform a normal vector, `self` must be the inverse of the matrix applied to points.
+     */
+    pub fn apply_normal(&self, n: &Vector3D) -> Vector3D {
+        let m = &self.m;
+        Vector3D::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+        .unit()
+    }
+}
+
+/**
+ * Transformation keyed by its components, the matrix is `translate * rotate * scale`.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translate: Vector3D,
+    /** rotation around the x, y and z axis in degrees */
+    pub rotate: Vector3D,
+    pub scale: Vector3D,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translate: Vector3D::new(0., 0., 0.),
+            rotate: Vector3D::new(0., 0., 0.),
+            scale: Vector3D::new(1., 1., 1.),
+        }
+    }
+}
+
+impl Transform {
+    pub fn is_identity(&self) -> bool {
+        let identity = Self::default();
+        self.translate == identity.translate
+            && self.rotate == identity.rotate
+            && self.scale == identity.scale
+    }
+    /**
+     * Interpolate each component linearly, `t` is from 0 (self) to 1 (other).
+     */
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        Self {
+            translate: self.translate + t * (other.translate - self.translate),
+            rotate: self.rotate + t * (other.rotate - self.rotate),
+            scale: self.scale + t * (other.scale - self.scale),
+        }
+    }
+    pub fn matrix(&self) -> Matrix {
+        let rotate = Vector3D::new(
+            self.rotate.x.to_radians(),
+            self.rotate.y.to_radians(),
+            self.rotate.z.to_radians(),
+        );
+        Matrix::translate(self.translate)
+            .mul(&Matrix::rotate(rotate))
+            .mul(&Matrix::scale(self.scale))
+    }
+}
@@ -0,0 +1,285 @@
+use crate::vector::Vector3D;
+
+/**
+ * A problem of a polygon found while splitting it into triangles.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Defect {
+    /** the polygon has no area, it produces no triangles */
+    Degenerate,
+    /** edges of the polygon cross each other, the triangles may not match its shape */
+    SelfIntersecting,
+}
+
+/** areas below this fraction of the squared perimeter are treated as zero */
+const EPSILON: f64 = 1e-10;
+
+/**
+ * Newell's normal of a polygon, its module is twice the area of the polygon.
+ */
+pub fn newell_normal<I>(points: I) -> Vector3D
+where
+    I: IntoIterator<Item = Vector3D>,
+    I::IntoIter: Clone,
+{
+    let points = points.into_iter();
+    let next = points.clone().cycle().skip(1);
+    let mut normal = Vector3D::new(0., 0., 0.);
+    for (cur, next) in points.zip(next) {
+        normal.x += (cur.y - next.y) * (cur.z + next.z);
+        normal.y += (cur.z - next.z) * (cur.x + next.x);
+        normal.z += (cur.x - next.x) * (cur.y + next.y);
+    }
+    normal
+}
+
+/**
+ * Buffers of `triangulate`, they are reused between polygons.
+ */
+#[derive(Default)]
+pub struct Scratch {
+    /** the points on the plane of the polygon */
+    projected: Vec<(f64, f64)>,
+    /** the points which are not cut off yet */
+    remaining: Vec<usize>,
+}
+
+/** twice the signed area of a 2D triangle, positive if counterclockwise */
+fn cross(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+/**
+ * Check if two edges of a polygon cross each other, touching edges don't count.
+ */
+fn edges_cross(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    let (d1, d2) = (cross(a, b, c), cross(a, b, d));
+    let (d3, d4) = (cross(c, d, a), cross(c, d, b));
+    d1 * d2 < 0. && d3 * d4 < 0.
+}
+
+fn self_intersects(points: &[(f64, f64)]) -> bool {
+    let n = points.len();
+    (0..n).any(|i| {
+        /* edges sharing a vertex are skipped */
+        (i + 2..n).filter(|j| (j + 1) % n != i).any(|j| {
+            edges_cross(
+                points[i],
+                points[(i + 1) % n],
+                points[j],
+                points[(j + 1) % n],
+            )
+        })
+    })
+}
+
+/**
+ * Split a polygon into triangles by ear clipping, the triangles are pushed as indexes of `points`.
+ *
+ * The polygon is projected onto its best-fit plane, so concave and slightly non-planar polygons
+ * are split along their shape. Degenerate polygons produce no triangles, self-intersecting
+ * polygons are still split but may be covered only partly.
+ */
+pub fn triangulate(
+    points: &[Vector3D],
+    triangles: &mut Vec<[usize; 3]>,
+    scratch: &mut Scratch,
+) -> Option<Defect> {
+    let n = points.len();
+    let normal = newell_normal(points.iter().copied());
+    let perimeter: f64 = (0..n)
+        .map(|i| (points[(i + 1) % n] - points[i]).module())
+        .sum();
+    if n < 3 || normal.module() <= EPSILON * perimeter * perimeter {
+        return Some(Defect::Degenerate);
+    }
+    if n == 3 {
+        triangles.push([0, 1, 2]);
+        return None;
+    }
+
+    /* coordinates on the plane, the polygon runs counterclockwise around the normal */
+    let normal = normal.unit();
+    let (u, v) = normal.basis();
+    let Scratch {
+        projected,
+        remaining,
+    } = scratch;
+    projected.clear();
+    projected.extend(points.iter().map(|p| (p.cdot(&u), p.cdot(&v))));
+    let defect = self_intersects(projected).then_some(Defect::SelfIntersecting);
+
+    remaining.clear();
+    remaining.extend(0..n);
+    let mut start = 0;
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let corner = |k: usize| {
+            (
+                remaining[(k + m - 1) % m],
+                remaining[k],
+                remaining[(k + 1) % m],
+            )
+        };
+        let is_ear = |k: usize| {
+            let (a, b, c) = corner(k);
+            let (pa, pb, pc) = (projected[a], projected[b], projected[c]);
+            cross(pa, pb, pc) > 0.
+                && !remaining.iter().any(|&i| {
+                    let p = projected[i];
+                    /* duplicated positions of the corners don't block the ear */
+                    p != pa
+                        && p != pb
+                        && p != pc
+                        && cross(pa, pb, p) >= 0.
+                        && cross(pb, pc, p) >= 0.
+                        && cross(pc, pa, p) >= 0.
+                })
+        };
+        /* the search goes on from the last ear, without an ear the most convex corner is cut */
+        let ear = (0..m)
+            .map(|k| (start + k) % m)
+            .find(|k| is_ear(*k))
+            .unwrap_or_else(|| {
+                (0..m)
+                    .max_by(|x, y| {
+                        let (a, b, c) = corner(*x);
+                        let (d, e, f) = corner(*y);
+                        cross(projected[a], projected[b], projected[c]).total_cmp(&cross(
+                            projected[d],
+                            projected[e],
+                            projected[f],
+                        ))
+                    })
+                    .unwrap()
+            });
+        let (a, b, c) = corner(ear);
+        triangles.push([a, b, c]);
+        remaining.remove(ear);
+        start = ear % remaining.len();
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    defect
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon(points: &[(f64, f64)]) -> Vec<Vector3D> {
+        points
+            .iter()
+            .map(|&(x, y)| Vector3D::new(x, y, 0.))
+            .collect()
+    }
+
+    /** the triangles of a polygon, checking that they cover it once with the same orientation */
+    fn split(points: &[Vector3D]) -> (Vec<[usize; 3]>, Option<Defect>) {
+        let mut triangles = Vec::new();
+        let defect = triangulate(points, &mut triangles, &mut Scratch::default());
+        if defect.is_none() {
+            let normal = newell_normal(points.iter().copied());
+            assert_eq!(triangles.len(), points.len() - 2, "{triangles:?}");
+            let areas = triangles.iter().map(|&[a, b, c]| {
+                ((points[b] - points[a]) * (points[c] - points[a])).cdot(&normal.unit())
+            });
+            for area in areas.clone() {
+                assert!(area >= 0., "{triangles:?}");
+            }
+            assert!(
+                (areas.sum::<f64>() - normal.module()).abs() < 1e-9,
+                "{triangles:?}"
+            );
+        }
+        (triangles, defect)
+    }
+
+    #[test]
+    fn concave_polygons() {
+        /* a square with a notch at (1, 1) */
+        let arrow = [(0., 0.), (2., 0.), (2., 2.), (1., 1.), (0., 2.)];
+        let (triangles, defect) = split(&polygon(&arrow));
+        assert_eq!(defect, None);
+        /* the notch can't be the tip of an ear */
+        assert!(!triangles.contains(&[2, 3, 4]));
+
+        let mut clockwise = arrow;
+        clockwise.reverse();
+        assert_eq!(split(&polygon(&clockwise)).1, None);
+
+        /* a comb with three teeth, in a plane which isn't aligned to the axes */
+        let comb = [
+            (0., 0.),
+            (5., 0.),
+            (5., 2.),
+            (4., 2.),
+            (4., 1.),
+            (3., 1.),
+            (3., 2.),
+            (2., 2.),
+            (2., 1.),
+            (1., 1.),
+            (1., 2.),
+            (0., 2.),
+        ];
+        let tilted = comb
+            .iter()
+            .map(|&(x, y)| Vector3D::new(x, y * 0.6, y * 0.8 + x * 0.1))
+            .collect::<Vec<_>>();
+        assert_eq!(split(&tilted).1, None);
+    }
+
+    #[test]
+    fn collinear_and_duplicate_points() {
+        let (_, defect) = split(&polygon(&[
+            (0., 0.),
+            (1., 0.),
+            (2., 0.),
+            (2., 1.),
+            (0., 1.),
+        ]));
+        assert_eq!(defect, None);
+        let (_, defect) = split(&polygon(&[
+            (0., 0.),
+            (1., 0.),
+            (1., 0.),
+            (1., 1.),
+            (0., 1.),
+            (0., 0.),
+        ]));
+        assert_eq!(defect, None);
+    }
+
+    #[test]
+    fn degenerate_polygons() {
+        let line = polygon(&[(0., 0.), (1., 1.), (2., 2.), (3., 3.)]);
+        let point = polygon(&[(1., 1.); 4]);
+        let edge = polygon(&[(0., 0.), (1., 0.)]);
+        for points in [line, point, edge, Vec::new()] {
+            let (triangles, defect) = split(&points);
+            assert_eq!(defect, Some(Defect::Degenerate));
+            assert!(triangles.is_empty());
+        }
+    }
+
+    #[test]
+    fn self_intersecting_polygons_are_still_split() {
+        /* the edge from (3, 2) to (1, -1) crosses the first edge */
+        let points = polygon(&[(0., 0.), (3., 0.), (3., 2.), (1., -1.), (0., 2.)]);
+        let (triangles, defect) = split(&points);
+        assert_eq!(defect, Some(Defect::SelfIntersecting));
+        assert_eq!(triangles.len(), 3);
+    }
+
+    #[test]
+    fn scratch_is_reused() {
+        let mut scratch = Scratch::default();
+        let mut triangles = Vec::new();
+        let square = polygon(&[(0., 0.), (1., 0.), (1., 1.), (0., 1.)]);
+        let notched = polygon(&[(0., 0.), (2., 0.), (2., 2.), (1., 1.), (0., 2.)]);
+        assert_eq!(triangulate(&notched, &mut triangles, &mut scratch), None);
+        assert_eq!(triangulate(&square, &mut triangles, &mut scratch), None);
+        assert_eq!(triangles.len(), 5);
+        assert!(triangles[3..].iter().flatten().all(|&i| i < 4));
+    }
+}
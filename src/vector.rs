@@ -1,7 +1,7 @@
 use rand::Rng;
-use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub};
+use std::ops::{Add, AddAssign, Div, Index, Mul, Neg, Sub};
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Vector3D {
     pub x: f64,
     pub y: f64,
@@ -12,6 +12,9 @@ impl Vector3D {
     pub fn new(x: f64, y: f64, z: f64) -> Self {
         Self { x, y, z }
     }
+    pub fn from_obj(n: &obj::vertex::Normal) -> Self {
+        Self::new(n.x, n.y, n.z)
+    }
     /**
      * Generate a random unit vector
      */
@@ -42,6 +45,19 @@ impl Vector3D {
     pub fn unit(&self) -> Self {
         *self / self.module()
     }
+    /**
+     * Two unit vectors perpendicular to this unit vector and to each other,
+     * with this vector they form a right-handed basis.
+     */
+    pub fn basis(&self) -> (Self, Self) {
+        let axis = if self.x.abs() < 0.9 {
+            Vector3D::new(1., 0., 0.)
+        } else {
+            Vector3D::new(0., 1., 0.)
+        };
+        let u = (axis * *self).unit();
+        (u, *self * u)
+    }
 }
 
 impl From<(f64, f64, f64)> for Vector3D {
@@ -145,3 +161,18 @@ impl Neg for Vector3D {
         -1. * self
     }
 }
+
+/**
+ * Get a component by axis, 0 is x, 1 is y and 2 is z.
+ */
+impl Index<usize> for Vector3D {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("axis {axis} out of range"),
+        }
+    }
+}
@@ -1,124 +1,341 @@
-use crate::{point::Point, ray::Ray, vector::Vector3D};
+use crate::{
+    camera::{
+        Camera, Cubemap, Equirectangular, Eye, Fisheye, Orthographic, Perspective, Projection,
+        Stereo,
+    },
+    point::Point,
+    ray::Ray,
+    vector::Vector3D,
+};
+
+/** default width of camera sensor in millimeters (full frame) */
+pub const DEFAULT_SENSOR_WIDTH: f64 = 36.;
+
+/**
+ * Position and orientation of a camera.
+ */
+#[derive(Clone, Copy)]
+pub struct Frame {
+    pub origin: Point,
+    /** unit vector pointing from the eye to the target */
+    pub forward: Vector3D,
+    /** unit vector pointing to the right side of the image */
+    pub right: Vector3D,
+    /** unit vector pointing to the top of the image */
+    pub up: Vector3D,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self {
+            origin: Point::default(),
+            forward: Vector3D::new(0., 0., -1.),
+            right: Vector3D::new(1., 0., 0.),
+            up: Vector3D::new(0., 1., 0.),
+        }
+    }
+}
+
+impl Frame {
+    /**
+     * forward: the viewing direction, up: the approximate up direction,
+     * roll: rotation around `forward` in radians.
+     */
+    pub fn new(origin: Point, forward: Vector3D, up: Vector3D, roll: f64) -> Self {
+        let forward = forward.unit();
+        let right = (forward * up).unit();
+        let up = (right * forward).unit();
+
+        /* rotate the frame clockwise around the viewing direction */
+        let (sin, cos) = roll.sin_cos();
+        let (right, up) = (cos * right - sin * up, cos * up + sin * right);
+
+        Self {
+            origin,
+            forward,
+            right,
+            up,
+        }
+    }
+    /**
+     * Interpolate between two frames, `t` is from 0 (self) to 1 (other).
+     */
+    pub fn lerp(&self, other: &Self, t: f64) -> Self {
+        let forward = self.forward + t * (other.forward - self.forward);
+        let up = self.up + t * (other.up - self.up);
+        let mut frame = Self::new(Point::default(), forward, up, 0.);
+        frame.origin = Point::from_vec3d(
+            self.origin.point_vec + t * (other.origin.point_vec - self.origin.point_vec),
+        );
+        frame
+    }
+    /**
+     * Transform a vector from camera space to world space.
+     */
+    fn camera_to_world(&self, v: Vector3D) -> Vector3D {
+        v.x * self.right + v.y * self.up + v.z * self.forward
+    }
+}
 
-#[derive(Default)]
 pub struct Viewport {
     pub pixel_x: usize,
     pub pixel_y: usize,
 
-    pub origin: Point,
-    pub at: Vector3D,
-    pub top: Vector3D,
-    pub left: Vector3D,
+    /** the camera frame when the shutter opens */
+    pub frame: Frame,
+    /** the camera frame when the shutter closes, if the camera moves */
+    pub frame_end: Option<Frame>,
+    /** shutter open and close time */
+    pub shutter: (f64, f64),
 
-    unit: f64,
+    camera: Box<dyn Camera>,
+    pub stereo: Option<Stereo>,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            pixel_x: 0,
+            pixel_y: 0,
+            frame: Frame::default(),
+            frame_end: None,
+            shutter: (0., 0.),
+            camera: Box::new(Perspective {
+                half_width: 1.,
+                half_height: 1.,
+            }),
+            stereo: None,
+        }
+    }
 }
 
 impl Viewport {
     /**
-     * at: the direction of eye. (NOTE: this is not a unit vector, the module of `at` is focal distance.)
+     * Create a camera, `camera` is the projection in camera space.
      */
-    pub fn new(
-        width: f64,
-        height: f64,
-        pixel_x: usize,
-        pixel_y: usize,
-        origin: Point,
-        at: Vector3D,
-        scale: f64,
-    ) -> Self {
-        let top = {
-            let at_u = at.unit();
-            let top = height / 2.
-                * Vector3D::new(-at_u.x * at_u.y, -at_u.y.powi(2) + 1., -at_u.z * at_u.y).unit();
-            let right = (at * top).unit();
-            top + scale.tan() * top.module() * right.unit()
-        };
-        let left = width / 2. * -(at * top).unit();
-
+    pub fn new(pixel_x: usize, pixel_y: usize, frame: Frame, camera: Box<dyn Camera>) -> Self {
         Self {
             pixel_x,
             pixel_y,
-            origin,
-            at,
-            top,
-            left,
-            unit: if (width / pixel_x as f64) < height / pixel_y as f64 {
-                width / pixel_x as f64
-            } else {
-                height / pixel_y as f64
-            },
+            frame,
+            camera,
+            ..Default::default()
         }
     }
-    pub fn get_ray_central(&self, x: usize, y: usize) -> Ray {
-        let x = if x > self.pixel_x {
-            (self.pixel_x / 2) as isize - (x - self.pixel_x) as isize
-        } else {
-            (self.pixel_x / 2) as isize - x as isize
-        };
-        let y = if y > self.pixel_y {
-            (self.pixel_y / 2) as isize - (y - self.pixel_y) as isize
-        } else {
-            (self.pixel_y / 2) as isize - y as isize
-        };
-        let x_vec = x as f64 / (self.pixel_x as f64 / 2.) * self.left;
-        let y_vec = y as f64 / (self.pixel_y as f64 / 2.) * self.top;
-        let direction = self.at + x_vec + y_vec;
-        Ray::new(self.origin, direction)
-    }
-    pub fn get_ray_random(&self, x: usize, y: usize) -> Ray {
-        let x = if x > self.pixel_x {
-            (self.pixel_x / 2) as isize - (x - self.pixel_x) as isize
-        } else {
-            (self.pixel_x / 2) as isize - x as isize
-        };
-        let y = if y > self.pixel_y {
-            (self.pixel_y / 2) as isize - (y - self.pixel_y) as isize
-        } else {
-            (self.pixel_y / 2) as isize - y as isize
-        };
-        let x_vec = x as f64 / (self.pixel_x as f64 / 2.) * self.left;
-        let y_vec = y as f64 / (self.pixel_y as f64 / 2.) * self.top;
-
-        let direction = self.at + x_vec + y_vec + self.unit * Vector3D::new_random_unit();
-        Ray::new(self.origin, direction)
+    /**
+     * Get the camera frame at the given time.
+     */
+    fn frame_at(&self, time: f64) -> Frame {
+        match &self.frame_end {
+            Some(end) if self.shutter.1 > self.shutter.0 => self.frame.lerp(
+                end,
+                ((time - self.shutter.0) / (self.shutter.1 - self.shutter.0)).clamp(0., 1.),
+            ),
+            _ => self.frame,
+        }
+    }
+    /**
+     * Generate a ray passing through the given position on the image plane.
+     *
+     * (x, y) are pixel coordinates, which may be fractional.
+     * Returns `None` if the position is not covered by the projection.
+     */
+    fn get_ray(&self, x: f64, y: f64, time: f64, eye: Eye) -> Option<Ray> {
+        let u = 2. * x / self.pixel_x as f64 - 1.;
+        let v = 1. - 2. * y / self.pixel_y as f64;
+        let (mut origin, mut direction) = self.camera.project(u, v)?;
+        if let Some(stereo) = &self.stereo {
+            (origin, direction) = stereo.apply(eye, origin, direction);
+        }
+        let frame = self.frame_at(time);
+        Some(Ray::new(
+            Point::from_vec3d(frame.origin.point_vec + frame.camera_to_world(origin)),
+            frame.camera_to_world(direction),
+            time,
+        ))
+    }
+    pub fn get_ray_central(&self, x: usize, y: usize, eye: Eye) -> Option<Ray> {
+        self.get_ray(x as f64 + 0.5, y as f64 + 0.5, self.shutter.0, eye)
+    }
+    pub fn get_ray_random(&self, x: usize, y: usize, eye: Eye) -> Option<Ray> {
+        let (open, close) = self.shutter;
+        self.get_ray(
+            x as f64 + rand::random::<f64>(),
+            y as f64 + rand::random::<f64>(),
+            open + (close - open) * rand::random::<f64>(),
+            eye,
+        )
     }
 }
 
-#[derive(Default)]
 pub struct ViewportBuilder {
-    width: f64,
-    height: f64,
     pixel_x: usize,
     pixel_y: usize,
 
     origin: Point,
     at: Vector3D,
-    scale: f64,
+    target: Option<Point>,
+    origin_end: Option<Point>,
+    target_end: Option<Point>,
+    shutter: (f64, f64),
+    up: Vector3D,
+    fov: Option<f64>,
+    projection: Projection,
+    ortho_height: f64,
+    focal_length: Option<f64>,
+    sensor_width: f64,
+    roll: f64,
+    stereo: Option<Stereo>,
+}
+
+impl Default for ViewportBuilder {
+    fn default() -> Self {
+        Self {
+            pixel_x: 0,
+            pixel_y: 0,
+            origin: Point::default(),
+            at: Vector3D::new(0., 0., -1.),
+            target: None,
+            origin_end: None,
+            target_end: None,
+            shutter: (0., 0.),
+            up: Vector3D::new(0., 1., 0.),
+            fov: None,
+            projection: Projection::default(),
+            ortho_height: 2.,
+            focal_length: None,
+            sensor_width: DEFAULT_SENSOR_WIDTH,
+            roll: 0.,
+            stereo: None,
+        }
+    }
 }
 
 impl ViewportBuilder {
     pub fn build(self) -> Viewport {
-        Viewport::new(
-            self.width,
-            self.height,
+        let forward = match self.target {
+            Some(target) => self.origin.to_vec3d(&target),
+            None => self.at,
+        };
+        let aspect = self.pixel_x as f64 / self.pixel_y as f64;
+        let fov = match (self.fov, self.focal_length) {
+            (Some(fov), _) => fov,
+            /* fisheye lenses default to a half sphere */
+            (None, None)
+                if self.projection == Projection::FisheyeEquidistant
+                    || self.projection == Projection::FisheyeEquisolid =>
+            {
+                std::f64::consts::PI
+            }
+            /* sensor width fits the horizontal extent of the image */
+            (None, Some(focal_length)) => {
+                2. * (self.sensor_width / 2. / focal_length / aspect).atan()
+            }
+            /* legacy behaviour: the image plane is 2 units high at the distance of `at` */
+            (None, None) => 2. * (1. / self.at.module()).atan(),
+        };
+
+        let camera: Box<dyn Camera> = match self.projection {
+            Projection::Perspective => {
+                let half_height = (fov / 2.).tan();
+                Box::new(Perspective {
+                    half_width: half_height * aspect,
+                    half_height,
+                })
+            }
+            Projection::Orthographic => Box::new(Orthographic {
+                half_width: self.ortho_height / 2. * aspect,
+                half_height: self.ortho_height / 2.,
+            }),
+            Projection::FisheyeEquidistant => Box::new(Fisheye {
+                fov,
+                aspect,
+                equisolid: false,
+            }),
+            Projection::FisheyeEquisolid => Box::new(Fisheye {
+                fov,
+                aspect,
+                equisolid: true,
+            }),
+            Projection::Equirectangular => Box::new(Equirectangular),
+            Projection::Cubemap => Box::new(Cubemap),
+        };
+
+        let mut viewport = Viewport::new(
             self.pixel_x,
             self.pixel_y,
-            self.origin,
-            self.at,
-            self.scale,
-        )
+            Frame::new(self.origin, forward, self.up, self.roll),
+            camera,
+        );
+        viewport.stereo = self.stereo;
+        viewport.shutter = self.shutter;
+        if self.origin_end.is_some() || self.target_end.is_some() {
+            let origin_end = self.origin_end.unwrap_or(self.origin);
+            let forward_end = match self.target_end.or(self.target) {
+                Some(target) => origin_end.to_vec3d(&target),
+                None => self.at,
+            };
+            viewport.frame_end = Some(Frame::new(origin_end, forward_end, self.up, self.roll));
+        }
+        viewport
     }
+    /**
+     * Set the viewing direction. (NOTE: this is not a unit vector, the module of `at` is focal distance.)
+     */
     pub fn at(mut self, at: Vector3D) -> Self {
         self.at = at;
         self
     }
+    /** Look at a point, this overrides `at` */
+    pub fn target(mut self, target: Option<Point>) -> Self {
+        self.target = target;
+        self
+    }
     pub fn origin(mut self, origin: Point) -> Self {
         self.origin = origin;
         self
     }
-    pub fn area(mut self, width: f64, height: f64) -> Self {
-        self.width = width;
-        self.height = height;
+    /** Position of the camera when the shutter closes */
+    pub fn origin_end(mut self, origin_end: Option<Point>) -> Self {
+        self.origin_end = origin_end;
+        self
+    }
+    /** Target of the camera when the shutter closes */
+    pub fn target_end(mut self, target_end: Option<Point>) -> Self {
+        self.target_end = target_end;
+        self
+    }
+    /** Shutter open and close time */
+    pub fn shutter(mut self, shutter: (f64, f64)) -> Self {
+        self.shutter = shutter;
+        self
+    }
+    pub fn up(mut self, up: Vector3D) -> Self {
+        self.up = up;
+        self
+    }
+    /** Vertical field of view in radians */
+    pub fn fov(mut self, fov: Option<f64>) -> Self {
+        self.fov = fov;
+        self
+    }
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+    /** Height of the view volume of orthographic cameras in world units */
+    pub fn ortho_height(mut self, ortho_height: f64) -> Self {
+        self.ortho_height = ortho_height;
+        self
+    }
+    /** Focal length in millimeters, only used when no field of view is given */
+    pub fn focal_length(mut self, focal_length: Option<f64>) -> Self {
+        self.focal_length = focal_length;
+        self
+    }
+    /** Sensor width in millimeters */
+    pub fn sensor_width(mut self, sensor_width: f64) -> Self {
+        self.sensor_width = sensor_width;
         self
     }
     pub fn size(mut self, size: (usize, usize)) -> Self {
@@ -126,8 +343,13 @@ impl ViewportBuilder {
         self.pixel_y = size.1;
         self
     }
-    pub fn scale(mut self, scale: f64) -> Self {
-        self.scale = scale;
+    pub fn stereo(mut self, stereo: Option<Stereo>) -> Self {
+        self.stereo = stereo;
+        self
+    }
+    /** Rotation around the viewing direction in radians */
+    pub fn roll(mut self, roll: f64) -> Self {
+        self.roll = roll;
         self
     }
 }